@@ -0,0 +1,119 @@
+// Latency benchmarks for each pipeline stage, run with `cargo bench -p aira_brain`.
+//
+// Stages run only when their model is configured:
+//   AIRA_BENCH_STT_MODEL   Whisper ggml model, transcribes fixtures/clip.wav
+//   AIRA_BENCH_LLM_MODEL   GGUF model, answers fixtures/prompt.txt
+//   AIRA_BENCH_TTS_MODEL   Piper .onnx.json config, speaks fixtures/sentence.txt
+//   AIRA_BENCH_ITERATIONS  Timed runs per stage after one warm-up (default: 3)
+
+use std::env;
+use std::time::{Duration, Instant};
+
+use aira_brain::audio::{downmix, resample};
+use aira_brain::config::default_system_prompt;
+use aira_brain::{DEFAULT_ASSISTANT_NAME, LlmEngine, SttEngine, TtsEngine};
+use anyhow::{Context, Result};
+
+const CLIP_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/fixtures/clip.wav");
+const PROMPT: &str = include_str!("fixtures/prompt.txt");
+const SENTENCE: &str = include_str!("fixtures/sentence.txt");
+
+const STT_SAMPLE_RATE: u32 = 16_000;
+
+fn main() -> Result<()> {
+    let iterations = env::var("AIRA_BENCH_ITERATIONS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3usize)
+        .max(1);
+
+    match env::var("AIRA_BENCH_STT_MODEL") {
+        Ok(path) => bench_stt(&path, iterations)?,
+        Err(_) => println!("stt: skipped (set AIRA_BENCH_STT_MODEL)"),
+    }
+    match env::var("AIRA_BENCH_LLM_MODEL") {
+        Ok(path) => bench_llm(&path, iterations)?,
+        Err(_) => println!("llm: skipped (set AIRA_BENCH_LLM_MODEL)"),
+    }
+    match env::var("AIRA_BENCH_TTS_MODEL") {
+        Ok(path) => bench_tts(&path, iterations)?,
+        Err(_) => println!("tts: skipped (set AIRA_BENCH_TTS_MODEL)"),
+    }
+
+    Ok(())
+}
+
+// Read the fixture clip as 16 kHz mono, the format Whisper expects
+fn load_clip() -> Result<Vec<f32>> {
+    let mut reader = hound::WavReader::open(CLIP_PATH)
+        .with_context(|| format!("Failed to open fixture {}", CLIP_PATH))?;
+    let spec = reader.spec();
+    let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => reader
+            .samples::<i32>()
+            .map(|s| s.map(|s| s as f32 / scale))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    let mono = downmix(&samples, spec.channels);
+    Ok(resample(mono, spec.sample_rate, STT_SAMPLE_RATE))
+}
+
+// Time `run` once to warm up, then `iterations` more times
+fn time_runs<T>(iterations: usize, mut run: impl FnMut() -> Result<T>) -> Result<(Duration, T)> {
+    let mut last = run()?;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        last = run()?;
+    }
+    Ok((start.elapsed() / iterations as u32, last))
+}
+
+fn bench_stt(model_path: &str, iterations: usize) -> Result<()> {
+    let stt = SttEngine::load(model_path)?;
+    let clip = load_clip()?;
+    let clip_secs = clip.len() as f64 / STT_SAMPLE_RATE as f64;
+
+    let (elapsed, text) = time_runs(iterations, || stt.transcribe(&clip))?;
+    println!(
+        "stt: {:.1} ms for {:.1}s of audio ({:.2}x real time) -> {:?}",
+        elapsed.as_secs_f64() * 1000.0,
+        clip_secs,
+        elapsed.as_secs_f64() / clip_secs,
+        text.trim()
+    );
+    Ok(())
+}
+
+fn bench_llm(model_path: &str, iterations: usize) -> Result<()> {
+    let mut llm = LlmEngine::load(model_path, &default_system_prompt(DEFAULT_ASSISTANT_NAME))?;
+
+    let (elapsed, metrics) = time_runs(iterations, || {
+        // Every run answers the same prompt from an empty history
+        llm.clear_history();
+        llm.ask(PROMPT.trim(), |_| Ok(()))
+    })?;
+    println!(
+        "llm: {:.1} tokens/sec, {:.1} ms per reply",
+        metrics.tokens_per_second,
+        elapsed.as_secs_f64() * 1000.0
+    );
+    Ok(())
+}
+
+fn bench_tts(config_path: &str, iterations: usize) -> Result<()> {
+    let tts = TtsEngine::load(config_path)?;
+    let sample_rate = tts.sample_rate() as f64;
+
+    let (elapsed, audio) = time_runs(iterations, || tts.synthesize(SENTENCE.trim()))?;
+    let audio_secs = audio.len() as f64 / sample_rate;
+    println!(
+        "tts: {:.1} ms for {:.1}s of speech (real-time factor {:.3})",
+        elapsed.as_secs_f64() * 1000.0,
+        audio_secs,
+        elapsed.as_secs_f64() / audio_secs.max(f64::EPSILON)
+    );
+    Ok(())
+}
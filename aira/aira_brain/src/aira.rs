@@ -1,7 +1,81 @@
-use crate::{llm::LlmEngine, stt::SttEngine, tts::TtsEngine};
+use crate::{
+    llm::{
+        ChatMessage, CompletionBackend, GenerationConfig, GenerationMetrics, LlmEngine,
+        ResponseLength, TokenEstimate,
+    },
+    observer::{ExchangeObserver, NoopObserver},
+    stt::SttEngine,
+    tts::TtsEngine,
+};
 use anyhow::Result;
+use std::fmt;
 use std::sync::{Arc, Mutex};
 
+// Error returned when an engine that was left out of the build is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AiraError {
+    SttNotConfigured,
+    TtsNotConfigured,
+}
+
+impl fmt::Display for AiraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AiraError::SttNotConfigured => write!(f, "Speech-to-text is not configured"),
+            AiraError::TtsNotConfigured => write!(f, "Text-to-speech is not configured"),
+        }
+    }
+}
+
+impl std::error::Error for AiraError {}
+
+// One of the models Aira runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineKind {
+    Llm,
+    Stt,
+    Tts,
+}
+
+impl fmt::Display for EngineKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineKind::Llm => write!(f, "LLM"),
+            EngineKind::Stt => write!(f, "speech-to-text"),
+            EngineKind::Tts => write!(f, "text-to-speech"),
+        }
+    }
+}
+
+// Error returned when bringing back an unloaded model fails, naming the model
+#[derive(Debug)]
+pub struct ReloadError {
+    pub engine: EngineKind,
+    source: anyhow::Error,
+}
+
+impl ReloadError {
+    fn new(engine: EngineKind, source: anyhow::Error) -> Self {
+        Self { engine, source }
+    }
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Failed to reload the {} model: {}",
+            self.engine, self.source
+        )
+    }
+}
+
+impl std::error::Error for ReloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
 // Emotional context for adaptive responses
 #[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub struct EmotionalContext {
@@ -15,13 +89,173 @@ pub struct EmotionalContext {
     pub positive_affect: f32,
     // Timestamp of last update
     pub timestamp: u64,
+    // Whether the camera saw a face; without one the metrics say nothing about the user
+    #[serde(default = "default_face_present")]
+    pub face_present: bool,
+}
+
+fn default_face_present() -> bool {
+    true
+}
+
+// A reading that can make an emotion dominant, as named in AIRA_EMOTION_PRIORITY
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Emotion {
+    Fatigue,
+    Stress,
+    Positive,
+    // High engagement reads as focused, low as disengaged
+    Engagement,
+}
+
+impl Emotion {
+    const ALL: [Emotion; 4] = [
+        Emotion::Fatigue,
+        Emotion::Stress,
+        Emotion::Positive,
+        Emotion::Engagement,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Emotion::Fatigue => "fatigue",
+            Emotion::Stress => "stress",
+            Emotion::Positive => "positive",
+            Emotion::Engagement => "engagement",
+        }
+    }
+}
+
+// The state a reading is dominated by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DominantEmotion {
+    Fatigued,
+    Stressed,
+    Happy,
+    Engaged,
+    Disengaged,
+    Neutral,
+}
+
+impl DominantEmotion {
+    // How the user appears, as told to the model
+    pub fn description(self) -> &'static str {
+        match self {
+            DominantEmotion::Fatigued => "fatigued and low-energy",
+            DominantEmotion::Stressed => "stressed or tense",
+            DominantEmotion::Happy => "happy and positive",
+            DominantEmotion::Engaged => "focused and engaged",
+            DominantEmotion::Disengaged => "disengaged or distracted",
+            DominantEmotion::Neutral => "neutral",
+        }
+    }
+
+    // Interaction style suggested to the model
+    fn recommendation(self) -> &'static str {
+        match self {
+            DominantEmotion::Fatigued => {
+                "Be supportive and gentle. Suggest taking a break if appropriate. Keep responses concise."
+            }
+            DominantEmotion::Stressed => {
+                "Be calming and reassuring. Break complex topics into manageable pieces. Offer practical help."
+            }
+            DominantEmotion::Disengaged => {
+                "Be engaging and interesting. Use questions to draw them in. Add relevant examples or stories."
+            }
+            DominantEmotion::Happy => {
+                "Match their energy! Be warm and enthusiastic. Build on their positive momentum."
+            }
+            DominantEmotion::Engaged | DominantEmotion::Neutral => {
+                "Maintain a balanced, helpful tone. Adapt based on conversation flow."
+            }
+        }
+    }
+}
+
+// Order emotions are checked in; the first one past its threshold is dominant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct EmotionPriority([Emotion; 4]);
+
+impl Default for EmotionPriority {
+    // Fatigue, then stress, positive affect and engagement
+    fn default() -> Self {
+        Self(Emotion::ALL)
+    }
+}
+
+impl EmotionPriority {
+    // Comma-separated emotions, most important first, e.g. "stress,fatigue"
+    // Emotions left out follow the listed ones in their default order
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut order = Vec::with_capacity(Emotion::ALL.len());
+        for name in value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            let emotion = Emotion::ALL
+                .into_iter()
+                .find(|emotion| emotion.name().eq_ignore_ascii_case(name))
+                .ok_or_else(|| {
+                    format!(
+                        "unknown emotion {:?} (expected fatigue, stress, positive or engagement)",
+                        name
+                    )
+                })?;
+            if order.contains(&emotion) {
+                return Err(format!("{} is listed twice", emotion.name()));
+            }
+            order.push(emotion);
+        }
+        let rest: Vec<Emotion> = Emotion::ALL
+            .into_iter()
+            .filter(|emotion| !order.contains(emotion))
+            .collect();
+        order.extend(rest);
+
+        let mut priority = Emotion::ALL;
+        priority.copy_from_slice(&order);
+        Ok(Self(priority))
+    }
+
+    pub fn order(&self) -> [Emotion; 4] {
+        self.0
+    }
+
+    // The first emotion in this order whose reading crosses its threshold
+    pub fn dominant(&self, context: &EmotionalContext) -> DominantEmotion {
+        self.0
+            .iter()
+            .find_map(|emotion| match emotion {
+                Emotion::Fatigue => (context.fatigue > 0.7).then_some(DominantEmotion::Fatigued),
+                Emotion::Stress => (context.stress > 0.6).then_some(DominantEmotion::Stressed),
+                Emotion::Positive => {
+                    (context.positive_affect > 0.6).then_some(DominantEmotion::Happy)
+                }
+                Emotion::Engagement if context.engagement > 0.7 => Some(DominantEmotion::Engaged),
+                Emotion::Engagement if context.engagement < 0.3 => {
+                    Some(DominantEmotion::Disengaged)
+                }
+                Emotion::Engagement => None,
+            })
+            .unwrap_or(DominantEmotion::Neutral)
+    }
 }
 
 impl EmotionalContext {
     // Convert emotional context to human-readable format for LLM injection
-    pub fn to_llm_context(&self) -> String {
-        let dominant_emotion = self.get_dominant_emotion();
-        let recommendations = self.get_recommendations();
+    // `priority` decides the dominant emotion when several readings are high
+    pub fn to_llm_context(&self, priority: &EmotionPriority) -> String {
+        // No face may just mean the camera is off, which says nothing about engagement
+        if !self.face_present {
+            return "The user is not visible on camera, so their emotional state is unknown.\n\n\
+                Recommended approach: Don't assume they are distracted or disengaged. \
+                Respond to what they say."
+                .to_string();
+        }
+
+        let dominant = priority.dominant(self);
 
         format!(
             "The user appears {} ({:.0}% confidence).\n\
@@ -31,35 +265,42 @@ impl EmotionalContext {
             - Stress: {:.0}%\n\
             - Positive affect: {:.0}%\n\n\
             Recommended approach: {}",
-            dominant_emotion,
+            dominant.description(),
             self.get_confidence() * 100.0,
             self.fatigue * 100.0,
             self.engagement * 100.0,
             self.stress * 100.0,
             self.positive_affect * 100.0,
-            recommendations
+            dominant.recommendation()
         )
     }
 
-    // Get dominant emotion as a string
-    fn get_dominant_emotion(&self) -> &'static str {
-        if self.fatigue > 0.7 {
-            "fatigued and low-energy"
-        } else if self.stress > 0.6 {
-            "stressed or tense"
-        } else if self.positive_affect > 0.6 {
-            "happy and positive"
-        } else if self.engagement > 0.7 {
-            "focused and engaged"
-        } else if self.engagement < 0.3 {
-            "disengaged or distracted"
-        } else {
-            "neutral"
-        }
+    // Whether `other` reads as a different state: another dominant emotion, a face
+    // appearing or leaving, or any metric moving more than `threshold`
+    fn differs_from(
+        &self,
+        other: &EmotionalContext,
+        threshold: f32,
+        priority: &EmotionPriority,
+    ) -> bool {
+        let drift = [
+            (self.fatigue - other.fatigue).abs(),
+            (self.engagement - other.engagement).abs(),
+            (self.stress - other.stress).abs(),
+            (self.positive_affect - other.positive_affect).abs(),
+        ];
+
+        self.face_present != other.face_present
+            || priority.dominant(self) != priority.dominant(other)
+            || drift.iter().any(|d| *d > threshold)
     }
 
-    // Get confidence level of emotional detection
-    fn get_confidence(&self) -> f32 {
+    // Confidence of the reading (0.5 - 1.0), or 0.0 when no face was seen
+    pub fn get_confidence(&self) -> f32 {
+        if !self.face_present {
+            return 0.0;
+        }
+
         // Higher variance in metrics = lower confidence
         let values = [
             self.fatigue,
@@ -73,72 +314,612 @@ impl EmotionalContext {
         // Lower variance = higher confidence
         (1.0 - variance).clamp(0.5, 1.0)
     }
+}
 
-    // Get recommendations for interaction style
-    fn get_recommendations(&self) -> &'static str {
-        if self.fatigue > 0.7 {
-            "Be supportive and gentle. Suggest taking a break if appropriate. Keep responses concise."
-        } else if self.stress > 0.6 {
-            "Be calming and reassuring. Break complex topics into manageable pieces. Offer practical help."
-        } else if self.engagement < 0.3 {
-            "Be engaging and interesting. Use questions to draw them in. Add relevant examples or stories."
-        } else if self.positive_affect > 0.6 {
-            "Match their energy! Be warm and enthusiastic. Build on their positive momentum."
-        } else {
-            "Maintain a balanced, helpful tone. Adapt based on conversation flow."
-        }
-    }
+// One completed exchange with what shaped and measured it, for exporting a session
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TurnRecord {
+    pub user: String,
+    pub reply: String,
+    pub metrics: Option<GenerationMetrics>,
+    // Emotional context the reply's prompt carried
+    pub emotion: Option<EmotionalContext>,
+    // Length of the spoken reply, once the caller has synthesized it
+    pub audio_duration_secs: Option<f32>,
+}
+
+// Notice that a conversation hit its turn limit and started over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct SessionReset {
+    // Exchanges the ended conversation had
+    pub turns: usize,
+    // True when the new one starts from a summary of it, written before the next reply
+    pub summarized: bool,
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
+// Default metric drift (0.0 - 1.0) that counts as a changed emotional state
+pub const DEFAULT_EMOTION_CHANGE_THRESHOLD: f32 = 0.1;
+
+// Most turn records kept for export; the oldest go first so long sessions stay bounded
+pub const MAX_TURN_RECORDS: usize = 500;
+
 pub struct Aira {
-    stt: Arc<Mutex<SttEngine>>, // Wrap in Mutex for thread safety
+    stt: Option<Arc<Mutex<SttEngine>>>, // Wrap in Mutex for thread safety
     llm: LlmEngine,
-    tts: TtsEngine,
+    tts: Option<TtsEngine>,
     emotional_context: Arc<Mutex<Option<EmotionalContext>>>,
+    // When false, replies never see the emotional context
+    emotion_enabled: bool,
+    // Context currently in the LLM prompt, so unchanged readings aren't re-injected
+    last_injected: Option<EmotionalContext>,
+    // Largest metric drift tolerated before the prompt is refreshed
+    emotion_change_threshold: f32,
+    // Which emotion is named dominant when several readings are high
+    emotion_priority: EmotionPriority,
+    // Seconds a reading steers replies before it counts as stale; None never expires
+    emotion_ttl: Option<u64>,
+    // Told about every completed exchange
+    observer: Box<dyn ExchangeObserver>,
+    // Every completed turn of the conversation, oldest first
+    turns: Vec<TurnRecord>,
+    // Needed to bring models back after `unload`
+    loaders: Option<ModelLoaders>,
+    // Speaking speed of the unloaded voice, restored on reload
+    unloaded_speed: Option<f32>,
+    // Said by `greet` before the user's first message
+    greeting: Option<String>,
+    // Exchanges before the conversation resets itself; None is unlimited
+    max_turns: Option<usize>,
+    // Start the reset conversation from a summary of the old one instead of nothing
+    summarize_on_reset: bool,
+    // Set when the last exchange reset the conversation, until taken
+    session_reset: Option<SessionReset>,
+    // The reset conversation still has to be summarized, at the start of the next request
+    summary_pending: bool,
 }
 
-impl Aira {
-    pub fn new(stt: SttEngine, llm: LlmEngine, tts: TtsEngine) -> Self {
+// Recreates a model dropped by `Aira::unload`
+pub type Loader<T> = Box<dyn Fn() -> Result<T> + Send>;
+
+// How to rebuild each model after an unload; engines without a loader stay in memory
+pub struct ModelLoaders {
+    pub llm: Loader<Box<dyn CompletionBackend>>,
+    pub stt: Option<Loader<SttEngine>>,
+    pub tts: Option<Loader<TtsEngine>>,
+}
+
+// Builds an Aira; STT and TTS can be left out for text-only or headless deployments
+pub struct AiraBuilder {
+    llm: LlmEngine,
+    stt: Option<SttEngine>,
+    tts: Option<TtsEngine>,
+    observer: Box<dyn ExchangeObserver>,
+    loaders: Option<ModelLoaders>,
+    greeting: Option<String>,
+}
+
+impl AiraBuilder {
+    pub fn new(llm: LlmEngine) -> Self {
         Self {
-            stt: Arc::new(Mutex::new(stt)),
             llm,
-            tts,
+            stt: None,
+            tts: None,
+            observer: Box::new(NoopObserver),
+            loaders: None,
+            greeting: None,
+        }
+    }
+
+    pub fn stt(mut self, stt: SttEngine) -> Self {
+        self.stt = Some(stt);
+        self
+    }
+
+    pub fn tts(mut self, tts: TtsEngine) -> Self {
+        self.tts = Some(tts);
+        self
+    }
+
+    // Allow `Aira::unload`, rebuilding the models with `loaders` when next needed
+    pub fn loaders(mut self, loaders: ModelLoaders) -> Self {
+        self.loaders = Some(loaders);
+        self
+    }
+
+    // Open each conversation with `greeting` (see `Aira::greet`); blank text means none
+    pub fn greeting(mut self, greeting: impl Into<String>) -> Self {
+        self.greeting = Some(greeting.into()).filter(|g| !g.trim().is_empty());
+        self
+    }
+
+    // Call `observer` after every completed exchange
+    pub fn observer(mut self, observer: impl ExchangeObserver + 'static) -> Self {
+        self.observer = Box::new(observer);
+        self
+    }
+
+    pub fn build(self) -> Aira {
+        Aira {
+            stt: self.stt.map(|stt| Arc::new(Mutex::new(stt))),
+            llm: self.llm,
+            tts: self.tts,
             emotional_context: Arc::new(Mutex::new(None)),
+            emotion_enabled: true,
+            last_injected: None,
+            emotion_change_threshold: DEFAULT_EMOTION_CHANGE_THRESHOLD,
+            emotion_priority: EmotionPriority::default(),
+            emotion_ttl: None,
+            observer: self.observer,
+            turns: Vec::new(),
+            loaders: self.loaders,
+            unloaded_speed: None,
+            greeting: self.greeting,
+            max_turns: None,
+            summarize_on_reset: false,
+            session_reset: None,
+            summary_pending: false,
+        }
+    }
+}
+
+impl Aira {
+    pub fn new(stt: SttEngine, llm: LlmEngine, tts: TtsEngine) -> Self {
+        AiraBuilder::new(llm).stt(stt).tts(tts).build()
+    }
+
+    pub fn builder(llm: LlmEngine) -> AiraBuilder {
+        AiraBuilder::new(llm)
+    }
+
+    // Drop the models to free RAM/VRAM while idle; history and settings are kept
+    // The next reply (or `reload`) loads them again
+    pub fn unload(&mut self) -> Result<()> {
+        let Some(loaders) = &self.loaders else {
+            anyhow::bail!("Models can't be unloaded without loaders to bring them back");
+        };
+
+        self.llm.unload_backend();
+        if loaders.stt.is_some() {
+            self.stt = None;
+        }
+        if loaders.tts.is_some()
+            && let Some(tts) = self.tts.take()
+        {
+            self.unloaded_speed = Some(tts.speed());
+        }
+        eprintln!("💤 Models unloaded");
+        Ok(())
+    }
+
+    // Load the models again after `unload`; does nothing while they are loaded
+    pub fn reload(&mut self) -> Result<()> {
+        if self.llm.is_loaded() {
+            return Ok(());
+        }
+        let Some(loaders) = &self.loaders else {
+            anyhow::bail!("No loaders configured to reload the models");
+        };
+
+        eprintln!("⏳ Reloading models...");
+        // Load everything before installing anything, so a failure leaves Aira unloaded
+        let backend = (loaders.llm)().map_err(|e| ReloadError::new(EngineKind::Llm, e))?;
+        let stt = loaders
+            .stt
+            .as_ref()
+            .map(|load| load())
+            .transpose()
+            .map_err(|e| ReloadError::new(EngineKind::Stt, e))?;
+        let mut tts = loaders
+            .tts
+            .as_ref()
+            .map(|load| load())
+            .transpose()
+            .map_err(|e| ReloadError::new(EngineKind::Tts, e))?;
+        // Keep a speed changed at runtime rather than falling back to the loader's
+        if let (Some(tts), Some(speed)) = (tts.as_mut(), self.unloaded_speed) {
+            tts.set_speed(speed)
+                .map_err(|e| ReloadError::new(EngineKind::Tts, e))?;
+        }
+
+        self.llm.set_backend(backend);
+        if let Some(stt) = stt {
+            self.stt = Some(Arc::new(Mutex::new(stt)));
         }
+        if let Some(tts) = tts {
+            self.tts = Some(tts);
+            self.unloaded_speed = None;
+        }
+        eprintln!("✅ Models reloaded");
+        Ok(())
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.llm.is_loaded()
     }
 
-    pub fn transcribe(&self, audio: &[f32]) -> Result<String> {
+    pub fn transcribe(&mut self, audio: &[f32]) -> Result<String> {
+        self.reload()?;
         let stt = self
             .stt
+            .as_ref()
+            .ok_or(AiraError::SttNotConfigured)?
             .lock()
             .map_err(|e| anyhow::anyhow!("STT lock poisoned: {}", e))?;
         stt.transcribe(audio)
     }
 
-    pub fn think<F>(&mut self, user_text: &str, callback: F) -> Result<f64>
+    pub fn think<F>(&mut self, user_text: &str, callback: F) -> Result<GenerationMetrics>
     where
         F: FnMut(&str) -> Result<()>,
     {
-        // Inject emotional context into LLM before generating response
-        if let Ok(guard) = self.emotional_context.lock() {
-            if let Some(context) = guard.as_ref() {
-                let llm_context = context.to_llm_context();
-                self.llm.update_emotional_context(&llm_context);
-                println!("🎭 Injected emotional context into LLM");
-            } else {
-                self.llm.clear_emotional_context();
-            }
+        // Honour "short answer" / "explain in detail" style requests
+        let config = GenerationConfig {
+            length: ResponseLength::detect(user_text).unwrap_or_default(),
+            ..Default::default()
+        };
+        self.think_with(user_text, &config, callback)
+    }
+
+    // Think with explicit per-call generation settings
+    pub fn think_with<F>(
+        &mut self,
+        user_text: &str,
+        config: &GenerationConfig,
+        mut callback: F,
+    ) -> Result<GenerationMetrics>
+    where
+        F: FnMut(&str) -> Result<()>,
+    {
+        self.reload()?;
+        self.summarize_pending_reset();
+        self.inject_emotional_context();
+
+        let mut reply = String::new();
+        let metrics = self.llm.ask_with(user_text, config, |piece| {
+            reply.push_str(piece);
+            callback(piece)
+        })?;
+        self.finish_turn(user_text, reply, &metrics);
+        self.reset_at_turn_limit();
+        Ok(metrics)
+    }
+
+    // Answer `user_text` after client-supplied `messages` instead of the stored conversation
+    // (stateless serving); the stored conversation neither sees nor keeps this exchange
+    pub fn think_stateless<F>(
+        &mut self,
+        messages: &[ChatMessage],
+        user_text: &str,
+        config: &GenerationConfig,
+        mut callback: F,
+    ) -> Result<GenerationMetrics>
+    where
+        F: FnMut(&str) -> Result<()>,
+    {
+        self.reload()?;
+        self.summarize_pending_reset();
+        self.inject_emotional_context();
+
+        let mut reply = String::new();
+        let metrics = self
+            .llm
+            .ask_stateless(messages, user_text, config, |piece| {
+                reply.push_str(piece);
+                callback(piece)
+            })?;
+        self.observer
+            .on_exchange(user_text, &reply, self.last_injected.as_ref(), &metrics);
+        Ok(metrics)
+    }
+
+    // Answer the last user message again, replacing the previous reply
+    pub fn regenerate<F>(&mut self, mut callback: F) -> Result<GenerationMetrics>
+    where
+        F: FnMut(&str) -> Result<()>,
+    {
+        let config = GenerationConfig {
+            length: self
+                .llm
+                .last_user_message()
+                .and_then(ResponseLength::detect)
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+
+        self.reload()?;
+        self.summarize_pending_reset();
+        self.inject_emotional_context();
+
+        let mut reply = String::new();
+        let metrics = self.llm.regenerate_with(&config, |piece| {
+            reply.push_str(piece);
+            callback(piece)
+        })?;
+        let user_text = self.llm.last_user_message().unwrap_or_default().to_string();
+        // The new reply replaces the previous one in the transcript too
+        self.turns.pop();
+        self.finish_turn(&user_text, reply, &metrics);
+        Ok(metrics)
+    }
+
+    // Extend the last reply where it stopped, e.g. after hitting the token cap
+    pub fn continue_reply<F>(&mut self, callback: F) -> Result<GenerationMetrics>
+    where
+        F: FnMut(&str) -> Result<()>,
+    {
+        let config = GenerationConfig {
+            length: self
+                .llm
+                .last_user_message()
+                .and_then(ResponseLength::detect)
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+
+        self.reload()?;
+        self.summarize_pending_reset();
+        self.inject_emotional_context();
+
+        let metrics = self.llm.continue_with(&config, callback)?;
+        let user_text = self.llm.last_user_message().unwrap_or_default().to_string();
+        let reply = self
+            .llm
+            .last_assistant_message()
+            .unwrap_or_default()
+            .to_string();
+        // The continued reply is still the same turn
+        self.turns.pop();
+        self.finish_turn(&user_text, reply, &metrics);
+        Ok(metrics)
+    }
+
+    // Record a completed exchange and tell the observer, with the emotional context its prompt carried
+    fn finish_turn(&mut self, user_text: &str, reply: String, metrics: &GenerationMetrics) {
+        self.observer
+            .on_exchange(user_text, &reply, self.last_injected.as_ref(), metrics);
+        self.turns.push(TurnRecord {
+            user: user_text.to_string(),
+            reply,
+            metrics: Some(metrics.clone()),
+            emotion: self.last_injected,
+            audio_duration_secs: None,
+        });
+        if self.turns.len() > MAX_TURN_RECORDS {
+            let excess = self.turns.len() - MAX_TURN_RECORDS;
+            self.turns.drain(..excess);
+        }
+    }
+
+    // Start over once the conversation reaches `max_turns`, bounding memory and context
+    // The summary, if wanted, runs the model again, so it waits for the next request
+    // rather than holding up the end of this reply
+    fn reset_at_turn_limit(&mut self) {
+        let turns = self.llm.exchange_count();
+        if self.max_turns.is_none_or(|max| turns < max) {
+            return;
+        }
+
+        if self.summarize_on_reset {
+            self.summary_pending = true;
+        } else {
+            self.llm.clear_history();
+        }
+        self.turns.clear();
+        eprintln!("🔄 Session reset after {} turns", turns);
+        self.session_reset = Some(SessionReset {
+            turns,
+            summarized: self.summarize_on_reset,
+        });
+    }
+
+    // Condense a conversation reset by the last request into its summary
+    fn summarize_pending_reset(&mut self) {
+        if !std::mem::take(&mut self.summary_pending) {
+            return;
+        }
+        if let Err(e) = self.llm.summarize_all() {
+            eprintln!("Couldn't summarize before the session reset: {}", e);
+            self.llm.clear_history();
+        }
+    }
+
+    // Reset the conversation after `max_turns` exchanges; None (the default) never resets
+    pub fn set_max_turns(&mut self, max_turns: Option<usize>) {
+        self.max_turns = max_turns.filter(|&max| max > 0);
+    }
+
+    pub fn max_turns(&self) -> Option<usize> {
+        self.max_turns
+    }
+
+    // Carry a summary of the old conversation into the reset one (default: false)
+    pub fn set_summarize_on_reset(&mut self, enabled: bool) {
+        self.summarize_on_reset = enabled;
+    }
+
+    pub fn summarize_on_reset_enabled(&self) -> bool {
+        self.summarize_on_reset
+    }
+
+    // The reset caused by the last exchange, if any; cleared once taken
+    pub fn take_session_reset(&mut self) -> Option<SessionReset> {
+        self.session_reset.take()
+    }
+
+    // The configured greeting, when the conversation has not started yet
+    // It becomes Aira's opening line in the history, so the model knows it already said hello,
+    // but it isn't a turn: there was no user message to answer
+    pub fn greet(&mut self) -> Option<String> {
+        let greeting = self.greeting.clone()?;
+        self.llm.add_opening(&greeting).then_some(greeting)
+    }
+
+    // Completed turns of the current conversation, oldest first
+    pub fn turns(&self) -> &[TurnRecord] {
+        &self.turns
+    }
+
+    // Attach the length of the synthesized reply to the latest turn
+    pub fn set_last_turn_audio_duration(&mut self, seconds: f32) {
+        if let Some(turn) = self.turns.last_mut() {
+            turn.audio_duration_secs = Some(seconds);
+        }
+    }
+
+    // Render the prompt the next reply to `user_text` would use, without generating
+    pub fn render_prompt(&mut self, user_text: &str, config: &GenerationConfig) -> String {
+        self.inject_emotional_context();
+        self.llm.render_prompt(user_text, config)
+    }
+
+    // The language model, for reading its settings
+    pub fn llm(&self) -> &LlmEngine {
+        &self.llm
+    }
+
+    // Log and capture rendered prompts (for template debugging only)
+    pub fn set_debug_prompts(&mut self, enabled: bool) {
+        self.llm.set_debug_prompts(enabled);
+    }
+
+    pub fn debug_prompts_enabled(&self) -> bool {
+        self.llm.debug_prompts_enabled()
+    }
+
+    // Prompt sent by the last reply, when prompt debugging is on
+    pub fn last_prompt(&self) -> Option<&str> {
+        self.llm.last_prompt()
+    }
+
+    // Turn emotion-adaptive prompting on or off, regardless of camera input
+    pub fn set_emotion_enabled(&mut self, enabled: bool) {
+        self.emotion_enabled = enabled;
+        if !enabled {
+            self.clear_injected_context();
+        }
+    }
+
+    // Re-inject only when a metric moves more than `threshold`; 0.0 re-injects on any change
+    pub fn set_emotion_change_threshold(&mut self, threshold: f32) {
+        self.emotion_change_threshold = threshold.clamp(0.0, 1.0);
+    }
+
+    pub fn emotion_change_threshold(&self) -> f32 {
+        self.emotion_change_threshold
+    }
+
+    // Pick the dominant emotion in `priority` order; the next reply re-renders the context
+    pub fn set_emotion_priority(&mut self, priority: EmotionPriority) {
+        self.emotion_priority = priority;
+        self.last_injected = None;
+    }
+
+    pub fn emotion_priority(&self) -> EmotionPriority {
+        self.emotion_priority
+    }
+
+    // Stop steering replies with a reading older than `ttl` seconds, e.g. once the camera stops
+    pub fn set_emotion_ttl(&mut self, ttl: Option<u64>) {
+        self.emotion_ttl = ttl;
+    }
+
+    pub fn emotion_ttl(&self) -> Option<u64> {
+        self.emotion_ttl
+    }
+
+    // Whether the next reply will be steered by the emotional context: injection is
+    // enabled and there is a reading that hasn't gone stale
+    pub fn is_emotion_injection_active(&self) -> bool {
+        self.is_emotion_injection_active_at(unix_now())
+    }
+
+    fn is_emotion_injection_active_at(&self, now: u64) -> bool {
+        self.emotion_enabled && self.fresh_emotional_context(now).is_some()
+    }
+
+    // The stored context, unless it is older than the TTL at `now`
+    fn fresh_emotional_context(&self, now: u64) -> Option<EmotionalContext> {
+        let context = self.get_emotional_context()?;
+        let stale = self
+            .emotion_ttl
+            .is_some_and(|ttl| now.saturating_sub(context.timestamp) > ttl);
+        (!stale).then_some(context)
+    }
+
+    pub fn is_emotion_enabled(&self) -> bool {
+        self.emotion_enabled
+    }
+
+    // Inject emotional context into LLM before generating response
+    fn inject_emotional_context(&mut self) {
+        if !self.emotion_enabled {
+            self.clear_injected_context();
+            return;
+        }
+
+        let Some(context) = self.fresh_emotional_context(unix_now()) else {
+            self.clear_injected_context();
+            return;
+        };
+
+        // Keep the prompt stable while the user's state hasn't meaningfully changed
+        if let Some(last) = &self.last_injected
+            && !last.differs_from(
+                &context,
+                self.emotion_change_threshold,
+                &self.emotion_priority,
+            )
+        {
+            return;
         }
 
-        self.llm.ask(user_text, callback)
+        self.llm
+            .update_emotional_context(&context.to_llm_context(&self.emotion_priority));
+        self.last_injected = Some(context);
+        eprintln!("🎭 Injected emotional context into LLM");
     }
 
-    pub fn speak(&self, text: &str) -> Result<Vec<f32>> {
-        self.tts.synthesize(text)
+    fn clear_injected_context(&mut self) {
+        self.llm.clear_emotional_context();
+        self.last_injected = None;
     }
 
-    // Get a clone of the TTS engine for concurrent synthesis
-    pub fn get_tts(&self) -> TtsEngine {
+    pub fn speak(&mut self, text: &str) -> Result<Vec<f32>> {
+        self.reload()?;
+        self.tts
+            .as_ref()
+            .ok_or(AiraError::TtsNotConfigured)?
+            .synthesize(text)
+    }
+
+    // Native sample rate of the configured voice
+    pub fn speech_sample_rate(&self) -> Option<u32> {
+        self.tts.as_ref().map(TtsEngine::sample_rate)
+    }
+
+    // Shared handle to the STT engine, so callers can transcribe without holding Aira
+    pub fn get_stt(&self) -> Option<Arc<Mutex<SttEngine>>> {
+        self.stt.clone()
+    }
+
+    // Change how fast replies are spoken from now on; returns the clamped speed
+    pub fn set_speech_speed(&mut self, speed: f32) -> Result<f32> {
+        self.reload()?;
+        self.tts
+            .as_mut()
+            .ok_or(AiraError::TtsNotConfigured)?
+            .set_speed(speed)
+    }
+
+    // Get a clone of the TTS engine for concurrent synthesis, if one is configured
+    pub fn get_tts(&self) -> Option<TtsEngine> {
         self.tts.clone()
     }
 
@@ -149,14 +930,64 @@ impl Aira {
         }
     }
 
+    // The camera is still sending, though nothing changed enough to replace the reading:
+    // move its timestamp up so the TTL counts from the latest frame, not the last change
+    pub fn touch_emotional_context(&self, timestamp: u64) {
+        if let Ok(mut guard) = self.emotional_context.lock()
+            && let Some(context) = guard.as_mut()
+        {
+            context.timestamp = context.timestamp.max(timestamp);
+        }
+    }
+
     // Get current emotional context
     pub fn get_emotional_context(&self) -> Option<EmotionalContext> {
-        self.emotional_context.lock().ok()?.clone()
+        *self.emotional_context.lock().ok()?
+    }
+
+    // Switch the system prompt mode; the conversation carries on
+    pub fn set_mode(&mut self, name: &str) -> Result<()> {
+        self.llm.set_mode(name)
+    }
+
+    pub fn mode(&self) -> &str {
+        self.llm.mode()
+    }
+
+    pub fn mode_names(&self) -> Vec<&str> {
+        self.llm.mode_names()
     }
 
     // Clear conversation history (useful when starting new conversation)
     pub fn clear_history(&mut self) {
         self.llm.clear_history();
+        self.turns.clear();
+        self.summary_pending = false;
+    }
+
+    // Compress older turns into a summary, keeping recent ones verbatim
+    pub fn summarize_history(&mut self) -> Result<bool> {
+        self.reload()?;
+        self.llm.summarize_history()
+    }
+
+    // Clear stored emotional context
+    pub fn clear_emotional_context(&mut self) {
+        if let Ok(mut guard) = self.emotional_context.lock() {
+            *guard = None;
+        }
+        self.clear_injected_context();
+    }
+
+    // Start fresh: forget history and emotional context
+    pub fn clear_conversation(&mut self) {
+        self.clear_history();
+        self.clear_emotional_context();
+    }
+
+    // Estimate token usage for a message without generating a reply
+    pub fn estimate(&self, user_text: &str, config: &GenerationConfig) -> TokenEstimate {
+        self.llm.estimate(user_text, config)
     }
 
     // Get conversation statistics
@@ -164,3 +995,537 @@ impl Aira {
         (self.llm.history_length(), self.llm.history_tokens())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{CompletionBackend, SamplingParams};
+
+    // Backend that replies with a fixed greeting
+    struct GreetingBackend;
+
+    impl CompletionBackend for GreetingBackend {
+        fn complete(
+            &mut self,
+            _prompt: &str,
+            _params: &SamplingParams,
+            on_piece: &mut dyn FnMut(&str) -> bool,
+        ) -> Result<()> {
+            on_piece("Hello!");
+            Ok(())
+        }
+    }
+
+    // Backend that records every prompt it is asked to complete
+    struct RecordingBackend {
+        prompts: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl CompletionBackend for RecordingBackend {
+        fn complete(
+            &mut self,
+            prompt: &str,
+            _params: &SamplingParams,
+            _on_piece: &mut dyn FnMut(&str) -> bool,
+        ) -> Result<()> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            Ok(())
+        }
+    }
+
+    fn stressed() -> EmotionalContext {
+        EmotionalContext {
+            fatigue: 0.2,
+            engagement: 0.5,
+            stress: 0.9,
+            positive_affect: 0.1,
+            timestamp: 0,
+            face_present: true,
+        }
+    }
+
+    #[test]
+    fn test_mixed_signals_lower_confidence() {
+        let uniform = EmotionalContext {
+            fatigue: 0.4,
+            engagement: 0.4,
+            stress: 0.4,
+            positive_affect: 0.4,
+            timestamp: 0,
+            face_present: true,
+        };
+        let mixed = EmotionalContext {
+            fatigue: 0.0,
+            engagement: 1.0,
+            stress: 0.0,
+            positive_affect: 1.0,
+            ..uniform
+        };
+
+        assert_eq!(uniform.get_confidence(), 1.0);
+        assert!(mixed.get_confidence() < uniform.get_confidence());
+
+        let no_face = EmotionalContext {
+            face_present: false,
+            ..uniform
+        };
+        assert_eq!(no_face.get_confidence(), 0.0);
+    }
+
+    #[test]
+    fn test_absent_face_is_not_reported_as_disengaged() {
+        let no_face = EmotionalContext {
+            fatigue: 0.5,
+            engagement: 0.0,
+            stress: 0.5,
+            positive_affect: 0.5,
+            timestamp: 0,
+            face_present: false,
+        };
+
+        let context = no_face.to_llm_context(&EmotionPriority::default());
+        assert!(context.contains("not visible on camera"));
+        assert!(!context.contains("The user appears disengaged"));
+        assert!(!context.contains("draw them in"));
+
+        // The same metrics with a face in frame do read as disengaged
+        let present = EmotionalContext {
+            face_present: true,
+            ..no_face
+        };
+        assert!(
+            present
+                .to_llm_context(&EmotionPriority::default())
+                .contains("disengaged")
+        );
+    }
+
+    #[test]
+    fn test_reordered_priority_changes_the_dominant_emotion() {
+        // Tired and stressed at once; which one leads is a deployment choice
+        let context = EmotionalContext {
+            fatigue: 0.75,
+            engagement: 0.5,
+            stress: 0.65,
+            positive_affect: 0.2,
+            timestamp: 0,
+            face_present: true,
+        };
+
+        let default = EmotionPriority::default();
+        assert_eq!(default.dominant(&context), DominantEmotion::Fatigued);
+        assert!(context.to_llm_context(&default).contains("fatigued"));
+
+        let stress_first = EmotionPriority::parse("stress, fatigue").unwrap();
+        assert_eq!(
+            stress_first.order(),
+            [
+                Emotion::Stress,
+                Emotion::Fatigue,
+                Emotion::Positive,
+                Emotion::Engagement
+            ]
+        );
+        assert_eq!(stress_first.dominant(&context), DominantEmotion::Stressed);
+        let prompt = context.to_llm_context(&stress_first);
+        assert!(prompt.contains("stressed or tense"), "{}", prompt);
+        assert!(prompt.contains("Be calming"), "{}", prompt);
+
+        assert!(EmotionPriority::parse("stress,stress").is_err());
+        assert!(EmotionPriority::parse("boredom").is_err());
+    }
+
+    #[test]
+    fn test_injection_is_active_only_for_a_fresh_enabled_context() {
+        let mut aira = text_only_aira();
+        aira.set_emotion_ttl(Some(30));
+        assert!(!aira.is_emotion_injection_active_at(100));
+
+        aira.update_emotional_context(EmotionalContext {
+            timestamp: 100,
+            ..stressed()
+        });
+        assert!(aira.is_emotion_injection_active_at(120));
+        // Past the TTL the reading no longer steers replies
+        assert!(!aira.is_emotion_injection_active_at(131));
+
+        aira.set_emotion_enabled(false);
+        assert!(!aira.is_emotion_injection_active_at(120));
+    }
+
+    #[test]
+    fn test_steady_frames_keep_the_reading_fresh() {
+        let mut aira = text_only_aira();
+        aira.set_emotion_ttl(Some(30));
+        // Nothing to touch before the first reading
+        aira.touch_emotional_context(100);
+        assert!(aira.get_emotional_context().is_none());
+
+        aira.update_emotional_context(EmotionalContext {
+            timestamp: 100,
+            ..stressed()
+        });
+        // Frames kept arriving without a significant change
+        aira.touch_emotional_context(125);
+        assert!(aira.is_emotion_injection_active_at(150));
+        assert_eq!(
+            aira.get_emotional_context().unwrap().stress,
+            stressed().stress
+        );
+    }
+
+    #[test]
+    fn test_disabled_emotion_leaves_prompt_without_emotional_block() {
+        let prompts = Arc::new(Mutex::new(Vec::new()));
+        let backend = RecordingBackend {
+            prompts: prompts.clone(),
+        };
+        let llm = LlmEngine::with_backend(Box::new(backend), "You are Aira.");
+        let mut aira = Aira::builder(llm).build();
+        aira.update_emotional_context(stressed());
+
+        aira.think("Hi", |_| Ok(())).unwrap();
+        aira.set_emotion_enabled(false);
+        aira.think("Hi again", |_| Ok(())).unwrap();
+
+        let prompts = prompts.lock().unwrap();
+        assert!(prompts[0].contains("[User's Current State]"));
+        assert!(!prompts[1].contains("[User's Current State]"));
+        assert!(!aira.is_emotion_enabled());
+    }
+
+    #[test]
+    fn test_unchanged_emotion_is_injected_once() {
+        let prompts = Arc::new(Mutex::new(Vec::new()));
+        let backend = RecordingBackend {
+            prompts: prompts.clone(),
+        };
+        let llm = LlmEngine::with_backend(Box::new(backend), "You are Aira.");
+        let mut aira = Aira::builder(llm).build();
+
+        aira.update_emotional_context(stressed());
+        aira.think("Hi", |_| Ok(())).unwrap();
+
+        // A small wobble in the same state keeps the first injection
+        aira.update_emotional_context(EmotionalContext {
+            stress: 0.95,
+            ..stressed()
+        });
+        aira.think("Hi again", |_| Ok(())).unwrap();
+
+        let prompts = prompts.lock().unwrap();
+        assert!(prompts[1].contains("Stress: 90%"));
+    }
+
+    #[test]
+    fn test_changed_emotion_is_reinjected() {
+        let prompts = Arc::new(Mutex::new(Vec::new()));
+        let backend = RecordingBackend {
+            prompts: prompts.clone(),
+        };
+        let llm = LlmEngine::with_backend(Box::new(backend), "You are Aira.");
+        let mut aira = Aira::builder(llm).build();
+
+        aira.update_emotional_context(stressed());
+        aira.think("Hi", |_| Ok(())).unwrap();
+        aira.update_emotional_context(EmotionalContext {
+            stress: 0.2,
+            positive_affect: 0.8,
+            ..stressed()
+        });
+        aira.think("Hi again", |_| Ok(())).unwrap();
+
+        let prompts = prompts.lock().unwrap();
+        assert!(prompts[0].contains("stressed or tense"));
+        assert!(prompts[1].contains("happy and positive"));
+    }
+
+    #[test]
+    fn test_same_emotion_is_reinjected_after_clearing() {
+        let prompts = Arc::new(Mutex::new(Vec::new()));
+        let backend = RecordingBackend {
+            prompts: prompts.clone(),
+        };
+        let llm = LlmEngine::with_backend(Box::new(backend), "You are Aira.");
+        let mut aira = Aira::builder(llm).build();
+
+        aira.update_emotional_context(stressed());
+        aira.think("Hi", |_| Ok(())).unwrap();
+        aira.clear_emotional_context();
+        aira.update_emotional_context(stressed());
+        aira.think("Hi again", |_| Ok(())).unwrap();
+
+        let prompts = prompts.lock().unwrap();
+        assert!(prompts[1].contains("stressed or tense"));
+    }
+
+    // User text, reply and injected stress of one exchange
+    type Exchange = (String, String, Option<f32>);
+
+    // Observer that keeps what it was told about each exchange
+    struct RecordingObserver {
+        exchanges: Arc<Mutex<Vec<Exchange>>>,
+    }
+
+    impl ExchangeObserver for RecordingObserver {
+        fn on_exchange(
+            &self,
+            user: &str,
+            reply: &str,
+            emotion: Option<&EmotionalContext>,
+            _metrics: &GenerationMetrics,
+        ) {
+            let stress = emotion.map(|e| e.stress);
+            let exchange = (user.to_string(), reply.to_string(), stress);
+            self.exchanges.lock().unwrap().push(exchange);
+        }
+    }
+
+    #[test]
+    fn test_observer_sees_each_exchange_once() {
+        let exchanges = Arc::new(Mutex::new(Vec::new()));
+        let llm = LlmEngine::with_backend(Box::new(GreetingBackend), "You are Aira.");
+        let mut aira = Aira::builder(llm)
+            .observer(RecordingObserver {
+                exchanges: exchanges.clone(),
+            })
+            .build();
+
+        aira.think("Hi", |_| Ok(())).unwrap();
+        aira.update_emotional_context(stressed());
+        aira.think("Hi again", |_| Ok(())).unwrap();
+
+        let exchanges = exchanges.lock().unwrap();
+        assert_eq!(exchanges.len(), 2);
+        assert_eq!(exchanges[0], ("Hi".into(), "Hello!".into(), None));
+        assert_eq!(
+            exchanges[1],
+            ("Hi again".into(), "Hello!".into(), Some(0.9))
+        );
+    }
+
+    #[test]
+    fn test_unloaded_models_reload_on_next_request() {
+        let loads = Arc::new(Mutex::new(0));
+        let counter = loads.clone();
+        let loaders = ModelLoaders {
+            llm: Box::new(move || {
+                *counter.lock().unwrap() += 1;
+                Ok(Box::new(GreetingBackend) as Box<dyn CompletionBackend>)
+            }),
+            stt: None,
+            tts: None,
+        };
+        let llm = LlmEngine::with_backend(Box::new(GreetingBackend), "You are Aira.");
+        let mut aira = Aira::builder(llm).loaders(loaders).build();
+
+        aira.unload().unwrap();
+        assert!(!aira.is_loaded());
+        assert!(aira.get_tts().is_none() && aira.get_stt().is_none());
+        assert_eq!(*loads.lock().unwrap(), 0);
+
+        let mut reply = String::new();
+        aira.think("Hi", |piece| {
+            reply.push_str(piece);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(reply, "Hello!");
+        assert!(aira.is_loaded());
+        assert_eq!(*loads.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_reload_failure_names_the_model_that_failed() {
+        let loaders = ModelLoaders {
+            llm: Box::new(|| Ok(Box::new(GreetingBackend) as Box<dyn CompletionBackend>)),
+            stt: Some(Box::new(|| -> Result<SttEngine> {
+                anyhow::bail!("ggml-small.en.bin not found")
+            })),
+            tts: None,
+        };
+        let llm = LlmEngine::with_backend(Box::new(GreetingBackend), "You are Aira.");
+        let mut aira = Aira::builder(llm).loaders(loaders).build();
+
+        aira.unload().unwrap();
+        let err = aira.reload().unwrap_err();
+        let reload = err.downcast_ref::<ReloadError>().unwrap();
+        assert_eq!(reload.engine, EngineKind::Stt);
+        assert!(err.to_string().contains("ggml-small.en.bin not found"));
+        // Nothing was installed, so the next request tries again
+        assert!(!aira.is_loaded());
+    }
+
+    #[test]
+    fn test_model_entry_points_reload_after_unload() {
+        let loaders = ModelLoaders {
+            llm: Box::new(|| Ok(Box::new(GreetingBackend) as Box<dyn CompletionBackend>)),
+            stt: None,
+            tts: None,
+        };
+        let llm = LlmEngine::with_backend(Box::new(GreetingBackend), "You are Aira.");
+        let mut aira = Aira::builder(llm).loaders(loaders).build();
+
+        aira.unload().unwrap();
+        aira.summarize_history().unwrap();
+        assert!(aira.is_loaded());
+
+        aira.unload().unwrap();
+        // Nothing to transcribe or speak with, but the models came back for the attempt
+        assert!(aira.transcribe(&[0.0; 160]).is_err());
+        assert!(aira.is_loaded());
+
+        aira.unload().unwrap();
+        assert!(aira.speak("Hi").is_err());
+        assert!(aira.is_loaded());
+    }
+
+    #[test]
+    fn test_unload_without_loaders_is_refused() {
+        let mut aira = text_only_aira();
+        assert!(aira.unload().is_err());
+        assert!(aira.is_loaded());
+    }
+
+    fn text_only_aira() -> Aira {
+        let llm = LlmEngine::with_backend(Box::new(GreetingBackend), "You are Aira.");
+        Aira::builder(llm).build()
+    }
+
+    #[test]
+    fn test_speak_without_tts_returns_not_configured() {
+        let mut aira = text_only_aira();
+
+        let err = aira.speak("Hi").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<AiraError>(),
+            Some(&AiraError::TtsNotConfigured)
+        );
+        assert!(aira.get_tts().is_none());
+    }
+
+    #[test]
+    fn test_transcribe_without_stt_returns_not_configured() {
+        let mut aira = text_only_aira();
+
+        let err = aira.transcribe(&[0.0; 16]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<AiraError>(),
+            Some(&AiraError::SttNotConfigured)
+        );
+    }
+
+    #[test]
+    fn test_text_only_aira_can_still_think() {
+        let mut aira = text_only_aira();
+
+        let mut reply = String::new();
+        aira.think("Hi", |piece| {
+            reply.push_str(piece);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(reply, "Hello!");
+    }
+
+    #[test]
+    fn test_greeting_opens_the_conversation_once() {
+        let prompts = Arc::new(Mutex::new(Vec::new()));
+        let llm = LlmEngine::with_backend(
+            Box::new(RecordingBackend {
+                prompts: prompts.clone(),
+            }),
+            "",
+        );
+        let mut aira = Aira::builder(llm)
+            .greeting("Hi! I'm Aira. How are you today?")
+            .build();
+
+        assert_eq!(
+            aira.greet().as_deref(),
+            Some("Hi! I'm Aira. How are you today?")
+        );
+        // Said once; the conversation has already started
+        assert_eq!(aira.greet(), None);
+        assert!(aira.turns().is_empty());
+
+        aira.think("Pretty good", |_| Ok(())).unwrap();
+        let prompt = prompts.lock().unwrap().last().cloned().unwrap();
+        let greeting = prompt.find("How are you today?").unwrap();
+        assert!(greeting < prompt.find("Pretty good").unwrap());
+        assert_eq!(aira.turns().len(), 1);
+        assert_eq!(aira.turns()[0].user, "Pretty good");
+    }
+
+    #[test]
+    fn test_completed_turn_records_metrics_and_emotion() {
+        let mut aira = text_only_aira();
+        aira.update_emotional_context(stressed());
+
+        let metrics = aira.think("Hi", |_| Ok(())).unwrap();
+        aira.set_last_turn_audio_duration(1.5);
+
+        let turns = aira.turns();
+        assert_eq!(turns.len(), 1);
+        assert_eq!(turns[0].user, "Hi");
+        assert_eq!(turns[0].reply, "Hello!");
+        let recorded = turns[0].metrics.as_ref().unwrap();
+        assert_eq!(recorded.finish_reason, metrics.finish_reason);
+        assert_eq!(turns[0].emotion.unwrap().stress, stressed().stress);
+        assert_eq!(turns[0].audio_duration_secs, Some(1.5));
+
+        // Regenerating replaces the turn rather than adding one
+        aira.regenerate(|_| Ok(())).unwrap();
+        assert_eq!(aira.turns().len(), 1);
+        assert_eq!(aira.turns()[0].audio_duration_secs, None);
+    }
+
+    #[test]
+    fn test_turn_records_are_capped() {
+        let mut aira = text_only_aira();
+        for turn in 0..=MAX_TURN_RECORDS {
+            aira.think(&format!("Turn {}", turn), |_| Ok(())).unwrap();
+        }
+
+        let turns = aira.turns();
+        assert_eq!(turns.len(), MAX_TURN_RECORDS);
+        assert_eq!(turns[0].user, "Turn 1");
+    }
+
+    #[test]
+    fn test_conversation_resets_after_max_turns() {
+        let mut aira = text_only_aira();
+        aira.set_max_turns(Some(2));
+
+        aira.think("Hi", |_| Ok(())).unwrap();
+        assert_eq!(aira.get_conversation_stats().0, 2);
+        assert_eq!(aira.take_session_reset(), None);
+
+        aira.think("Hi again", |_| Ok(())).unwrap();
+        assert_eq!(aira.get_conversation_stats().0, 0);
+        assert!(aira.turns().is_empty());
+        assert_eq!(
+            aira.take_session_reset(),
+            Some(SessionReset {
+                turns: 2,
+                summarized: false
+            })
+        );
+        // The notice is given once
+        assert_eq!(aira.take_session_reset(), None);
+
+        // With summarizing on, the new conversation starts from a single summary turn
+        aira.set_summarize_on_reset(true);
+        aira.think("One", |_| Ok(())).unwrap();
+        aira.think("Two", |_| Ok(())).unwrap();
+        assert!(aira.take_session_reset().unwrap().summarized);
+        // Written when the next request starts, not while the last reply finishes
+        assert_eq!(aira.get_conversation_stats().0, 4);
+        aira.think("Three", |_| Ok(())).unwrap();
+        assert_eq!(aira.get_conversation_stats().0, 3);
+        // The summary isn't an exchange, so the limit counts from the new conversation
+        assert_eq!(aira.take_session_reset(), None);
+        aira.think("Four", |_| Ok(())).unwrap();
+        assert_eq!(aira.take_session_reset().unwrap().turns, 2);
+    }
+}
@@ -0,0 +1,286 @@
+// Sample-level helpers shared by the STT and TTS pipelines
+
+use std::time::Duration;
+
+// Rate Whisper expects for transcription
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+// Rates accepted for client audio, from telephone quality up to studio
+pub const MIN_SAMPLE_RATE: u32 = 8000;
+pub const MAX_SAMPLE_RATE: u32 = 48000;
+
+// Samples that carry their own rate and channel layout, so conversions can't guess wrong
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioBuffer {
+    // Interleaved when there is more than one channel
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl AudioBuffer {
+    pub fn new(samples: Vec<f32>, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            samples,
+            sample_rate,
+            channels: channels.max(1),
+        }
+    }
+
+    pub fn mono(samples: Vec<f32>, sample_rate: u32) -> Self {
+        Self::new(samples, sample_rate, 1)
+    }
+
+    // Samples per channel
+    pub fn frames(&self) -> usize {
+        self.samples.len().div_ceil(self.channels as usize)
+    }
+
+    pub fn duration(&self) -> Duration {
+        if self.sample_rate == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(self.frames() as f64 / self.sample_rate as f64)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    // Average the channels into one
+    pub fn to_mono(&self) -> Self {
+        Self::mono(downmix(&self.samples, self.channels), self.sample_rate)
+    }
+
+    // Mix down to mono, then repeat it on each of `channels`
+    pub fn to_channels(&self, channels: u16) -> Self {
+        let channels = channels.max(1);
+        if channels == self.channels {
+            return self.clone();
+        }
+        let mono = downmix(&self.samples, self.channels);
+        Self::new(upmix(mono, channels), self.sample_rate, channels)
+    }
+
+    // Convert to `sample_rate`, resampling each channel on its own
+    pub fn resample(self, sample_rate: u32) -> Self {
+        if self.channels == 1 {
+            let samples = resample(self.samples, self.sample_rate, sample_rate);
+            return Self::mono(samples, sample_rate);
+        }
+        if self.sample_rate == sample_rate {
+            return self;
+        }
+
+        let channels = self.channels as usize;
+        let resampled: Vec<Vec<f32>> = (0..channels)
+            .map(|channel| {
+                let plane = self.samples.iter().skip(channel).step_by(channels).copied();
+                resample(plane.collect(), self.sample_rate, sample_rate)
+            })
+            .collect();
+        let frames = resampled.iter().map(Vec::len).min().unwrap_or(0);
+        let samples = (0..frames)
+            .flat_map(|frame| resampled.iter().map(move |plane| plane[frame]))
+            .collect();
+        Self::new(samples, sample_rate, self.channels)
+    }
+
+    // Mono audio at Whisper's rate, ready for transcription
+    pub fn for_whisper(&self) -> Vec<f32> {
+        self.to_mono().resample(WHISPER_SAMPLE_RATE).samples
+    }
+}
+
+// Average interleaved channels down to mono (a trailing partial frame is averaged too)
+pub fn downmix(samples: &[f32], channels: u16) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+
+    samples
+        .chunks(channels as usize)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+// Repeat each mono sample on every channel, giving interleaved frames
+pub fn upmix(samples: Vec<f32>, channels: u16) -> Vec<f32> {
+    if channels <= 1 {
+        return samples;
+    }
+
+    samples
+        .into_iter()
+        .flat_map(|sample| std::iter::repeat_n(sample, channels as usize))
+        .collect()
+}
+
+// Linear-interpolation resample; samples pass through untouched when the rates match
+pub fn resample(samples: Vec<f32>, from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || from_rate == 0 || to_rate == 0 || samples.is_empty() {
+        return samples;
+    }
+
+    let ratio = from_rate as f64 / to_rate as f64;
+    let out_len = (samples.len() as f64 / ratio).round() as usize;
+    let last = samples.len() - 1;
+
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 * ratio;
+            let idx = (pos as usize).min(last);
+            let frac = (pos - idx as f64) as f32;
+            let next = samples[(idx + 1).min(last)];
+            samples[idx] + (next - samples[idx]) * frac
+        })
+        .collect()
+}
+
+// Signed 16-bit PCM to -1.0..1.0
+pub fn i16_to_f32(sample: i16) -> f32 {
+    sample as f32 / 32768.0
+}
+
+// Unsigned 16-bit PCM (silence at 32768) to -1.0..1.0
+pub fn u16_to_f32(sample: u16) -> f32 {
+    (sample as f32 - 32768.0) / 32768.0
+}
+
+// Loudest output gain accepted; higher values are clamped
+pub const MAX_GAIN: f32 = 4.0;
+
+// Scale samples by `gain` (0.0 - MAX_GAIN), clamping the result to -1.0..=1.0 so boosts can't wrap
+pub fn apply_gain(samples: &mut [f32], gain: f32) {
+    let gain = if gain.is_finite() {
+        gain.clamp(0.0, MAX_GAIN)
+    } else {
+        1.0
+    };
+
+    for sample in samples {
+        *sample = (*sample * gain).clamp(-1.0, 1.0);
+    }
+}
+
+// Boost high frequencies with y[n] = x[n] - coefficient * x[n-1]; 0.0 leaves samples unchanged
+// Around 0.97 is the usual choice for speech
+pub fn pre_emphasis(samples: &[f32], coefficient: f32) -> Vec<f32> {
+    let mut previous = 0.0;
+    samples
+        .iter()
+        .map(|&sample| {
+            let emphasized = sample - coefficient * previous;
+            previous = sample;
+            emphasized
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_samples_convert_to_unit_range() {
+        assert_eq!(i16_to_f32(0), 0.0);
+        assert_eq!(i16_to_f32(i16::MIN), -1.0);
+        assert_eq!(i16_to_f32(16384), 0.5);
+        assert!((i16_to_f32(i16::MAX) - 1.0).abs() < 1e-4);
+
+        assert_eq!(u16_to_f32(32768), 0.0);
+        assert_eq!(u16_to_f32(0), -1.0);
+        assert_eq!(u16_to_f32(49152), 0.5);
+        assert!((u16_to_f32(u16::MAX) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_matched_rate_is_not_resampled() {
+        let samples: Vec<f32> = (0..16000).map(|i| (i as f32 * 0.01).sin()).collect();
+        let out = resample(samples.clone(), 16000, 16000);
+        assert_eq!(out.len(), samples.len());
+        assert_eq!(out, samples);
+    }
+
+    #[test]
+    fn test_mismatched_rate_is_resampled() {
+        let samples = vec![0.0; 22050];
+        assert_eq!(resample(samples.clone(), 22050, 16000).len(), 16000);
+        assert_eq!(resample(samples, 22050, 44100).len(), 44100);
+    }
+
+    #[test]
+    fn test_downmix_averages_each_frame() {
+        assert_eq!(downmix(&[0.5, -0.5, 1.0, 0.0], 2), vec![0.0, 0.5]);
+        assert_eq!(downmix(&[0.1, 0.2], 1), vec![0.1, 0.2]);
+    }
+
+    #[test]
+    fn test_half_gain_halves_amplitude() {
+        let mut samples = vec![0.8, -0.4, 0.0, 1.0];
+        apply_gain(&mut samples, 0.5);
+        assert_eq!(samples, vec![0.4, -0.2, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn test_boost_is_clamped_to_valid_range() {
+        let mut samples = vec![0.3, -0.6, 0.9];
+        apply_gain(&mut samples, 3.0);
+        assert!(samples.iter().all(|s| (-1.0..=1.0).contains(s)));
+        assert_eq!(samples[1], -1.0);
+        assert_eq!(samples[2], 1.0);
+        assert!((samples[0] - 0.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_buffer_duration_counts_frames_not_samples() {
+        let stereo = AudioBuffer::new(vec![0.0; 44100], 22050, 2);
+        assert_eq!(stereo.frames(), 22050);
+        assert_eq!(stereo.duration(), Duration::from_secs(1));
+        assert_eq!(
+            AudioBuffer::mono(vec![0.0; 8000], 16000).duration(),
+            Duration::from_millis(500)
+        );
+    }
+
+    #[test]
+    fn test_buffer_to_mono_and_back_to_stereo() {
+        let stereo = AudioBuffer::new(vec![0.5, -0.5, 1.0, 0.0], 16000, 2);
+        let mono = stereo.to_mono();
+        assert_eq!(mono, AudioBuffer::mono(vec![0.0, 0.5], 16000));
+
+        let again = mono.to_channels(2);
+        assert_eq!(again.samples, vec![0.0, 0.0, 0.5, 0.5]);
+        assert_eq!(again.channels, 2);
+    }
+
+    #[test]
+    fn test_buffer_resample_keeps_channels_apart() {
+        // Left is silent, right is full scale; resampling must not blend them
+        let stereo = AudioBuffer::new([0.0, 1.0].repeat(22050), 22050, 2);
+        let resampled = stereo.resample(16000);
+
+        assert_eq!(resampled.sample_rate, 16000);
+        assert_eq!(resampled.channels, 2);
+        assert_eq!(resampled.frames(), 16000);
+        assert!(resampled.samples.chunks(2).all(|frame| frame == [0.0, 1.0]));
+        assert_eq!(resampled.duration(), Duration::from_secs(1));
+
+        let whisper = AudioBuffer::new(vec![0.25; 96000], 48000, 2).for_whisper();
+        assert_eq!(whisper.len(), 16000);
+    }
+
+    #[test]
+    fn test_pre_emphasis_subtracts_the_scaled_previous_sample() {
+        // An impulse leaves a negative echo one sample later
+        assert_eq!(
+            pre_emphasis(&[0.0, 1.0, 0.0, 0.0], 0.5),
+            vec![0.0, 1.0, -0.5, 0.0]
+        );
+        // A steady level is mostly removed after the first sample
+        assert_eq!(pre_emphasis(&[1.0, 1.0, 1.0], 0.75), vec![1.0, 0.25, 0.25]);
+
+        let samples = vec![0.3, -0.2, 0.7, 0.1];
+        assert_eq!(pre_emphasis(&samples, 0.0), samples);
+    }
+}
@@ -1,6 +1,109 @@
+// Name the assistant goes by when none is configured
+pub const DEFAULT_ASSISTANT_NAME: &str = "Aira";
+
 pub struct AiraConfig {
     pub llm_path: &'static str,
     pub stt_path: &'static str,
     pub tts_path: &'static str,
     pub system_prompt: &'static str,
 }
+
+// Stands for the assistant's name in configured prompts and canned replies
+pub const ASSISTANT_NAME_PLACEHOLDER: &str = "{name}";
+
+// Fill in `assistant_name` wherever `text` says "{name}"
+pub fn with_assistant_name(text: &str, assistant_name: &str) -> String {
+    text.replace(ASSISTANT_NAME_PLACEHOLDER, assistant_name)
+}
+
+// Default persona as a ChatML system turn, introducing the assistant by `assistant_name`
+pub fn default_system_prompt(assistant_name: &str) -> String {
+    format!(
+        "<|im_start|>system\nYou are {}, a warm, empathetic AI assistant.<|im_end|>\n",
+        assistant_name
+    )
+}
+
+// Wake phrase used when wake-word listening is on without an explicit phrase
+pub fn default_wake_phrase(assistant_name: &str) -> String {
+    format!("hey {}", assistant_name.to_lowercase())
+}
+
+// Words said or typed to end a CLI session
+pub const DEFAULT_EXIT_PHRASES: [&str; 2] = ["exit", "quit"];
+
+// Phrases that end a session; each must make up the whole input, not just appear in it
+pub struct ExitCommandConfig {
+    pub phrases: Vec<String>,
+}
+
+impl Default for ExitCommandConfig {
+    fn default() -> Self {
+        Self {
+            phrases: DEFAULT_EXIT_PHRASES.map(String::from).to_vec(),
+        }
+    }
+}
+
+// Lowercase words with punctuation stripped, so "Hey, Aira!" reads as "hey aira"
+pub fn normalize_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// True if `text` is exactly one of the exit phrases, ignoring case and punctuation
+// "Quit." exits; "I want to quit smoking" is a message
+pub fn is_exit_command(text: &str, config: &ExitCommandConfig) -> bool {
+    let words = normalize_words(text);
+    !words.is_empty()
+        && config
+            .phrases
+            .iter()
+            .any(|phrase| normalize_words(phrase) == words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assistant_name_replaces_default_in_system_prompt() {
+        let prompt = default_system_prompt("Nova");
+        assert!(prompt.contains("You are Nova"));
+        assert!(!prompt.contains("Aira"));
+
+        assert!(default_system_prompt(DEFAULT_ASSISTANT_NAME).contains("You are Aira"));
+    }
+
+    #[test]
+    fn test_configured_text_takes_the_assistant_name() {
+        assert_eq!(
+            with_assistant_name("Hi! I'm {name}. How are you today?", "Nova"),
+            "Hi! I'm Nova. How are you today?"
+        );
+        assert_eq!(with_assistant_name("Hmm...", "Nova"), "Hmm...");
+    }
+
+    #[test]
+    fn test_wake_phrase_follows_assistant_name() {
+        assert_eq!(default_wake_phrase("Nova"), "hey nova");
+    }
+
+    #[test]
+    fn test_only_a_bare_exit_phrase_ends_the_session() {
+        let config = ExitCommandConfig::default();
+        assert!(is_exit_command("quit", &config));
+        assert!(is_exit_command("  Exit! ", &config));
+        assert!(!is_exit_command("I want to quit smoking", &config));
+        assert!(!is_exit_command("quit smoking", &config));
+        assert!(!is_exit_command("", &config));
+
+        let config = ExitCommandConfig {
+            phrases: vec!["goodbye aira".to_string()],
+        };
+        assert!(is_exit_command("Goodbye, Aira.", &config));
+        assert!(!is_exit_command("quit", &config));
+    }
+}
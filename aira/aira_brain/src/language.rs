@@ -0,0 +1,120 @@
+// Reply languages: the instruction added to the prompt, and which Piper voice can speak it
+
+// Known languages by English name and ISO 639-1 code, as Piper voices are labelled ("fr_FR")
+const LANGUAGES: &[(&str, &str)] = &[
+    ("Arabic", "ar"),
+    ("Chinese", "zh"),
+    ("Czech", "cs"),
+    ("Danish", "da"),
+    ("Dutch", "nl"),
+    ("English", "en"),
+    ("Finnish", "fi"),
+    ("French", "fr"),
+    ("German", "de"),
+    ("Greek", "el"),
+    ("Hindi", "hi"),
+    ("Hungarian", "hu"),
+    ("Italian", "it"),
+    ("Japanese", "ja"),
+    ("Korean", "ko"),
+    ("Norwegian", "no"),
+    ("Polish", "pl"),
+    ("Portuguese", "pt"),
+    ("Romanian", "ro"),
+    ("Russian", "ru"),
+    ("Spanish", "es"),
+    ("Swedish", "sv"),
+    ("Turkish", "tr"),
+    ("Ukrainian", "uk"),
+    ("Vietnamese", "vi"),
+];
+
+// A language replies should be written in
+#[derive(Clone, Debug, PartialEq)]
+pub struct Language {
+    // As written in the prompt, e.g. "French"
+    pub name: String,
+    // ISO 639-1 code, when the language is known; without one no voice is matched
+    pub code: Option<&'static str>,
+}
+
+impl Language {
+    // Accepts a name ("French"), a code ("fr") or a locale ("fr_FR", "fr-FR"); blank is none
+    // Unknown names are kept as written, so the model is still asked to use them
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+
+        let code = value.split(['_', '-']).next().unwrap_or(value);
+        let known = LANGUAGES.iter().find(|(name, known_code)| {
+            name.eq_ignore_ascii_case(value) || known_code.eq_ignore_ascii_case(code)
+        });
+        Some(match known {
+            Some((name, code)) => Self {
+                name: name.to_string(),
+                code: Some(code),
+            },
+            None => Self {
+                name: value.to_string(),
+                code: None,
+            },
+        })
+    }
+
+    // Line appended to the user's message
+    pub fn instruction(&self) -> String {
+        format!("Respond in {}.", self.name)
+    }
+
+    // Whether a voice labelled `voice_language` (e.g. "fr_FR") speaks this language
+    pub fn is_spoken_by(&self, voice_language: &str) -> bool {
+        self.code.is_some_and(|code| {
+            voice_language
+                .split(['_', '-'])
+                .next()
+                .is_some_and(|voice_code| voice_code.eq_ignore_ascii_case(code))
+        })
+    }
+
+    // Index of the first voice that speaks this language; voices without a label never match
+    pub fn matching_voice<'a>(
+        &self,
+        voice_languages: impl IntoIterator<Item = Option<&'a str>>,
+    ) -> Option<usize> {
+        voice_languages
+            .into_iter()
+            .position(|voice| voice.is_some_and(|voice| self.is_spoken_by(voice)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_codes_and_locales_name_the_same_language() {
+        let french = Language::parse("French").unwrap();
+        assert_eq!(french.code, Some("fr"));
+        assert_eq!(Language::parse("fr"), Some(french.clone()));
+        assert_eq!(Language::parse("fr-FR"), Some(french.clone()));
+        assert_eq!(french.instruction(), "Respond in French.");
+
+        // Unknown languages are still asked for, but can't pick a voice
+        let klingon = Language::parse("Klingon").unwrap();
+        assert_eq!(klingon.code, None);
+        assert_eq!(klingon.matching_voice([Some("en_US")]), None);
+        assert_eq!(Language::parse("  "), None);
+    }
+
+    #[test]
+    fn test_matching_voice_is_selected_when_available() {
+        let voices = [Some("en_US"), None, Some("fr_FR"), Some("fr_BE")];
+        let french = Language::parse("French").unwrap();
+        assert_eq!(french.matching_voice(voices), Some(2));
+
+        let german = Language::parse("de").unwrap();
+        assert_eq!(german.matching_voice(voices), None);
+    }
+}
@@ -1,12 +1,28 @@
 pub mod aira;
+pub mod audio;
 pub mod config;
+pub mod language;
 pub mod llm;
+pub mod observer;
+pub mod ssml;
 pub mod stt;
 pub mod tts;
 
 // Re-export commonly used types
-pub use aira::Aira;
-pub use config::AiraConfig;
-pub use llm::LlmEngine;
-pub use stt::SttEngine;
-pub use tts::TtsEngine;
+pub use aira::{
+    Aira, AiraBuilder, AiraError, DEFAULT_EMOTION_CHANGE_THRESHOLD, DominantEmotion, Emotion,
+    EmotionPriority, EngineKind, ModelLoaders, ReloadError, SessionReset, TurnRecord,
+};
+pub use audio::AudioBuffer;
+pub use config::{AiraConfig, DEFAULT_ASSISTANT_NAME, with_assistant_name};
+pub use language::Language;
+pub use llm::{
+    ChatMessage, FinishReason, GenerationConfig, GpuConfig, LlmEngine, ResponseLength,
+    SamplingParams, ThreadConfig, TokenEstimate,
+};
+pub use observer::{ExchangeObserver, JsonLinesObserver, NoopObserver};
+pub use stt::{
+    SpeechSegment, SttBackend, SttConfig, SttEngine, TimedSegment, TranscribedSegment,
+    Transcription,
+};
+pub use tts::{TtsEngine, WordTiming};
@@ -0,0 +1,125 @@
+use crate::aira::EmotionalContext;
+use crate::llm::GenerationMetrics;
+use anyhow::Result;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+// Notified after every completed exchange, e.g. to log conversations for analytics
+// Runs on the generating thread, so slow observers delay the next reply
+pub trait ExchangeObserver: Send {
+    fn on_exchange(
+        &self,
+        user: &str,
+        reply: &str,
+        emotion: Option<&EmotionalContext>,
+        metrics: &GenerationMetrics,
+    );
+}
+
+// Observer that ignores every exchange; used when none is registered
+pub struct NoopObserver;
+
+impl ExchangeObserver for NoopObserver {
+    fn on_exchange(
+        &self,
+        _user: &str,
+        _reply: &str,
+        _emotion: Option<&EmotionalContext>,
+        _metrics: &GenerationMetrics,
+    ) {
+    }
+}
+
+// One line of a JSON-lines exchange log
+#[derive(Serialize)]
+struct ExchangeRecord<'a> {
+    timestamp: u64,
+    user: &'a str,
+    reply: &'a str,
+    emotion: Option<&'a EmotionalContext>,
+    metrics: &'a GenerationMetrics,
+}
+
+// Appends each exchange to a file as one JSON object per line
+pub struct JsonLinesObserver {
+    file: Mutex<File>,
+}
+
+impl JsonLinesObserver {
+    // Open `path` for appending, creating it if needed
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    fn write_record(&self, record: &ExchangeRecord) -> Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Exchange log lock poisoned: {}", e))?;
+        file.write_all(&line)?;
+        Ok(())
+    }
+}
+
+impl ExchangeObserver for JsonLinesObserver {
+    fn on_exchange(
+        &self,
+        user: &str,
+        reply: &str,
+        emotion: Option<&EmotionalContext>,
+        metrics: &GenerationMetrics,
+    ) {
+        let record = ExchangeRecord {
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            user,
+            reply,
+            emotion,
+            metrics,
+        };
+
+        // A failed log write must never fail the conversation
+        if let Err(e) = self.write_record(&record) {
+            eprintln!("⚠️  Failed to log exchange: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_lines_observer_appends_one_line_per_exchange() {
+        let path =
+            std::env::temp_dir().join(format!("aira_exchanges_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let observer = JsonLinesObserver::create(&path).unwrap();
+        let metrics = GenerationMetrics::default();
+        observer.on_exchange("Hi", "Hello!", None, &metrics);
+        observer.on_exchange("Bye", "See you!", None, &metrics);
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["user"], "Hi");
+        assert_eq!(lines[1]["reply"], "See you!");
+        assert_eq!(lines[1]["metrics"]["finish_reason"], "stop");
+    }
+}
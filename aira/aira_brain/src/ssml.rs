@@ -0,0 +1,327 @@
+// A small SSML subset for spoken text:
+//   <speak>...</speak>                  optional wrapper
+//   <break time="500ms"/>               silence, in ms or s (at most MAX_BREAK,
+//                                       MAX_TOTAL_BREAK across the whole text)
+//   <prosody rate="slow">...</prosody>  speaking rate: x-slow, slow, medium, fast, x-fast
+//                                       or a percentage like "80%"; nested rates multiply
+// Any other tag or attribute is rejected rather than read aloud
+use std::fmt;
+use std::time::Duration;
+
+// Longest single <break>, so one tag can't stall playback
+pub const MAX_BREAK: Duration = Duration::from_secs(10);
+
+// Most silence all the <break>s of one text add up to, so many tags can't either
+pub const MAX_TOTAL_BREAK: Duration = Duration::from_secs(30);
+
+// Tags `parse` understands; anything else with a `<` in it is plain text to `has_markup`
+const TAGS: [&str; 3] = ["speak", "break", "prosody"];
+
+// Piece of marked-up speech, in order
+#[derive(Debug, Clone, PartialEq)]
+pub enum SsmlSpan {
+    // Text spoken at `rate` times the voice's current speed
+    Text { text: String, rate: f32 },
+    Break(Duration),
+}
+
+// Markup outside the supported subset, or malformed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SsmlError(String);
+
+impl fmt::Display for SsmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid SSML: {}", self.0)
+    }
+}
+
+impl std::error::Error for SsmlError {}
+
+fn error(message: impl Into<String>) -> SsmlError {
+    SsmlError(message.into())
+}
+
+// Whether `text` contains one of the supported tags; plain text (even "x<y" or
+// "a < b") is spoken as is
+pub fn has_markup(text: &str) -> bool {
+    text.match_indices('<').any(|(i, _)| {
+        let after = &text[i + 1..];
+        let after = after.strip_prefix('/').unwrap_or(after);
+        TAGS.iter().any(|tag| {
+            after.strip_prefix(tag).is_some_and(|rest| {
+                rest.starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            })
+        })
+    })
+}
+
+// Parse `markup` into spans; adjacent text at the same rate is merged
+pub fn parse(markup: &str) -> Result<Vec<SsmlSpan>, SsmlError> {
+    let mut spans = Vec::new();
+    // Open tags, each with the rate in effect inside it
+    let mut open: Vec<(&str, f32)> = Vec::new();
+    let mut silence = Duration::ZERO;
+    let mut rest = markup;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            push_text(&mut spans, rest, current_rate(&open));
+            break;
+        };
+        push_text(&mut spans, &rest[..start], current_rate(&open));
+
+        let end = rest[start..]
+            .find('>')
+            .ok_or_else(|| error("unterminated tag"))?
+            + start;
+        let tag = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            match open.pop() {
+                Some((opened, _)) if opened == name => {}
+                Some((opened, _)) => {
+                    return Err(error(format!("</{}> closes <{}>", name, opened)));
+                }
+                None => return Err(error(format!("</{}> was never opened", name))),
+            }
+            continue;
+        }
+
+        let (body, self_closing) = match tag.strip_suffix('/') {
+            Some(body) => (body, true),
+            None => (tag, false),
+        };
+        let (name, attributes) = split_tag(body)?;
+
+        match name {
+            "break" => {
+                if !self_closing {
+                    return Err(error("<break> must be written <break .../>"));
+                }
+                let time = parse_time(single_attribute(name, &attributes, "time")?)?
+                    .min(MAX_TOTAL_BREAK.saturating_sub(silence));
+                silence += time;
+                spans.push(SsmlSpan::Break(time));
+            }
+            "prosody" | "speak" if self_closing => {}
+            "prosody" => {
+                let rate = parse_rate(single_attribute(name, &attributes, "rate")?)?;
+                open.push(("prosody", current_rate(&open) * rate));
+            }
+            "speak" => {
+                if let Some((attribute, _)) = attributes.first() {
+                    return Err(error(format!("<speak> has no attribute {}", attribute)));
+                }
+                let rate = current_rate(&open);
+                open.push(("speak", rate));
+            }
+            _ => {
+                return Err(error(format!(
+                    "unsupported tag <{}>; only <speak>, <break> and <prosody> are supported",
+                    name
+                )));
+            }
+        }
+    }
+
+    if let Some((name, _)) = open.last() {
+        return Err(error(format!("<{}> is never closed", name)));
+    }
+    Ok(spans)
+}
+
+fn current_rate(open: &[(&str, f32)]) -> f32 {
+    open.last().map_or(1.0, |(_, rate)| *rate)
+}
+
+fn push_text(spans: &mut Vec<SsmlSpan>, raw: &str, rate: f32) {
+    let text = decode_entities(raw);
+    if text.trim().is_empty() {
+        return;
+    }
+    if let Some(SsmlSpan::Text {
+        text: previous,
+        rate: previous_rate,
+    }) = spans.last_mut()
+        && *previous_rate == rate
+    {
+        previous.push_str(&text);
+        return;
+    }
+    spans.push(SsmlSpan::Text { text, rate });
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+// A `key="value"` pair inside a tag
+type Attribute<'a> = (&'a str, &'a str);
+
+// Name and attributes of a tag body
+fn split_tag(body: &str) -> Result<(&str, Vec<Attribute<'_>>), SsmlError> {
+    let body = body.trim();
+    let name_end = body.find(char::is_whitespace).unwrap_or(body.len());
+    let (name, mut rest) = body.split_at(name_end);
+    if name.is_empty() {
+        return Err(error("empty tag"));
+    }
+
+    let mut attributes = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        let (key, after) = rest
+            .split_once('=')
+            .ok_or_else(|| error(format!("attribute without a value in <{}>", name)))?;
+        let after = after.trim_start();
+        let quote = after
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| error(format!("unquoted attribute value in <{}>", name)))?;
+        let value_end = after[1..]
+            .find(quote)
+            .ok_or_else(|| error(format!("unterminated attribute value in <{}>", name)))?;
+        attributes.push((key.trim(), &after[1..value_end + 1]));
+        rest = &after[value_end + 2..];
+    }
+    Ok((name, attributes))
+}
+
+// The value of `tag`'s only attribute, which must be `key`
+fn single_attribute<'a>(
+    tag: &str,
+    attributes: &[Attribute<'a>],
+    key: &str,
+) -> Result<&'a str, SsmlError> {
+    match attributes {
+        [(found, value)] if *found == key => Ok(value),
+        [] => Err(error(format!("<{}> needs a {} attribute", tag, key))),
+        _ => Err(error(format!(
+            "<{}> supports only the {} attribute",
+            tag, key
+        ))),
+    }
+}
+
+// "500ms", "1.5s"
+fn parse_time(value: &str) -> Result<Duration, SsmlError> {
+    let value = value.trim();
+    let seconds = if let Some(ms) = value.strip_suffix("ms") {
+        ms.trim().parse::<f64>().ok().map(|ms| ms / 1000.0)
+    } else if let Some(s) = value.strip_suffix('s') {
+        s.trim().parse::<f64>().ok()
+    } else {
+        None
+    };
+
+    match seconds {
+        Some(seconds) if seconds.is_finite() && seconds >= 0.0 => Ok(Duration::from_secs_f64(
+            seconds.min(MAX_BREAK.as_secs_f64()),
+        )),
+        _ => Err(error(format!(
+            "break time \"{}\" is not like \"500ms\" or \"1s\"",
+            value
+        ))),
+    }
+}
+
+// Named rates or a percentage of the normal speed
+fn parse_rate(value: &str) -> Result<f32, SsmlError> {
+    let rate = match value.trim() {
+        "x-slow" => Some(0.5),
+        "slow" => Some(0.75),
+        "medium" => Some(1.0),
+        "fast" => Some(1.25),
+        "x-fast" => Some(1.5),
+        other => other
+            .strip_suffix('%')
+            .and_then(|percent| percent.trim().parse::<f32>().ok())
+            .map(|percent| percent / 100.0),
+    };
+
+    match rate {
+        Some(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        _ => Err(error(format!(
+            "prosody rate \"{}\" is not x-slow, slow, medium, fast, x-fast or a percentage",
+            value
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaks_and_nested_prosody_are_parsed() {
+        let spans = parse(
+            "<speak>Hello<break time=\"500ms\"/>\
+             <prosody rate=\"slow\">take it <prosody rate=\"200%\">easy</prosody></prosody> &amp; rest</speak>",
+        )
+        .unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                SsmlSpan::Text {
+                    text: "Hello".to_string(),
+                    rate: 1.0
+                },
+                SsmlSpan::Break(Duration::from_millis(500)),
+                SsmlSpan::Text {
+                    text: "take it ".to_string(),
+                    rate: 0.75
+                },
+                SsmlSpan::Text {
+                    text: "easy".to_string(),
+                    rate: 1.5
+                },
+                SsmlSpan::Text {
+                    text: " & rest".to_string(),
+                    rate: 1.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_tags_and_malformed_markup_are_rejected() {
+        let err = parse("Hi <emphasis>there</emphasis>").unwrap_err();
+        assert!(err.to_string().contains("unsupported tag <emphasis>"));
+
+        assert!(parse("<prosody pitch=\"high\">Hi</prosody>").is_err());
+        assert!(parse("<prosody rate=\"slow\">Hi").is_err());
+        assert!(parse("<break time=\"soon\"/>").is_err());
+
+        // Plain text is not markup, even with a comparison in it
+        assert!(!has_markup("1 < 2 and 3 > 2"));
+        assert!(!has_markup("if x<y then y>x"));
+        assert!(!has_markup("a <breakfast> menu"));
+        assert!(has_markup("Wait <break time=\"1s\"/>"));
+        assert!(has_markup("<speak>Hi</speak>"));
+    }
+
+    #[test]
+    fn test_total_break_time_is_capped() {
+        let markup = "<break time=\"10s\"/>".repeat(100);
+        let total: Duration = parse(&markup)
+            .unwrap()
+            .iter()
+            .map(|span| match span {
+                SsmlSpan::Break(time) => *time,
+                SsmlSpan::Text { .. } => Duration::ZERO,
+            })
+            .sum();
+        assert_eq!(total, MAX_TOTAL_BREAK);
+    }
+}
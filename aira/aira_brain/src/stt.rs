@@ -1,22 +1,137 @@
 use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::audio::{WHISPER_SAMPLE_RATE, pre_emphasis};
+use std::borrow::Cow;
 use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
 
-pub struct SttEngine {
-    ctx: WhisperContext,
+// Decoding options for the STT engine
+#[derive(Clone, Debug)]
+pub struct SttConfig {
+    // Ask whisper to suppress non-speech tokens while decoding
+    pub suppress_non_speech: bool,
+    // Strip annotations like "[BLANK_AUDIO]" or "(music)" from the output text
+    pub strip_annotations: bool,
+    // Initial decoding temperature
+    pub temperature: f32,
+    // Temperature step used to retry a segment that failed to decode (0 disables fallback)
+    pub temperature_inc: f32,
+    // A retry happens when the output is this repetitive (like a high compression ratio)...
+    pub entropy_thold: f32,
+    // ...or when the average token log probability drops below this
+    pub logprob_thold: f32,
+    // Candidates the greedy decoder weighs per token; more is slower but more accurate
+    pub best_of: i32,
+    // Pre-emphasis coefficient applied before decoding; helps muffled mics (None = off)
+    pub pre_emphasis: Option<f32>,
 }
 
-impl SttEngine {
-    pub fn load(model_path: &str) -> Result<Self> {
-        let ctx = WhisperContext::new_with_params(model_path, WhisperContextParameters::default())?;
+impl Default for SttConfig {
+    fn default() -> Self {
+        Self {
+            suppress_non_speech: true,
+            strip_annotations: true,
+            // Whisper's recommended fallback schedule: 0.0, 0.2, ... 1.0
+            temperature: 0.0,
+            temperature_inc: 0.2,
+            entropy_thold: 2.4,
+            logprob_thold: -1.0,
+            best_of: 1,
+            pre_emphasis: None,
+        }
+    }
+}
 
-        Ok(Self { ctx })
+impl SttConfig {
+    fn sampling_strategy(&self) -> SamplingStrategy {
+        SamplingStrategy::Greedy {
+            best_of: self.best_of.max(1),
+        }
     }
 
-    pub fn transcribe(&self, audio: &[f32]) -> Result<String> {
-        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    fn apply_fallback(&self, params: &mut FullParams) {
+        params.set_temperature(self.temperature);
+        params.set_temperature_inc(self.temperature_inc);
+        params.set_entropy_thold(self.entropy_thold);
+        params.set_logprob_thold(self.logprob_thold);
+    }
+}
+
+// A stretch of the input where speech was heard, in seconds from the start
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SpeechSegment {
+    pub start: f32,
+    pub end: f32,
+}
+
+// Transcribed text along with where in the audio it was spoken
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Transcription {
+    pub text: String,
+    pub segments: Vec<SpeechSegment>,
+    // Mean confidence (0.0 - 1.0) of the spoken segments; 0.0 when nothing was said
+    pub confidence: f32,
+}
+
+// One decoded stretch of speech, as handed out while a recording is still being transcribed
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TranscribedSegment {
+    pub text: String,
+    // Seconds from the start of the recording
+    pub start: f32,
+    pub end: f32,
+    // Mean probability Whisper gave the segment's tokens (0.0 - 1.0); low on noisy or mumbled audio
+    pub confidence: f32,
+}
+
+// A segment as Whisper reports it, timed in centiseconds
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimedSegment {
+    pub start: i64,
+    pub end: i64,
+    pub text: String,
+    // Mean probability of the segment's word tokens (0.0 - 1.0)
+    pub confidence: f32,
+}
+
+impl TimedSegment {
+    // Annotation-only segments like "[BLANK_AUDIO]" hold no speech
+    fn is_spoken(&self) -> bool {
+        !strip_annotations(&self.text).is_empty() && self.end > self.start
+    }
+}
+
+// Long recordings are transcribed in windows this long, each overlapping the previous one
+// so words at a boundary are heard whole by at least one window
+const LONG_WINDOW_SECS: usize = 30;
+const LONG_OVERLAP_SECS: usize = 2;
+
+// Decodes one recording into timed segments
+// whisper.cpp is used in production; tests can plug in a scripted backend
+// Each call must decode its recording on its own: a backend may keep state behind `&self`
+// (a Mutex, a cache), but nothing one recording leaves behind may shape the next
+pub trait SttBackend: Send {
+    fn decode(&self, audio: &[f32], config: &SttConfig) -> Result<Vec<TimedSegment>>;
+}
+
+// whisper.cpp backed decoding
+// The context holds only the model weights; every call decodes in a state of its own
+struct WhisperBackend {
+    ctx: WhisperContext,
+}
+
+impl SttBackend for WhisperBackend {
+    fn decode(&self, audio: &[f32], config: &SttConfig) -> Result<Vec<TimedSegment>> {
+        let mut params = FullParams::new(config.sampling_strategy());
         params.set_language(Some("en"));
         params.set_n_threads(4);
+        params.set_suppress_nst(config.suppress_non_speech);
+        // Never prompt with text decoded earlier: each recording is heard on its own
+        params.set_no_context(true);
+        // Retry hard audio at higher temperatures instead of returning garbage
+        config.apply_fallback(&mut params);
 
+        // A fresh state per recording, dropped when decoding is done
         let mut state = self
             .ctx
             .create_state()
@@ -24,11 +139,502 @@ impl SttEngine {
 
         state.full(params, audio)?;
 
-        let mut text = String::new();
+        let mut timed = Vec::new();
         for seg in state.as_iter() {
-            text.push_str(seg.to_str()?);
+            // Timestamp and other special tokens say nothing about how well words were heard
+            let probabilities: Vec<f32> = (0..seg.n_tokens())
+                .filter_map(|i| seg.get_token(i))
+                .filter(|token| {
+                    token
+                        .to_str()
+                        .is_ok_and(|text| !text.starts_with("[_") && !text.starts_with("<|"))
+                })
+                .map(|token| token.token_probability())
+                .collect();
+            timed.push(TimedSegment {
+                start: seg.start_timestamp(),
+                end: seg.end_timestamp(),
+                text: seg.to_str()?.to_string(),
+                confidence: segment_confidence(&probabilities),
+            });
+        }
+        Ok(timed)
+    }
+}
+
+pub struct SttEngine {
+    backend: Box<dyn SttBackend>,
+    config: SttConfig,
+}
+
+impl SttEngine {
+    pub fn load(model_path: &str) -> Result<Self> {
+        Self::load_with_config(model_path, SttConfig::default())
+    }
+
+    pub fn load_with_config(model_path: &str, config: SttConfig) -> Result<Self> {
+        let ctx = WhisperContext::new_with_params(model_path, WhisperContextParameters::default())?;
+
+        Ok(Self::with_backend(Box::new(WhisperBackend { ctx }), config))
+    }
+
+    // Engine around any decoding backend (e.g. a scripted one in tests)
+    pub fn with_backend(backend: Box<dyn SttBackend>, config: SttConfig) -> Self {
+        Self { backend, config }
+    }
+
+    pub fn transcribe(&self, audio: &[f32]) -> Result<String> {
+        Ok(self.transcribe_with_segments(audio)?.text)
+    }
+
+    // Like `transcribe`, but also reports the time ranges that contained speech
+    pub fn transcribe_with_segments(&self, audio: &[f32]) -> Result<Transcription> {
+        let audio = self.prepare(audio);
+        let timed = self.backend.decode(&audio, &self.config)?;
+        Ok(self.assemble(&timed))
+    }
+
+    // Transcribe a recording of any length in overlapping windows
+    // Segment times are absolute within the whole recording, with overlaps transcribed only once
+    pub fn transcribe_long(&self, audio: &[f32]) -> Result<Transcription> {
+        self.transcribe_streamed(audio, &mut |_| {})
+    }
+
+    // Like `transcribe_long`, but hands out each spoken segment as soon as its window is decoded
+    pub fn transcribe_streamed(
+        &self,
+        audio: &[f32],
+        on_segment: &mut dyn FnMut(TranscribedSegment),
+    ) -> Result<Transcription> {
+        // Filter the whole recording at once so windows don't restart the filter
+        let prepared = self.prepare(audio);
+        let audio = &*prepared;
+        let window = LONG_WINDOW_SECS * WHISPER_SAMPLE_RATE as usize;
+        let step = (LONG_WINDOW_SECS - LONG_OVERLAP_SECS) * WHISPER_SAMPLE_RATE as usize;
+        // Windows step along until one reaches the end, even for audio shorter than a window
+        let mut starts = vec![0];
+        while starts[starts.len() - 1] + window < audio.len() {
+            starts.push(starts[starts.len() - 1] + step);
+        }
+        let offsets: Vec<i64> = starts
+            .iter()
+            .map(|start| (start * 100 / WHISPER_SAMPLE_RATE as usize) as i64)
+            .collect();
+        let overlap = (LONG_OVERLAP_SECS * 100) as i64;
+
+        let mut timed = Vec::new();
+        for (i, (start, (boundary, next_boundary))) in starts
+            .iter()
+            .zip(window_boundaries(&offsets, overlap))
+            .enumerate()
+        {
+            let end = (start + window).min(audio.len());
+            let decoded = self.backend.decode(&audio[*start..end], &self.config)?;
+            for segment in stitch_window(offsets[i], decoded, boundary, next_boundary) {
+                if segment.is_spoken() {
+                    on_segment(transcribed_segment(&segment, self.config.strip_annotations));
+                }
+                timed.push(segment);
+            }
+        }
+        Ok(self.assemble(&timed))
+    }
+
+    // The audio as the decoder should hear it, after any configured pre-emphasis
+    fn prepare<'a>(&self, audio: &'a [f32]) -> Cow<'a, [f32]> {
+        match self.config.pre_emphasis {
+            Some(coefficient) => Cow::Owned(pre_emphasis(audio, coefficient)),
+            None => Cow::Borrowed(audio),
+        }
+    }
+
+    fn assemble(&self, timed: &[TimedSegment]) -> Transcription {
+        let mut text: String = timed.iter().map(|segment| segment.text.as_str()).collect();
+        if self.config.strip_annotations {
+            text = strip_annotations(&text);
+        }
+
+        let spoken: Vec<f32> = timed
+            .iter()
+            .filter(|segment| segment.is_spoken())
+            .map(|segment| segment.confidence)
+            .collect();
+        Transcription {
+            text: text.trim().to_string(),
+            segments: speech_segments(timed),
+            confidence: segment_confidence(&spoken),
+        }
+    }
+}
+
+// Public form of a segment, in seconds, with annotations stripped if configured
+fn transcribed_segment(segment: &TimedSegment, strip: bool) -> TranscribedSegment {
+    let text = if strip {
+        strip_annotations(&segment.text)
+    } else {
+        segment.text.trim().to_string()
+    };
+    TranscribedSegment {
+        text,
+        start: segment.start as f32 / 100.0,
+        end: segment.end as f32 / 100.0,
+        confidence: segment.confidence,
+    }
+}
+
+// Mean of token (or segment) probabilities, kept within 0.0 - 1.0; nothing heard is no confidence
+fn segment_confidence(probabilities: &[f32]) -> f32 {
+    if probabilities.is_empty() {
+        return 0.0;
+    }
+    let mean = probabilities.iter().sum::<f32>() / probabilities.len() as f32;
+    mean.clamp(0.0, 1.0)
+}
+
+// Remove non-speech annotations whisper emits, e.g. "[BLANK_AUDIO]", "(music)" or "♪"
+// Only closed groups that stand apart from the words around them count; a stray "[" or
+// "(" and anything after it is kept as spoken
+pub fn strip_annotations(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let closing = match c {
+            '[' => Some(']'),
+            '(' => Some(')'),
+            _ => None,
+        };
+        let standalone = result.chars().last().is_none_or(char::is_whitespace);
+        if let Some(end) = closing
+            && standalone
+            && let Some(len) = annotation_len(&rest[1..], end)
+        {
+            rest = &rest[1 + len..];
+            continue;
+        }
+
+        if !matches!(c, '♪' | '♫' | '🎵' | '🎶') {
+            result.push(c);
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+
+    // Collapse the whitespace left behind by removed annotations
+    result.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Length of an annotation body up to and including `end`, if it closes before another
+// group opens and isn't glued to the next word
+fn annotation_len(body: &str, end: char) -> Option<usize> {
+    let close = body.find([end, '[', '('])?;
+    if !body[close..].starts_with(end) {
+        return None;
+    }
+    let after = &body[close + end.len_utf8()..];
+    let separated = after
+        .chars()
+        .next()
+        .is_none_or(|next| next.is_whitespace() || next.is_ascii_punctuation());
+    separated.then_some(close + end.len_utf8())
+}
+
+// Range of the recording timeline each window owns, given the windows' start offsets
+// Where two windows overlap, the halfway point decides, so no stretch of speech is kept twice
+fn window_boundaries(offsets: &[i64], overlap: i64) -> Vec<(i64, i64)> {
+    (0..offsets.len())
+        .map(|i| {
+            let boundary = if i == 0 {
+                i64::MIN
+            } else {
+                offsets[i] + overlap / 2
+            };
+            let next_boundary = offsets
+                .get(i + 1)
+                .map_or(i64::MAX, |next_offset| next_offset + overlap / 2);
+            (boundary, next_boundary)
+        })
+        .collect()
+}
+
+// Shift a window's segments by its `offset` and keep those whose midpoint it owns
+fn stitch_window(
+    offset: i64,
+    timed: Vec<TimedSegment>,
+    boundary: i64,
+    next_boundary: i64,
+) -> Vec<TimedSegment> {
+    timed
+        .into_iter()
+        .filter_map(|segment| {
+            let (start, end) = (segment.start + offset, segment.end + offset);
+            let midpoint = (start + end) / 2;
+            (midpoint >= boundary && midpoint < next_boundary).then(|| TimedSegment {
+                start: start.max(boundary),
+                end,
+                ..segment
+            })
+        })
+        .collect()
+}
+
+// Turn whisper's (start, end, text) segments, timed in centiseconds, into speech ranges
+// Segments holding only annotations like "[BLANK_AUDIO]" are silence; touching ranges are merged
+fn speech_segments(timed: &[TimedSegment]) -> Vec<SpeechSegment> {
+    let mut segments: Vec<SpeechSegment> = Vec::new();
+
+    for segment in timed {
+        if !segment.is_spoken() {
+            continue;
+        }
+        let (start, end) = (segment.start as f32 / 100.0, segment.end as f32 / 100.0);
+        match segments.last_mut() {
+            Some(last) if start <= last.end => last.end = last.end.max(end),
+            _ => segments.push(SpeechSegment { start, end }),
+        }
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_strip_bracketed_annotations() {
+        assert_eq!(strip_annotations("[BLANK_AUDIO]"), "");
+        assert_eq!(
+            strip_annotations(" [Music] Hello there. [BLANK_AUDIO]"),
+            "Hello there."
+        );
+    }
+
+    #[test]
+    fn test_strip_parenthesized_and_music_symbols() {
+        assert_eq!(
+            strip_annotations("♪ (upbeat music) ♪ How are you?"),
+            "How are you?"
+        );
+    }
+
+    #[test]
+    fn test_unclosed_brackets_keep_the_rest_of_the_transcript() {
+        assert_eq!(
+            strip_annotations("I said [um and then left"),
+            "I said [um and then left"
+        );
+        assert_eq!(
+            strip_annotations("Call me (maybe tomorrow. [BLANK_AUDIO]"),
+            "Call me (maybe tomorrow."
+        );
+        // Brackets inside a word aren't annotations
+        assert_eq!(strip_annotations("Try f(x) first"), "Try f(x) first");
+    }
+
+    #[test]
+    fn test_fallback_defaults_match_whisper_cpp() {
+        // Whisper's own schedule, as FullParams starts from it before apply_fallback
+        let whisper = unsafe {
+            whisper_rs_sys::whisper_full_default_params(
+                whisper_rs_sys::whisper_sampling_strategy_WHISPER_SAMPLING_GREEDY,
+            )
+        };
+        let config = SttConfig::default();
+        assert_eq!(config.temperature, whisper.temperature);
+        assert_eq!(config.temperature_inc, whisper.temperature_inc);
+        assert_eq!(config.entropy_thold, whisper.entropy_thold);
+        assert_eq!(config.logprob_thold, whisper.logprob_thold);
+    }
+
+    #[test]
+    fn test_best_of_reaches_the_greedy_strategy() {
+        let config = SttConfig {
+            best_of: 3,
+            ..SttConfig::default()
+        };
+        assert!(matches!(
+            config.sampling_strategy(),
+            SamplingStrategy::Greedy { best_of: 3 }
+        ));
+        assert!(matches!(
+            SttConfig::default().sampling_strategy(),
+            SamplingStrategy::Greedy { best_of: 1 }
+        ));
+    }
+
+    fn is_loud(audio: &[f32]) -> bool {
+        audio.iter().any(|sample| sample.abs() > 0.5)
+    }
+
+    // Hears a recording as "yes" or "no" by its loudness, keeping every input it was given
+    // With `remember`, a loud recording leaks into the next one, as a stateful backend would
+    #[derive(Default)]
+    struct LoudnessBackend {
+        inputs: Arc<Mutex<Vec<Vec<f32>>>>,
+        remember: bool,
+    }
+
+    impl SttBackend for LoudnessBackend {
+        fn decode(&self, audio: &[f32], _config: &SttConfig) -> Result<Vec<TimedSegment>> {
+            let mut inputs = self.inputs.lock().unwrap();
+            let leaked = self.remember && inputs.last().is_some_and(|last| is_loud(last));
+            inputs.push(audio.to_vec());
+            let loud = leaked || is_loud(audio);
+            Ok(vec![timed(0, 100, if loud { " Yes." } else { " No." })])
+        }
+    }
+
+    // Transcribe quiet, loud, then quiet audio; both quiet recordings must read the same
+    fn assert_transcribed_independently(engine: &SttEngine) {
+        let quiet = vec![0.1; 16_000];
+        let loud = vec![0.9; 16_000];
+
+        let first = engine.transcribe_with_segments(&quiet).unwrap();
+        assert_eq!(engine.transcribe(&loud).unwrap(), "Yes.");
+        let again = engine.transcribe_with_segments(&quiet).unwrap();
+
+        assert_eq!(first.text, "No.");
+        assert_eq!(again.text, first.text, "the loud recording carried over");
+        assert_eq!(again.segments, first.segments);
+        assert_eq!(again.confidence, first.confidence);
+    }
+
+    #[test]
+    fn test_back_to_back_transcriptions_do_not_influence_each_other() {
+        let inputs = Arc::new(Mutex::new(Vec::new()));
+        let backend = LoudnessBackend {
+            inputs: inputs.clone(),
+            remember: false,
+        };
+        // Pre-emphasis filters every recording, and must start over each time
+        let config = SttConfig {
+            pre_emphasis: Some(0.97),
+            ..SttConfig::default()
+        };
+        let engine = SttEngine::with_backend(Box::new(backend), config);
+
+        assert_transcribed_independently(&engine);
+        // The backend was handed the same samples for the same audio
+        let inputs = inputs.lock().unwrap();
+        assert_eq!(inputs.len(), 3);
+        assert_eq!(inputs[2], inputs[0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "carried over")]
+    fn test_backend_that_remembers_a_recording_is_caught() {
+        let backend = LoudnessBackend {
+            remember: true,
+            ..LoudnessBackend::default()
+        };
+        let engine = SttEngine::with_backend(Box::new(backend), SttConfig::default());
+        assert_transcribed_independently(&engine);
+    }
+
+    #[test]
+    fn test_plain_speech_is_untouched() {
+        assert_eq!(
+            strip_annotations("I want to talk about my day."),
+            "I want to talk about my day."
+        );
+    }
+
+    fn timed(start: i64, end: i64, text: &str) -> TimedSegment {
+        TimedSegment {
+            start,
+            end,
+            text: text.to_string(),
+            confidence: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_gap_in_speech_splits_segments() {
+        let timed = vec![
+            timed(0, 120, " Hello there."),
+            timed(120, 180, " How are you?"),
+            timed(180, 400, " [BLANK_AUDIO]"),
+            timed(400, 550, " I'm back."),
+        ];
+
+        let segments = speech_segments(&timed);
+        assert_eq!(
+            segments,
+            vec![
+                SpeechSegment {
+                    start: 0.0,
+                    end: 1.8
+                },
+                SpeechSegment {
+                    start: 4.0,
+                    end: 5.5
+                },
+            ]
+        );
+        assert!(segments[1].start - segments[0].end > 2.0);
+    }
+
+    #[test]
+    fn test_stitched_segments_are_absolute_across_windows() {
+        // Two 30 s windows starting 28 s apart; both heard the words in the 2 s overlap
+        let windows = vec![
+            (
+                0,
+                vec![
+                    timed(0, 1500, " First part."),
+                    timed(2750, 2950, " Boundary."),
+                ],
+            ),
+            (
+                2800,
+                vec![
+                    timed(0, 150, " Boundary."),
+                    timed(200, 900, " Second part."),
+                ],
+            ),
+        ];
+
+        let offsets: Vec<i64> = windows.iter().map(|(offset, _)| *offset).collect();
+        let stitched: Vec<TimedSegment> = windows
+            .into_iter()
+            .zip(window_boundaries(&offsets, 200))
+            .flat_map(|((offset, timed), (boundary, next_boundary))| {
+                stitch_window(offset, timed, boundary, next_boundary)
+            })
+            .collect();
+        let texts: Vec<&str> = stitched.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, vec![" First part.", " Boundary.", " Second part."]);
+        // Second-window times are offset into the recording
+        assert_eq!(stitched[2].start, 3000);
+        assert_eq!(stitched[2].end, 3700);
+        assert!(stitched.windows(2).all(|pair| pair[0].end <= pair[1].start));
+    }
+
+    #[test]
+    fn test_unclear_audio_gets_lower_segment_confidence() {
+        // Token probabilities as Whisper reports them for clear and for noisy speech
+        let clear = segment_confidence(&[0.97, 0.92, 0.95, 0.99]);
+        let noisy = segment_confidence(&[0.41, 0.22, 0.65, 0.30]);
+        for confidence in [clear, noisy] {
+            assert!((0.0..=1.0).contains(&confidence));
         }
+        assert!(noisy < clear);
+        assert_eq!(segment_confidence(&[]), 0.0);
 
-        Ok(text.trim().to_string())
+        // The streamed segment carries its confidence along with its times in seconds
+        let segment = TimedSegment {
+            confidence: noisy,
+            ..timed(250, 400, " [Music] Maybe?")
+        };
+        assert_eq!(
+            transcribed_segment(&segment, true),
+            TranscribedSegment {
+                text: "Maybe?".to_string(),
+                start: 2.5,
+                end: 4.0,
+                confidence: noisy,
+            }
+        );
     }
 }
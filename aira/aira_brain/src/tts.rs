@@ -1,32 +1,987 @@
+use crate::audio::{MAX_GAIN, resample};
+use crate::ssml::SsmlSpan;
 use anyhow::Result;
-use piper_rs::{self, synth::PiperSpeechSynthesizer};
+use piper_rs::{PiperSynthesisConfig, synth::PiperSpeechSynthesizer};
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+// Output rate of most Piper voices; the loaded voice's config is authoritative
+pub const PIPER_SAMPLE_RATE: u32 = 22050;
+
+// Default crossfade between synthesized chunks
+const DEFAULT_CROSSFADE_MS: u32 = 10;
+
+// Longest text handed to Piper in one call; longer input is split at sentence ends
+const DEFAULT_MAX_TEXT_CHARS: usize = 500;
+
+// Default silence between lines and list items
+const DEFAULT_LINE_PAUSE_MS: u32 = 300;
+
+// Speaking speed multipliers accepted by `set_speed` (1.0 = the voice's own pace)
+pub const MIN_SPEED: f32 = 0.5;
+pub const MAX_SPEED: f32 = 2.0;
 
 // Thread-safe TTS engine using Arc for shared ownership
 #[derive(Clone)]
 pub struct TtsEngine {
     tts: Arc<PiperSpeechSynthesizer>,
+    // Native output rate of the loaded voice
+    sample_rate: u32,
+    // Overlap between consecutive chunks, in samples (0 = plain concatenation)
+    crossfade_samples: usize,
+    // Texts longer than this (in chars) are synthesized sentence chunk by chunk
+    max_text_chars: usize,
+    // Default output gain applied before playback or WAV encoding
+    volume: f32,
+    // Shared voice settings, including the speaking speed every clone speaks at
+    voice: VoiceSettings,
+    // Language the voice speaks, as labelled in its config (e.g. "fr_FR")
+    language: Option<String>,
+    // Silence between lines, in samples; None runs lines together like one paragraph
+    line_pause_samples: Option<usize>,
 }
 
 impl TtsEngine {
     pub fn load(config_path: &str) -> Result<Self> {
         let model = piper_rs::from_config_path(Path::new(config_path))?;
+        let sample_rate = model.audio_output_info()?.sample_rate as u32;
+        let base_synthesis = model
+            .get_default_synthesis_config()?
+            .downcast::<PiperSynthesisConfig>()
+            .ok()
+            .map(|config| *config);
+        let voice = VoiceSettings::new(
+            base_synthesis,
+            Arc::new({
+                let model = Arc::clone(&model);
+                move |config: &PiperSynthesisConfig| Ok(model.set_fallback_synthesis_config(config)?)
+            }),
+        );
         let tts = PiperSpeechSynthesizer::new(model)?;
-        Ok(Self { tts: Arc::new(tts) })
+        let language = voice_language(config_path);
+        Ok(Self {
+            tts: Arc::new(tts),
+            sample_rate,
+            crossfade_samples: crossfade_len(DEFAULT_CROSSFADE_MS, sample_rate),
+            max_text_chars: DEFAULT_MAX_TEXT_CHARS,
+            volume: 1.0,
+            voice,
+            language,
+            line_pause_samples: Some(crossfade_len(DEFAULT_LINE_PAUSE_MS, sample_rate)),
+        })
+    }
+
+    // Language of the loaded voice, if its config names one
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    // Set the crossfade between chunks in milliseconds (0 disables it)
+    pub fn set_crossfade_ms(&mut self, ms: u32) {
+        self.crossfade_samples = crossfade_len(ms, self.sample_rate);
+    }
+
+    // Voice each line (e.g. list item or verse) on its own with `ms` of silence between
+    // None ignores line breaks, so lines run together as one paragraph
+    pub fn set_line_pause_ms(&mut self, ms: Option<u32>) {
+        self.line_pause_samples = ms.map(|ms| crossfade_len(ms, self.sample_rate));
+    }
+
+    // Set the longest text synthesized in a single Piper call
+    pub fn set_max_text_chars(&mut self, max_chars: usize) {
+        self.max_text_chars = max_chars.max(1);
+    }
+
+    // Set the default output gain (1.0 = unchanged, clamped to 0.0 - MAX_GAIN)
+    pub fn set_volume(&mut self, volume: f32) {
+        if volume.is_finite() {
+            self.volume = volume.clamp(0.0, MAX_GAIN);
+        }
+    }
+
+    // Default output gain; synthesis itself leaves samples unscaled
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    // Set the speaking speed (clamped to MIN_SPEED - MAX_SPEED) and return the applied value
+    // The setting lives in the shared voice, so every clone of this engine speaks at it
+    pub fn set_speed(&mut self, speed: f32) -> Result<f32> {
+        if !speed.is_finite() {
+            return Ok(self.speed());
+        }
+        self.voice.set_speed(speed)
+    }
+
+    pub fn speed(&self) -> f32 {
+        *self.voice.shared()
+    }
+
+    // Sample rate of the audio returned by `synthesize`
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // Synthesize and convert to `target_rate`, skipping the resample when rates match
+    pub fn synthesize_at(&self, text: &str, target_rate: u32) -> Result<Vec<f32>> {
+        Ok(resample(
+            self.synthesize(text)?,
+            self.sample_rate,
+            target_rate,
+        ))
     }
 
     // Synthesize text to audio samples
-    // Returns f32 samples at 22050 Hz
+    // Returns f32 samples at the voice's native rate (see `sample_rate`); blank text gives no samples
+    // Long text is split into sentence chunks so no single Piper call runs unbounded
+    // Line breaks end a chunk too, with a pause after each line (see `set_line_pause_ms`)
     pub fn synthesize(&self, text: &str) -> Result<Vec<f32>> {
+        let _voice = self.voice.shared();
+        self.synthesize_text(text)
+    }
+
+    // `synthesize` for callers that already hold the voice
+    fn synthesize_text(&self, text: &str) -> Result<Vec<f32>> {
+        synthesize_lines(text, self.line_pause_samples, |line| {
+            synthesize_chunked(line, self.max_text_chars, self.crossfade_samples, |chunk| {
+                self.synthesize_one(chunk)
+            })
+        })
+    }
+
+    // Like `synthesize`, but hands over audio piece by piece as Piper produces it
+    // Lets playback start before a long sentence is fully voiced; pieces are not crossfaded
+    pub fn synthesize_streamed(
+        &self,
+        text: &str,
+        on_audio: &mut dyn FnMut(Vec<f32>) -> Result<()>,
+    ) -> Result<()> {
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+
+        let _voice = self.voice.shared();
+        let lines = match self.line_pause_samples {
+            Some(_) => speech_lines(text),
+            None => vec![text.to_string()],
+        };
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0
+                && let Some(pause) = self.line_pause_samples
+                && pause > 0
+            {
+                on_audio(vec![0.0; pause])?;
+            }
+            for chunk in split_text_chunks(line, self.max_text_chars) {
+                for audio in self.tts.synthesize_parallel(chunk, None)? {
+                    let samples = audio?.into_vec();
+                    if !samples.is_empty() {
+                        on_audio(samples)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Synthesize parsed SSML spans (see `ssml::parse`) at the voice's native rate
+    pub fn synthesize_spans(&self, spans: &[SsmlSpan]) -> Result<Vec<f32>> {
+        render_spans(
+            spans,
+            self.sample_rate,
+            self.crossfade_samples,
+            |text, rate| self.synthesize_at_rate(text, rate),
+        )
+    }
+
+    // Synthesize `text` at `rate` times the current speed without changing the setting
+    // The voice's length_scale is shared, so it is swapped in for this call and restored after
+    fn synthesize_at_rate(&self, text: &str, rate: f32) -> Result<Vec<f32>> {
+        if rate == 1.0 {
+            return self.synthesize(text);
+        }
+        self.voice.with_rate(rate, || self.synthesize_text(text))
+    }
+
+    // Synthesize like `synthesize`, also estimating when each word is spoken
+    // Sentences are voiced one at a time, so timings are exact at sentence seams
+    // and spread by word length in between
+    pub fn synthesize_captioned(&self, text: &str) -> Result<(Vec<f32>, Vec<WordTiming>)> {
+        let _voice = self.voice.shared();
+        synthesize_captioned_chunks(
+            text,
+            self.max_text_chars,
+            self.crossfade_samples,
+            self.sample_rate,
+            |chunk| self.synthesize_one(chunk),
+        )
+    }
+
+    fn synthesize_one(&self, text: &str) -> Result<Vec<f32>> {
         let chunks = self.tts.synthesize_parallel(text.to_string(), None)?;
         let mut samples = Vec::new();
 
         for chunk in chunks {
-            samples.extend(chunk?);
+            // Blend chunk seams so they don't click
+            append_crossfaded(&mut samples, &chunk?.into_vec(), self.crossfade_samples);
         }
 
         Ok(samples)
     }
 }
 
+// Synthesis settings of the loaded voice, shared by every clone of the engine
+#[derive(Clone)]
+struct VoiceSettings {
+    // The voice's own settings, which speed changes are relative to
+    base: Option<PiperSynthesisConfig>,
+    // Hands new settings to the voice
+    apply: ApplySynthesis,
+    // Speed the voice is set to; read while synthesizing, written while it changes,
+    // so a speed swapped in for one caller is never heard in another's audio
+    speed: Arc<RwLock<f32>>,
+}
+
+type ApplySynthesis = Arc<dyn Fn(&PiperSynthesisConfig) -> Result<()> + Send + Sync>;
+
+impl VoiceSettings {
+    fn new(base: Option<PiperSynthesisConfig>, apply: ApplySynthesis) -> Self {
+        Self {
+            base,
+            apply,
+            speed: Arc::new(RwLock::new(1.0)),
+        }
+    }
+
+    // Hold the voice at its current speed while synthesizing
+    fn shared(&self) -> RwLockReadGuard<'_, f32> {
+        // The speed is only stored once applied, so a panic mid-change leaves nothing broken
+        self.speed.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Change the speed for everyone, once no one is mid-synthesis
+    fn set_speed(&self, speed: f32) -> Result<f32> {
+        let mut current = self.speed.write().unwrap_or_else(|e| e.into_inner());
+        *current = self.apply_speed(speed)?;
+        Ok(*current)
+    }
+
+    // Run `synthesize` with the voice at `rate` times its speed, then put the speed back
+    fn with_rate<T>(&self, rate: f32, synthesize: impl FnOnce() -> Result<T>) -> Result<T> {
+        let current = self.speed.write().unwrap_or_else(|e| e.into_inner());
+        self.apply_speed(*current * rate)?;
+        let result = synthesize();
+        self.apply_speed(*current)?;
+        result
+    }
+
+    // Make the voice speak at `speed` (clamped to MIN_SPEED - MAX_SPEED); returns the applied speed
+    fn apply_speed(&self, speed: f32) -> Result<f32> {
+        let Some(base) = &self.base else {
+            anyhow::bail!("This voice does not support changing the speaking speed");
+        };
+
+        let speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+        (self.apply)(&synthesis_config_for_speed(base, speed))?;
+        Ok(speed)
+    }
+}
+
+// Piper settings for speaking at `speed`; a larger length_scale means slower speech
+fn synthesis_config_for_speed(base: &PiperSynthesisConfig, speed: f32) -> PiperSynthesisConfig {
+    PiperSynthesisConfig {
+        length_scale: base.length_scale / speed,
+        ..base.clone()
+    }
+}
+
+// Voice each line of `text` with `synth`, putting `line_pause` samples of silence between
+// Without a pause the text is voiced whole, line breaks and all
+fn synthesize_lines<F>(text: &str, line_pause: Option<usize>, mut synth: F) -> Result<Vec<f32>>
+where
+    F: FnMut(&str) -> Result<Vec<f32>>,
+{
+    let Some(pause) = line_pause else {
+        return synth(text);
+    };
+
+    let mut samples = Vec::new();
+    for (i, line) in speech_lines(text).iter().enumerate() {
+        if i > 0 {
+            samples.resize(samples.len() + pause, 0.0);
+        }
+        samples.extend(synth(line)?);
+    }
+    Ok(samples)
+}
+
+// Non-blank lines of `text` as they should be spoken: bullets are dropped and
+// "1." or "1)" list numbers read as "1," so they don't end a sentence of their own
+fn speech_lines(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            if let Some(item) = line.strip_prefix(['-', '*', '•', '+'])
+                && item.starts_with(' ')
+            {
+                return item.trim_start().to_string();
+            }
+            let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+            if digits > 0
+                && let Some(item) = line[digits..].strip_prefix(['.', ')'])
+                && item.starts_with(' ')
+            {
+                return format!("{},{}", &line[..digits], item);
+            }
+            line.to_string()
+        })
+        .collect()
+}
+
+// Run `synth` on each chunk of `text` in order and join the audio
+fn synthesize_chunked<F>(
+    text: &str,
+    max_chars: usize,
+    fade_len: usize,
+    mut synth: F,
+) -> Result<Vec<f32>>
+where
+    F: FnMut(&str) -> Result<Vec<f32>>,
+{
+    // Nothing to say; Piper is never asked to voice an empty string
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    if text.chars().count() <= max_chars {
+        return synth(text);
+    }
+
+    let mut samples = Vec::new();
+    for chunk in split_text_chunks(text, max_chars) {
+        append_crossfaded(&mut samples, &synth(&chunk)?, fade_len);
+    }
+    Ok(samples)
+}
+
+// When a word is spoken, in seconds from the start of the audio
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct WordTiming {
+    pub word: String,
+    pub start: f32,
+    pub end: f32,
+}
+
+// Run `synth` on each sentence of `text` and time its words within that sentence's audio
+fn synthesize_captioned_chunks<F>(
+    text: &str,
+    max_chars: usize,
+    fade_len: usize,
+    sample_rate: u32,
+    mut synth: F,
+) -> Result<(Vec<f32>, Vec<WordTiming>)>
+where
+    F: FnMut(&str) -> Result<Vec<f32>>,
+{
+    let seconds = |samples: usize| samples as f32 / sample_rate as f32;
+    let mut samples = Vec::new();
+    let mut words = Vec::new();
+
+    for sentence in split_sentences(text) {
+        for chunk in split_text_chunks(sentence, max_chars) {
+            // Timed from the previous chunk's end; the crossfade overlap is too short to matter
+            let start = samples.len();
+            append_crossfaded(&mut samples, &synth(&chunk)?, fade_len);
+            words.extend(spread_words(&chunk, seconds(start), seconds(samples.len())));
+        }
+    }
+    Ok((samples, words))
+}
+
+// Share `start..end` among the words of `text` by length, leaving a pause after punctuation
+// The last word runs to `end`, so the words cover the whole span
+fn spread_words(text: &str, start: f32, end: f32) -> Vec<WordTiming> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let weights: Vec<(f32, f32)> = words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| {
+            let spoken = word.chars().filter(|c| c.is_alphanumeric()).count().max(1);
+            let pause = match word.chars().last() {
+                _ if i + 1 == words.len() => 0.0,
+                Some('.' | '!' | '?') => 4.0,
+                Some(',' | ';' | ':') => 2.0,
+                _ => 0.0,
+            };
+            (spoken as f32, pause)
+        })
+        .collect();
+
+    let total: f32 = weights.iter().map(|(spoken, pause)| spoken + pause).sum();
+    let scale = if total > 0.0 {
+        (end - start) / total
+    } else {
+        0.0
+    };
+
+    let mut at = start;
+    words
+        .iter()
+        .zip(weights)
+        .map(|(word, (spoken, pause))| {
+            let word_start = at;
+            at += spoken * scale;
+            let timing = WordTiming {
+                word: word.to_string(),
+                start: word_start,
+                end: at,
+            };
+            at += pause * scale;
+            timing
+        })
+        .collect()
+}
+
+// Voice each text span with `synth(text, rate)` and put silence where breaks are
+fn render_spans<F>(
+    spans: &[SsmlSpan],
+    sample_rate: u32,
+    fade_len: usize,
+    mut synth: F,
+) -> Result<Vec<f32>>
+where
+    F: FnMut(&str, f32) -> Result<Vec<f32>>,
+{
+    let mut samples = Vec::new();
+    for span in spans {
+        match span {
+            SsmlSpan::Text { text, rate } => {
+                append_crossfaded(&mut samples, &synth(text, *rate)?, fade_len);
+            }
+            SsmlSpan::Break(pause) => {
+                let silence = (pause.as_secs_f64() * sample_rate as f64).round() as usize;
+                samples.resize(samples.len() + silence, 0.0);
+            }
+        }
+    }
+    Ok(samples)
+}
+
+// Split `text` at sentence ends into chunks of at most `max_chars` chars
+// Sentences are packed together while they fit; an oversized sentence is cut at word breaks
+pub fn split_text_chunks(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for sentence in split_sentences(text) {
+        let joined_len = current.chars().count() + 1 + sentence.chars().count();
+        if !current.is_empty() && joined_len > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+
+        if sentence.chars().count() > max_chars {
+            chunks.extend(split_words(sentence, max_chars));
+        } else if current.is_empty() {
+            current.push_str(sentence);
+        } else {
+            current.push(' ');
+            current.push_str(sentence);
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+// Sentences of `text`, each ending at `.`, `!`, `?` or a newline
+fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+
+    for (i, c) in text.char_indices() {
+        if matches!(c, '.' | '!' | '?' | '\n') {
+            let end = i + c.len_utf8();
+            sentences.push(text[start..end].trim());
+            start = end;
+        }
+    }
+    sentences.push(text[start..].trim());
+
+    sentences.retain(|s| !s.is_empty());
+    sentences
+}
+
+// Break one long sentence into pieces of at most `max_chars`, at spaces where possible
+fn split_words(sentence: &str, max_chars: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+
+    for word in sentence.split_whitespace() {
+        let word_len = word.chars().count();
+
+        // A single word longer than the limit is cut hard
+        if word_len > max_chars {
+            if !current.is_empty() {
+                pieces.push(std::mem::take(&mut current));
+            }
+            let chars: Vec<char> = word.chars().collect();
+            pieces.extend(
+                chars
+                    .chunks(max_chars)
+                    .map(|c| c.iter().collect::<String>()),
+            );
+            continue;
+        }
+
+        if !current.is_empty() && current.chars().count() + 1 + word_len > max_chars {
+            pieces.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+// The "language": {"code": ...} of a Piper voice config; piper_rs doesn't expose it
+fn voice_language(config_path: &str) -> Option<String> {
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(config_path).ok()?).ok()?;
+    config["language"]["code"].as_str().map(String::from)
+}
+
+fn crossfade_len(ms: u32, sample_rate: u32) -> usize {
+    sample_rate as usize * ms as usize / 1000
+}
+
+// Append `next` to `out`, overlapping up to `fade_len` samples with an equal-power crossfade
+// The first chunk (empty `out`) is appended unchanged
+fn append_crossfaded(out: &mut Vec<f32>, next: &[f32], fade_len: usize) {
+    let overlap = fade_len.min(out.len()).min(next.len());
+    let start = out.len() - overlap;
+
+    for (i, (old, new)) in out[start..].iter_mut().zip(&next[..overlap]).enumerate() {
+        let t = (i + 1) as f32 / (overlap + 1) as f32 * std::f32::consts::FRAC_PI_2;
+        *old = *old * t.cos() + *new * t.sin();
+    }
+
+    out.extend_from_slice(&next[overlap..]);
+}
+
+// Glyphs that are fine on screen but should never be read aloud
+const UNSPOKEN_SYMBOLS: &[char] = &[
+    '•', '◦', '▪', '▫', '‣', '●', '○', '■', '□', '*', '#', '`', '~', '|', '>', '<', '^', '_',
+];
+
+// Abbreviations expanded before synthesis (matched case-insensitively)
+const ABBREVIATIONS: &[(&str, &str)] = &[
+    ("e.g.", "for example"),
+    ("i.e.", "that is"),
+    ("etc.", "et cetera"),
+    ("vs.", "versus"),
+    ("approx.", "approximately"),
+    ("dr.", "Doctor"),
+    ("mr.", "Mister"),
+    ("mrs.", "Missus"),
+    ("ms.", "Miss"),
+];
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+// Spell out integers below 100; larger numbers are left for the voice to read
+fn number_to_words(n: u32) -> Option<String> {
+    match n {
+        0..=19 => Some(ONES[n as usize].to_string()),
+        20..=99 if n.is_multiple_of(10) => Some(TENS[(n / 10) as usize].to_string()),
+        20..=99 => Some(format!(
+            "{}-{}",
+            TENS[(n / 10) as usize],
+            ONES[(n % 10) as usize]
+        )),
+        _ => None,
+    }
+}
+
+// Rewrite one whitespace-separated word for speech
+fn normalize_word(word: &str) -> Option<String> {
+    let word: String = word
+        .chars()
+        .filter(|c| !UNSPOKEN_SYMBOLS.contains(c))
+        .collect();
+    if word.is_empty() {
+        return None;
+    }
+    if word == "&" {
+        return Some("and".to_string());
+    }
+
+    // Keep trailing clause punctuation so the voice still pauses
+    let core = word.trim_end_matches([',', ';', ':', '!', '?']);
+    let suffix = &word[core.len()..];
+
+    if let Some((_, expansion)) = ABBREVIATIONS
+        .iter()
+        .find(|(abbr, _)| abbr.eq_ignore_ascii_case(core))
+    {
+        return Some(format!("{}{}", expansion, suffix));
+    }
+
+    // Small integers, optionally with a percent sign or trailing punctuation ("3.", "50%")
+    let digits_len = word.bytes().take_while(u8::is_ascii_digit).count();
+    let (digits, rest) = word.split_at(digits_len);
+    let (percent, rest) = match rest.strip_prefix('%') {
+        Some(rest) => (" percent", rest),
+        None => ("", rest),
+    };
+    if digits_len > 0
+        && rest.chars().all(|c| ".,;:!?".contains(c))
+        && let Some(words) = digits.parse().ok().and_then(number_to_words)
+    {
+        return Some(format!("{}{}{}", words, percent, rest));
+    }
+
+    Some(word)
+}
+
+// Prepare display text for TTS: drop bullets and symbols, spell out small
+// numbers and expand common abbreviations
+// Line breaks are kept, as `TtsEngine::synthesize` pauses between lines
+pub fn normalize_for_speech(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            line.split_whitespace()
+                .filter_map(normalize_word)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_chunk_is_unchanged() {
+        let chunk = vec![0.1, -0.2, 0.3];
+        let mut out = Vec::new();
+        append_crossfaded(&mut out, &chunk, 64);
+        assert_eq!(out, chunk);
+    }
+
+    #[test]
+    fn test_captions_cover_the_audio_word_for_word() {
+        let text = "Hello there, friend. How are you today?";
+        let rate = 16_000;
+        // 50 ms of audio per character, like a steady voice
+        let (samples, words) = synthesize_captioned_chunks(text, 500, 160, rate, |chunk| {
+            Ok(vec![0.1; chunk.chars().count() * 800])
+        })
+        .unwrap();
+
+        let duration = samples.len() as f32 / rate as f32;
+        assert_eq!(words.len(), text.split_whitespace().count());
+        assert_eq!(words[0].word, "Hello");
+        assert_eq!(words[0].start, 0.0);
+        assert!((words.last().unwrap().end - duration).abs() < 1e-3);
+        // In order and never overlapping
+        for pair in words.windows(2) {
+            assert!(pair[0].start < pair[0].end);
+            assert!(pair[0].end <= pair[1].start, "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn test_crossfade_overlaps_chunks_without_a_jump() {
+        let first = vec![0.5; 1000];
+        let second = vec![-0.5; 1000];
+
+        let mut out = Vec::new();
+        append_crossfaded(&mut out, &first, 200);
+        append_crossfaded(&mut out, &second, 200);
+
+        // The overlapping samples are shared, not duplicated
+        assert_eq!(out.len(), 2000 - 200);
+
+        // Plain concatenation would step by 1.0 at the seam
+        let max_step = out
+            .windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0, f32::max);
+        assert!(max_step < 0.05, "step of {} at the seam", max_step);
+    }
+
+    #[test]
+    fn test_speed_scales_length_scale_only() {
+        let base = PiperSynthesisConfig {
+            speaker: Some(0),
+            noise_scale: 0.667,
+            length_scale: 1.0,
+            noise_w: 0.8,
+        };
+
+        let fast = synthesis_config_for_speed(&base, 2.0);
+        assert_eq!(fast.length_scale, 0.5);
+        assert_eq!(fast.noise_scale, base.noise_scale);
+        assert_eq!(fast.speaker, base.speaker);
+
+        let slow = synthesis_config_for_speed(&base, MIN_SPEED);
+        assert_eq!(slow.length_scale, 2.0);
+    }
+
+    // A voice that notes each length_scale it is given
+    fn recording_voice(applied: &Arc<std::sync::Mutex<Vec<f32>>>) -> VoiceSettings {
+        let applied = Arc::clone(applied);
+        VoiceSettings::new(
+            Some(PiperSynthesisConfig {
+                speaker: None,
+                noise_scale: 0.667,
+                length_scale: 1.0,
+                noise_w: 0.8,
+            }),
+            Arc::new(move |config: &PiperSynthesisConfig| {
+                applied.lock().unwrap().push(config.length_scale);
+                Ok(())
+            }),
+        )
+    }
+
+    #[test]
+    fn test_speed_change_reaches_the_voice() {
+        let applied = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let voice = recording_voice(&applied);
+
+        assert_eq!(voice.apply_speed(2.0).unwrap(), 2.0);
+        // Out of range speeds are clamped before they reach the voice
+        assert_eq!(voice.apply_speed(10.0).unwrap(), MAX_SPEED);
+        assert_eq!(*applied.lock().unwrap(), vec![0.5, 1.0 / MAX_SPEED]);
+
+        let fixed = VoiceSettings {
+            base: None,
+            ..voice
+        };
+        assert!(fixed.apply_speed(1.5).is_err());
+    }
+
+    #[test]
+    fn test_temporary_speed_holds_the_voice_until_restored() {
+        let applied = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let voice = recording_voice(&applied);
+
+        let samples = voice
+            .with_rate(2.0, || {
+                // Nobody else can synthesize while the voice is sped up
+                assert!(voice.speed.try_read().is_err());
+                Ok(vec![0.0; 4])
+            })
+            .unwrap();
+
+        assert_eq!(samples.len(), 4);
+        assert_eq!(*applied.lock().unwrap(), vec![0.5, 1.0]);
+        assert!(voice.speed.try_read().is_ok());
+    }
+
+    #[test]
+    fn test_temporary_speed_restores_a_speed_set_through_another_clone() {
+        let applied = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let stale = recording_voice(&applied);
+        let current = stale.clone();
+
+        current.set_speed(1.25).unwrap();
+        stale.with_rate(2.0, || Ok(())).unwrap();
+
+        // The clone made before the change puts back 1.25, not the 1.0 it started with
+        assert_eq!(*applied.lock().unwrap(), vec![0.8, 0.4, 0.8]);
+        assert_eq!(*stale.shared(), 1.25);
+    }
+
+    #[test]
+    fn test_bullets_and_small_numbers_are_spoken() {
+        assert_eq!(normalize_for_speech("• 3 items"), "three items");
+        assert_eq!(
+            normalize_for_speech("• 42 apples, 7 pears and 250 grapes."),
+            "forty-two apples, seven pears and 250 grapes."
+        );
+    }
+
+    #[test]
+    fn test_abbreviations_and_symbols_are_expanded() {
+        assert_eq!(
+            normalize_for_speech("Fruit, e.g. apples & pears, is **great**"),
+            "Fruit, for example apples and pears, is great"
+        );
+        assert_eq!(
+            normalize_for_speech("About 50% done."),
+            "About fifty percent done."
+        );
+    }
+
+    #[test]
+    fn test_zero_crossfade_is_plain_concatenation() {
+        let mut out = vec![1.0, 2.0];
+        append_crossfaded(&mut out, &[3.0, 4.0], 0);
+        assert_eq!(out, vec![1.0, 2.0, 3.0, 4.0]);
+    }
+
+    // Stand-in for Piper: one sample per char, valued by the char
+    fn fake_synth(text: &str) -> Result<Vec<f32>> {
+        Ok(text.chars().map(|c| c as u32 as f32).collect())
+    }
+
+    #[test]
+    fn test_blank_text_is_not_synthesized() {
+        let samples = synthesize_chunked("  \n ", 500, 0, |_| panic!("synthesized blank text"));
+        assert!(samples.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_long_paragraph_is_chunked_by_sentence() {
+        let paragraph = "The sun rose over the hills. Birds began to sing! \
+                         Was it already morning? The village slowly woke up.";
+        let max_chars = 40;
+
+        let mut calls = Vec::new();
+        let samples = synthesize_chunked(paragraph, max_chars, 0, |chunk| {
+            calls.push(chunk.to_string());
+            fake_synth(chunk)
+        })
+        .unwrap();
+
+        assert_eq!(
+            calls,
+            vec![
+                "The sun rose over the hills.",
+                "Birds began to sing!",
+                "Was it already morning?",
+                "The village slowly woke up.",
+            ]
+        );
+        assert!(calls.iter().all(|c| c.chars().count() <= max_chars));
+
+        // Same audio as synthesizing each sentence on its own
+        let sentence_wise: Vec<f32> = calls.iter().flat_map(|c| fake_synth(c).unwrap()).collect();
+        assert_eq!(samples, sentence_wise);
+    }
+
+    #[test]
+    fn test_each_line_is_its_own_chunk_with_pauses_between() {
+        let mut calls = Vec::new();
+        let samples = synthesize_lines("1. Eggs\n- Flour\n\nMilk\n", Some(100), |line| {
+            calls.push(line.to_string());
+            fake_synth(line)
+        })
+        .unwrap();
+
+        assert_eq!(calls, vec!["1, Eggs", "Flour", "Milk"]);
+        let lens: Vec<usize> = calls.iter().map(|c| c.chars().count()).collect();
+        assert_eq!(samples.len(), lens.iter().sum::<usize>() + 2 * 100);
+        // Silence right after the first and second lines, and nowhere else
+        let first_pause = lens[0]..lens[0] + 100;
+        let second_pause = lens[0] + 100 + lens[1]..lens[0] + 200 + lens[1];
+        assert!(samples[first_pause].iter().all(|s| *s == 0.0));
+        assert!(samples[second_pause].iter().all(|s| *s == 0.0));
+        assert_eq!(samples.iter().filter(|s| **s == 0.0).count(), 200);
+
+        // Without a pause the lines go to the voice as one text
+        let mut calls = 0;
+        synthesize_lines("Eggs\nFlour\nMilk", None, |line| {
+            calls += 1;
+            fake_synth(line)
+        })
+        .unwrap();
+        assert_eq!(calls, 1);
+        assert_eq!(
+            normalize_for_speech("• 2 eggs\n\n• flour"),
+            "two eggs\nflour"
+        );
+    }
+
+    #[test]
+    fn test_short_text_is_synthesized_in_one_call() {
+        let mut calls = 0;
+        synthesize_chunked("Hello there. How are you?", 500, 0, |chunk| {
+            calls += 1;
+            fake_synth(chunk)
+        })
+        .unwrap();
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_oversized_sentence_is_split_at_words() {
+        let chunks = split_text_chunks("one two three four five six", 10);
+        assert_eq!(chunks, vec!["one two", "three four", "five six"]);
+
+        let chunks = split_text_chunks("abcdefghijkl", 5);
+        assert_eq!(chunks, vec!["abcde", "fghij", "kl"]);
+    }
+
+    // Stand-in for Piper speaking at `rate`: 100 samples per char at normal speed
+    fn fake_rated_synth(text: &str, rate: f32) -> Result<Vec<f32>> {
+        let len = (text.chars().count() as f32 * 100.0 / rate) as usize;
+        Ok(vec![0.5; len])
+    }
+
+    #[test]
+    fn test_break_inserts_silence_of_its_duration() {
+        let spans = crate::ssml::parse("Hi<break time=\"500ms\"/>yo").unwrap();
+        let samples = render_spans(&spans, 16000, 0, fake_rated_synth).unwrap();
+
+        // 200 samples of speech either side of 8000 silent ones
+        assert_eq!(samples.len(), 200 + 8000 + 200);
+        assert!(samples[200..8200].iter().all(|s| *s == 0.0));
+        assert!(samples[..200].iter().all(|s| *s != 0.0));
+    }
+
+    #[test]
+    fn test_prosody_rate_changes_chunk_length() {
+        let normal = crate::ssml::parse("Hello").unwrap();
+        let slow = crate::ssml::parse("<prosody rate=\"x-slow\">Hello</prosody>").unwrap();
+        let fast = crate::ssml::parse("<prosody rate=\"200%\">Hello</prosody>").unwrap();
+
+        let len = |spans| {
+            render_spans(spans, 16000, 0, fake_rated_synth)
+                .unwrap()
+                .len()
+        };
+        assert_eq!(len(&normal), 500);
+        assert_eq!(len(&slow), 1000);
+        assert_eq!(len(&fast), 250);
+    }
+}
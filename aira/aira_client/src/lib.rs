@@ -0,0 +1,134 @@
+// Typed client for the Aira server's HTTP API
+pub mod models;
+mod sse;
+
+pub use models::*;
+
+use anyhow::{Context, Result};
+use reqwest::{RequestBuilder, Response, StatusCode, header};
+use sse::SseParser;
+
+pub struct AiraClient {
+    http: reqwest::Client,
+    base_url: String,
+    // Sent as a bearer token, for servers started with AIRA_API_TOKEN
+    token: Option<String>,
+}
+
+impl AiraClient {
+    // `base_url` is the server root, e.g. "http://127.0.0.1:3000"
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+        }
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        let request = self.http.post(format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    // Send a message and hand each streamed event to `on_event` as it arrives
+    // Returns once the server closes the stream
+    pub async fn chat_stream(
+        &self,
+        request: &ChatRequest,
+        mut on_event: impl FnMut(ChatEvent),
+    ) -> Result<()> {
+        let mut response = send(self.post("/chat").json(request), "/chat").await?;
+
+        let mut parser = SseParser::default();
+        while let Some(bytes) = response.chunk().await.context("chat stream interrupted")? {
+            for event in parser.push(&bytes) {
+                on_event(event.into_chat_event());
+            }
+        }
+        Ok(())
+    }
+
+    // The server's opening line for a new conversation, if it has one and nothing was said yet
+    pub async fn greet(&self) -> Result<Option<String>> {
+        let path = "/api/session/greet";
+        let response = send(self.post(path), path).await?;
+        Ok(response.json::<Greeting>().await?.greeting)
+    }
+
+    // Transcribe a WAV (or any format the server decodes) to text
+    pub async fn transcribe(&self, audio: Vec<u8>, file_name: &str) -> Result<TranscribeResponse> {
+        // A single-file form, built by hand to keep reqwest's multipart feature out
+        let boundary = "aira-client-boundary-7f3c9d";
+        let mut body = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"audio\"; filename=\"{}\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n",
+            boundary,
+            file_name.replace('"', "")
+        )
+        .into_bytes();
+        body.extend_from_slice(&audio);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+        let request = self
+            .post("/api/stt/transcribe")
+            .header(
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(body);
+        let response = send(request, "/api/stt/transcribe").await?;
+        Ok(response.json().await?)
+    }
+
+    // Synthesize speech, returning WAV bytes; blank text yields no audio
+    pub async fn tts(&self, request: &TtsRequest) -> Result<Option<Vec<u8>>> {
+        let response = send(self.post("/api/tts").json(request), "/api/tts").await?;
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+
+    // Synthesize plain text with word timings for captions; blank text yields none
+    pub async fn tts_captioned(&self, request: &TtsRequest) -> Result<Option<CaptionedSpeech>> {
+        let path = "/api/tts/captioned";
+        let response = send(self.post(path).json(request), path).await?;
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        Ok(Some(response.json().await?))
+    }
+
+    // Send one camera frame and get the smoothed emotional state back
+    pub async fn post_camera_features(
+        &self,
+        features: &CameraFeatures,
+    ) -> Result<CameraFeaturesResponse> {
+        let request = self.post("/api/camera/features").json(features);
+        let response = send(request, "/api/camera/features").await?;
+        Ok(response.json().await?)
+    }
+}
+
+// Send a request, turning error statuses into errors carrying the server's message
+async fn send(request: RequestBuilder, path: &str) -> Result<Response> {
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("request to {} failed", path))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let message = response.text().await.unwrap_or_default();
+        anyhow::bail!("{} returned {}: {}", path, status, message);
+    }
+    Ok(response)
+}
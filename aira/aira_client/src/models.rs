@@ -0,0 +1,215 @@
+// Request and response bodies of the Aira server, mirroring aira_server's models.rs
+// Kept free of aira_brain so clients don't pull in the model runtimes
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseLength {
+    Short,
+    Normal,
+    Long,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+// A prior message, for stateless chats that send the whole conversation
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+// Unit of text per streamed event
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamGranularity {
+    Token,
+    Word,
+    Sentence,
+}
+
+// Body for POST /chat; unset options take the server's defaults
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ChatRequest {
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length: Option<ResponseLength>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub messages: Option<Vec<ChatMessage>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_granularity: Option<StreamGranularity>,
+    pub audio_streaming: bool,
+    // 1 (mono) or 2 (stereo)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u16>,
+    // Quick greedy first sentence, so speech starts sooner
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fast_start: Option<bool>,
+    // Cut the reply off after this many words
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_words: Option<usize>,
+}
+
+impl ChatRequest {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            ..Default::default()
+        }
+    }
+}
+
+// Why a generation ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FinishReason {
+    Stop,
+    Length,
+    Timeout,
+    Cancelled,
+}
+
+// Body of the `done` event
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ChatDone {
+    pub finish_reason: FinishReason,
+    pub tokens_per_second: f64,
+    pub input_truncated: bool,
+    // Replayed from the server's response cache; older servers don't send it
+    #[serde(default)]
+    pub cached: bool,
+}
+
+// One event of a chat stream
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChatEvent {
+    // Reply text; `id` is the sentence it belongs to
+    Text {
+        id: Option<u64>,
+        text: String,
+    },
+    // Base64 WAV of a whole sentence
+    AudioComplete {
+        id: Option<u64>,
+        wav_base64: String,
+    },
+    // Part of a sentence's audio with audio_streaming; the last part has no audio
+    AudioChunk {
+        id: Option<u64>,
+        wav_base64: Option<String>,
+        end: bool,
+    },
+    // Base64 WAV spoken while the first sentence is generated
+    Filler(String),
+    Tps(f64),
+    Warning(String),
+    // `stage` is "llm" or "tts" when the server knows which part failed
+    Error {
+        stage: Option<String>,
+        message: String,
+    },
+    Done(ChatDone),
+    AudioStopped,
+    NoAudio,
+    // An event this client doesn't know yet
+    Other {
+        event: String,
+        data: String,
+    },
+}
+
+// Response of POST /api/session/greet
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Greeting {
+    pub greeting: Option<String>,
+}
+
+// Where speech was heard, in seconds
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct SpeechSegment {
+    pub start: f32,
+    pub end: f32,
+}
+
+// Response of POST /api/stt/transcribe
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct TranscribeResponse {
+    pub text: String,
+    pub confidence: f32,
+    pub segments: Vec<SpeechSegment>,
+}
+
+// Body for POST /api/tts; unset options take the server's defaults
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TtsRequest {
+    // Plain text, or SSML-lite with <break> and <prosody rate> tags
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u16>,
+}
+
+// When a word of captioned speech is spoken, in seconds from the start
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct WordTiming {
+    pub word: String,
+    pub start: f32,
+    pub end: f32,
+}
+
+// Response of POST /api/tts/captioned
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct CaptionedSpeech {
+    // Base64 WAV
+    pub audio: String,
+    pub sample_rate: u32,
+    pub duration_secs: f32,
+    pub words: Vec<WordTiming>,
+}
+
+// One camera frame's face features
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraFeatures {
+    pub face_present: bool,
+    pub face_confidence: f32,
+    pub avg_eye_openness: f32,
+    pub blink_rate: f32,
+    pub smile_score: f32,
+    pub head_pitch: f32,
+    pub head_yaw: f32,
+}
+
+// Smoothed emotional state returned for camera frames
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct EmotionalState {
+    pub fatigue: f32,
+    pub engagement: f32,
+    pub stress: f32,
+    pub positive_affect: f32,
+    // Unix seconds or an ISO 8601 string, depending on the requested format
+    #[serde(default)]
+    pub timestamp: Option<serde_json::Value>,
+    pub face_present: bool,
+}
+
+// Response of POST /api/camera/features
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct CameraFeaturesResponse {
+    #[serde(flatten)]
+    pub state: EmotionalState,
+    #[serde(default)]
+    pub frames: Option<Vec<EmotionalState>>,
+}
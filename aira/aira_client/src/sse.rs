@@ -0,0 +1,181 @@
+use crate::models::{ChatDone, ChatEvent};
+use serde::Deserialize;
+
+// A raw server-sent event as framed on the wire
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct RawEvent {
+    pub event: Option<String>,
+    pub id: Option<String>,
+    pub data: String,
+}
+
+// Splits a byte stream into SSE events; bytes may arrive cut anywhere, even inside a character
+#[derive(Default)]
+pub(crate) struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    // Add received bytes and return the events they complete
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<RawEvent> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut events = Vec::new();
+        while let Some((end, separator)) = find_blank_line(&self.buffer) {
+            let block: Vec<u8> = self.buffer.drain(..end + separator).collect();
+            if let Some(event) = parse_block(&String::from_utf8_lossy(&block[..end])) {
+                events.push(event);
+            }
+        }
+        events
+    }
+}
+
+// Position and length of the first blank line, which ends an event
+fn find_blank_line(buffer: &[u8]) -> Option<(usize, usize)> {
+    (0..buffer.len()).find_map(|i| {
+        if buffer[i..].starts_with(b"\n\n") {
+            Some((i, 2))
+        } else if buffer[i..].starts_with(b"\r\n\r\n") {
+            Some((i, 4))
+        } else {
+            None
+        }
+    })
+}
+
+// Fields of one event block; comment-only blocks (keep-alives) yield nothing
+fn parse_block(block: &str) -> Option<RawEvent> {
+    let mut event = RawEvent::default();
+    let mut has_data = false;
+
+    for line in block.lines() {
+        if line.starts_with(':') {
+            continue;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => event.event = Some(value.to_string()),
+            "id" => event.id = Some(value.to_string()),
+            "data" => {
+                if has_data {
+                    event.data.push('\n');
+                }
+                event.data.push_str(value);
+                has_data = true;
+            }
+            _ => {}
+        }
+    }
+
+    (has_data || event.event.is_some()).then_some(event)
+}
+
+#[derive(Deserialize)]
+struct AudioChunkBody {
+    audio: Option<String>,
+    end: bool,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    stage: Option<String>,
+    message: String,
+}
+
+impl RawEvent {
+    pub fn into_chat_event(self) -> ChatEvent {
+        let id = self.id.as_deref().and_then(|id| id.parse().ok());
+        let Some(event) = self.event else {
+            return ChatEvent::Text {
+                id,
+                text: self.data,
+            };
+        };
+
+        match event.as_str() {
+            "audio_complete" => ChatEvent::AudioComplete {
+                id,
+                wav_base64: self.data,
+            },
+            "audio_chunk" => match serde_json::from_str::<AudioChunkBody>(&self.data) {
+                Ok(chunk) => ChatEvent::AudioChunk {
+                    id,
+                    wav_base64: chunk.audio,
+                    end: chunk.end,
+                },
+                Err(_) => ChatEvent::Other {
+                    event,
+                    data: self.data,
+                },
+            },
+            "filler" => ChatEvent::Filler(self.data),
+            "tps" => match self.data.parse() {
+                Ok(tps) => ChatEvent::Tps(tps),
+                Err(_) => ChatEvent::Other {
+                    event,
+                    data: self.data,
+                },
+            },
+            "warning" => ChatEvent::Warning(self.data),
+            // Stage errors are JSON; errors raised before streaming starts are plain text
+            "error" => match serde_json::from_str::<ErrorBody>(&self.data) {
+                Ok(error) => ChatEvent::Error {
+                    stage: error.stage,
+                    message: error.message,
+                },
+                Err(_) => ChatEvent::Error {
+                    stage: None,
+                    message: self.data,
+                },
+            },
+            "done" => match serde_json::from_str::<ChatDone>(&self.data) {
+                Ok(done) => ChatEvent::Done(done),
+                Err(_) => ChatEvent::Other {
+                    event,
+                    data: self.data,
+                },
+            },
+            "audio_stopped" => ChatEvent::AudioStopped,
+            "no_audio" => ChatEvent::NoAudio,
+            _ => ChatEvent::Other {
+                event,
+                data: self.data,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_split_across_reads_are_reassembled() {
+        let wire = "id: 0\ndata: Hel\n\n: keep-alive\n\nevent: error\ndata: {\"stage\":\"tts\",\"message\":\"No audio\"}\n\n";
+        let mut parser = SseParser::default();
+
+        // Feed a byte at a time, as a slow connection might
+        let events: Vec<ChatEvent> = wire
+            .as_bytes()
+            .chunks(1)
+            .flat_map(|byte| parser.push(byte))
+            .map(RawEvent::into_chat_event)
+            .collect();
+
+        assert_eq!(
+            events,
+            vec![
+                ChatEvent::Text {
+                    id: Some(0),
+                    text: "Hel".to_string()
+                },
+                ChatEvent::Error {
+                    stage: Some("tts".to_string()),
+                    message: "No audio".to_string()
+                },
+            ]
+        );
+    }
+}
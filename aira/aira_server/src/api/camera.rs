@@ -1,8 +1,16 @@
-use crate::models::CameraFeatures;
-use crate::states::SharedAira;
-use aira_brain::aira::EmotionalContext;
-use axum::{Json, extract::State};
-use serde::Serialize;
+use crate::models::{
+    CameraFeatures, CameraFeaturesInput, CameraFeaturesQuery, EmotionAlphaRequest,
+    EmotionDetailsQuery, EmotionToggleRequest, TimestampFormat,
+};
+use crate::states::{SharedAira, lock_or_recover};
+use aira_brain::aira::{DominantEmotion, EmotionPriority, EmotionalContext};
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use tokio::sync::Semaphore;
 
@@ -10,7 +18,7 @@ use tokio::sync::Semaphore;
 struct EmotionalStateTracker {
     // Current smoothed state
     current: EmotionalContext,
-    // Previous raw state for change detection
+    // Latest raw reading, before smoothing
     previous_raw: Option<EmotionalContext>,
     // EMA alpha parameter (0.0-1.0, higher = more responsive)
     alpha: f32,
@@ -18,11 +26,198 @@ struct EmotionalStateTracker {
     change_threshold: f32,
     // State machine for emotion transitions
     state_machine: EmotionStateMachine,
+    // Throttle for the console emotion log
+    log_throttle: EmotionLogThrottle,
+    // Optional on-disk snapshot of the smoothed state
+    persistence: Option<TrackerPersistence>,
+    // Starting point, and the reading used when no face is seen
+    neutral: NeutralLevels,
 }
 
-// Emotion state machine for smooth transitions
+// The deployment's resting emotional state, e.g. a calmer baseline for a kiosk
 #[derive(Debug, Clone, Copy, PartialEq)]
-enum EmotionState {
+struct NeutralLevels {
+    fatigue: f32,
+    engagement: f32,
+    stress: f32,
+    positive_affect: f32,
+}
+
+impl Default for NeutralLevels {
+    fn default() -> Self {
+        Self {
+            fatigue: 0.5,
+            engagement: 0.5,
+            stress: 0.5,
+            positive_affect: 0.5,
+        }
+    }
+}
+
+impl NeutralLevels {
+    // "fatigue,engagement,stress,positive_affect", each 0.0 - 1.0
+    fn parse(value: &str) -> Result<Self, String> {
+        let levels = value
+            .split(',')
+            .map(|level| match level.trim().parse::<f32>() {
+                Ok(level) if (0.0..=1.0).contains(&level) => Ok(level),
+                _ => Err(format!("{:?} is not a level between 0 and 1", level.trim())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let [fatigue, engagement, stress, positive_affect] = levels[..] else {
+            return Err(format!(
+                "expected 4 levels (fatigue,engagement,stress,positive_affect), got {}",
+                levels.len()
+            ));
+        };
+        Ok(Self {
+            fatigue,
+            engagement,
+            stress,
+            positive_affect,
+        })
+    }
+
+    // Read AIRA_EMOTION_NEUTRAL, keeping 0.5 everywhere when unset or invalid
+    fn from_env() -> Self {
+        let Ok(value) = std::env::var("AIRA_EMOTION_NEUTRAL") else {
+            return Self::default();
+        };
+        Self::parse(&value).unwrap_or_else(|e| {
+            eprintln!("Ignoring AIRA_EMOTION_NEUTRAL: {}", e);
+            Self::default()
+        })
+    }
+
+    fn context(&self, timestamp: u64, face_present: bool) -> EmotionalContext {
+        EmotionalContext {
+            fatigue: self.fatigue,
+            engagement: self.engagement,
+            stress: self.stress,
+            positive_affect: self.positive_affect,
+            timestamp,
+            face_present,
+        }
+    }
+}
+
+// Seconds between snapshot writes while frames keep arriving
+const SNAPSHOT_SAVE_INTERVAL: u64 = 30;
+
+// Tracker state saved to disk so a restart keeps the user's baseline
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+struct TrackerSnapshot {
+    current: EmotionalContext,
+    alpha: f32,
+    change_threshold: f32,
+    saved_at: u64,
+}
+
+// Where the snapshot lives and how long it stays valid
+struct TrackerPersistence {
+    path: PathBuf,
+    // Snapshots older than this (seconds) are ignored on load
+    ttl: u64,
+    last_saved: u64,
+}
+
+impl TrackerPersistence {
+    // Enabled by AIRA_EMOTION_STATE_PATH, TTL from AIRA_EMOTION_STATE_TTL (default: 1 hour)
+    fn from_env() -> Option<Self> {
+        let path = std::env::var("AIRA_EMOTION_STATE_PATH").ok()?;
+        let ttl = std::env::var("AIRA_EMOTION_STATE_TTL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3600);
+
+        Some(Self {
+            path: PathBuf::from(path),
+            ttl,
+            last_saved: 0,
+        })
+    }
+}
+
+// Write atomically so a crash mid-write never leaves a corrupt snapshot
+fn save_snapshot(path: &Path, snapshot: &TrackerSnapshot) -> anyhow::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, serde_json::to_vec(snapshot)?)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+// Load a snapshot unless it is missing, unreadable or older than `ttl` seconds
+fn load_snapshot(path: &Path, now: u64, ttl: u64) -> Option<TrackerSnapshot> {
+    let data = std::fs::read(path).ok()?;
+    let snapshot: TrackerSnapshot = serde_json::from_slice(&data).ok()?;
+
+    if now.saturating_sub(snapshot.saved_at) > ttl {
+        println!(
+            "⏰ Ignoring stale emotion snapshot from {}",
+            snapshot.saved_at
+        );
+        return None;
+    }
+    Some(snapshot)
+}
+
+// Rate limits emotion logging to discrete state changes
+struct EmotionLogThrottle {
+    // Minimum seconds between two logs
+    min_interval: u64,
+    // Print a single line instead of the full banner
+    compact: bool,
+    // Last state that was actually logged
+    last_logged_state: Option<EmotionState>,
+    // Timestamp of the last log
+    last_log_time: Option<u64>,
+}
+
+impl EmotionLogThrottle {
+    fn new(min_interval: u64, compact: bool) -> Self {
+        Self {
+            min_interval,
+            compact,
+            last_logged_state: None,
+            last_log_time: None,
+        }
+    }
+
+    // Read AIRA_EMOTION_LOG_INTERVAL (seconds) and AIRA_EMOTION_LOG_COMPACT
+    fn from_env() -> Self {
+        let min_interval = std::env::var("AIRA_EMOTION_LOG_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5);
+        let compact = std::env::var("AIRA_EMOTION_LOG_COMPACT")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        Self::new(min_interval, compact)
+    }
+
+    // Returns true if `state` should be logged at `now`.
+    // Only a change of the discrete state counts, and at most once per interval.
+    fn should_log(&mut self, state: EmotionState, now: u64) -> bool {
+        if self.last_logged_state == Some(state) {
+            return false;
+        }
+
+        if let Some(last) = self.last_log_time
+            && now.saturating_sub(last) < self.min_interval
+        {
+            return false;
+        }
+
+        self.last_logged_state = Some(state);
+        self.last_log_time = Some(now);
+        true
+    }
+}
+
+// Emotion state machine for smooth transitions
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmotionState {
     Neutral,
     Engaged,
     Fatigued,
@@ -31,12 +226,34 @@ enum EmotionState {
     Disengaged,
 }
 
+impl From<DominantEmotion> for EmotionState {
+    fn from(dominant: DominantEmotion) -> Self {
+        match dominant {
+            DominantEmotion::Fatigued => EmotionState::Fatigued,
+            DominantEmotion::Stressed => EmotionState::Stressed,
+            DominantEmotion::Happy => EmotionState::Happy,
+            DominantEmotion::Engaged => EmotionState::Engaged,
+            DominantEmotion::Disengaged => EmotionState::Disengaged,
+            DominantEmotion::Neutral => EmotionState::Neutral,
+        }
+    }
+}
+
+// Debounced state and how long it has been held, as of the latest frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct HeldState {
+    state: EmotionState,
+    state_duration: u64,
+}
+
 struct EmotionStateMachine {
     current_state: EmotionState,
     state_duration: u64,  // How long in current state (seconds)
     last_transition: u64, // Timestamp of last state change
     // Minimum duration before allowing state change (prevents rapid flickering)
     min_state_duration: u64,
+    // Which emotion wins when several readings are high (AIRA_EMOTION_PRIORITY)
+    priority: EmotionPriority,
 }
 
 impl EmotionStateMachine {
@@ -46,12 +263,17 @@ impl EmotionStateMachine {
             state_duration: 0,
             last_transition: 0,
             min_state_duration: 3, // Require 3 seconds before state change
+            priority: EmotionPriority::default(),
         }
     }
 
     // Update state based on emotional metrics with hysteresis
     fn update(&mut self, context: &EmotionalContext) -> EmotionState {
         let now = context.timestamp;
+        // The first reading starts the clock, so durations never count from the epoch
+        if self.last_transition == 0 {
+            self.last_transition = now;
+        }
         self.state_duration = now.saturating_sub(self.last_transition);
 
         let new_state = self.determine_state(context);
@@ -86,19 +308,18 @@ impl EmotionStateMachine {
 
     // Determine target state from emotional context
     fn determine_state(&self, context: &EmotionalContext) -> EmotionState {
-        // Priority order with hysteresis thresholds
-        if context.fatigue > 0.7 {
-            EmotionState::Fatigued
-        } else if context.stress > 0.6 {
-            EmotionState::Stressed
-        } else if context.positive_affect > 0.6 {
-            EmotionState::Happy
-        } else if context.engagement > 0.7 {
-            EmotionState::Engaged
-        } else if context.engagement < 0.3 {
-            EmotionState::Disengaged
-        } else {
+        // Without a face (e.g. camera off) there is nothing to read, so stay neutral
+        if !context.face_present {
             EmotionState::Neutral
+        } else {
+            self.priority.dominant(context).into()
+        }
+    }
+
+    fn held_state(&self) -> HeldState {
+        HeldState {
+            state: self.current_state,
+            state_duration: self.state_duration,
         }
     }
 
@@ -116,24 +337,77 @@ impl EmotionStateMachine {
 }
 
 impl EmotionalStateTracker {
-    fn new() -> Self {
+    fn new(neutral: NeutralLevels) -> Self {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
 
         Self {
-            current: EmotionalContext {
-                fatigue: 0.5,
-                engagement: 0.5,
-                stress: 0.5,
-                positive_affect: 0.5,
-                timestamp: now,
-            },
+            current: neutral.context(now, true),
             previous_raw: None,
             alpha: 0.3,             // 30% new data, 70% old data (smooth)
             change_threshold: 0.05, // 5% change required
             state_machine: EmotionStateMachine::new(),
+            log_throttle: EmotionLogThrottle::from_env(),
+            persistence: None,
+            neutral,
+        }
+    }
+
+    // Tracker that resumes from, and keeps saving to, AIRA_EMOTION_STATE_PATH
+    // Its neutral levels come from AIRA_EMOTION_NEUTRAL
+    fn from_env() -> Self {
+        let mut tracker = Self::new(NeutralLevels::from_env());
+
+        if let Some(persistence) = TrackerPersistence::from_env() {
+            if let Some(snapshot) = load_snapshot(
+                &persistence.path,
+                tracker.current.timestamp,
+                persistence.ttl,
+            ) {
+                println!(
+                    "💾 Restored emotion baseline from {}",
+                    persistence.path.display()
+                );
+                tracker.restore(snapshot);
+            }
+            tracker.persistence = Some(persistence);
+        }
+
+        tracker
+    }
+
+    fn snapshot(&self, now: u64) -> TrackerSnapshot {
+        TrackerSnapshot {
+            current: self.current,
+            alpha: self.alpha,
+            change_threshold: self.change_threshold,
+            saved_at: now,
+        }
+    }
+
+    // Resume smoothing from a saved snapshot
+    fn restore(&mut self, snapshot: TrackerSnapshot) {
+        self.current = snapshot.current;
+        self.alpha = snapshot.alpha;
+        self.change_threshold = snapshot.change_threshold;
+        self.state_machine.current_state = self.state_machine.determine_state(&self.current);
+    }
+
+    // Save a snapshot if persistence is on and the save interval has passed
+    fn maybe_persist(&mut self, now: u64) {
+        let snapshot = self.snapshot(now);
+        let Some(persistence) = self.persistence.as_mut() else {
+            return;
+        };
+        if now.saturating_sub(persistence.last_saved) < SNAPSHOT_SAVE_INTERVAL {
+            return;
+        }
+
+        match save_snapshot(&persistence.path, &snapshot) {
+            Ok(()) => persistence.last_saved = now,
+            Err(e) => eprintln!("Failed to save emotion snapshot: {}", e),
         }
     }
 
@@ -149,6 +423,8 @@ impl EmotionalStateTracker {
             positive_affect: alpha * new_state.positive_affect
                 + one_minus_alpha * self.current.positive_affect,
             timestamp: new_state.timestamp,
+            // Presence is a fact about this frame, not something to average
+            face_present: new_state.face_present,
         }
     }
 
@@ -156,7 +432,8 @@ impl EmotionalStateTracker {
     fn has_significant_change(&self, new_state: &EmotionalContext) -> bool {
         let diff = |a: f32, b: f32| (a - b).abs();
 
-        diff(new_state.fatigue, self.current.fatigue) > self.change_threshold
+        new_state.face_present != self.current.face_present
+            || diff(new_state.fatigue, self.current.fatigue) > self.change_threshold
             || diff(new_state.engagement, self.current.engagement) > self.change_threshold
             || diff(new_state.stress, self.current.stress) > self.change_threshold
             || diff(new_state.positive_affect, self.current.positive_affect) > self.change_threshold
@@ -168,12 +445,14 @@ impl EmotionalStateTracker {
         let smoothed = self.apply_ema(raw_state);
 
         // Update state machine
-        let _emotion_state = self.state_machine.update(&smoothed);
+        self.state_machine.update(&smoothed);
+
+        // Keep every raw reading for calibration, even when it is not applied
+        self.previous_raw = Some(raw_state);
 
         // Check if change is significant
         if self.has_significant_change(&smoothed) {
             self.current = smoothed;
-            self.previous_raw = Some(raw_state);
             Some(smoothed)
         } else {
             // No significant change, return None to skip update
@@ -184,50 +463,278 @@ impl EmotionalStateTracker {
     fn get_current(&self) -> EmotionalContext {
         self.current
     }
+
+    fn get_raw(&self) -> Option<EmotionalContext> {
+        self.previous_raw
+    }
+
+    fn held_state(&self) -> HeldState {
+        self.state_machine.held_state()
+    }
+
+    fn priority(&self) -> EmotionPriority {
+        self.state_machine.priority
+    }
+
+    // Decide whether the current discrete state should be logged at `now`
+    fn should_log(&mut self, now: u64) -> bool {
+        let state = self.state_machine.current_state;
+        self.log_throttle.should_log(state, now)
+    }
 }
 
 // Global state tracker (one per application instance)
 lazy_static::lazy_static! {
     static ref STATE_TRACKER: Arc<Mutex<EmotionalStateTracker>> =
-        Arc::new(Mutex::new(EmotionalStateTracker::new()));
+        Arc::new(Mutex::new(EmotionalStateTracker::from_env()));
 }
 
-// Process camera features and return emotional state with rate limiting
-pub async fn process_camera_features(
-    State((aira_state, _semaphore)): State<(SharedAira, &'static Semaphore)>,
-    Json(features): Json<CameraFeatures>,
-) -> Json<EmotionalContext> {
+// Order the dominant emotion is picked in for states, details and logs
+// Aira's prompt takes the same order through `Aira::set_emotion_priority`
+pub fn set_emotion_priority(priority: EmotionPriority) {
+    let mut tracker = lock_or_recover(&STATE_TRACKER);
+    tracker.state_machine.priority = priority;
+    let current = tracker.current;
+    tracker.state_machine.current_state = tracker.state_machine.determine_state(&current);
+}
+
+// Reject non-finite values and clamp the rest to their valid ranges, so one bad
+// frame can't poison the EMA or the state thresholds
+fn sanitize_features(features: &CameraFeatures) -> Result<CameraFeatures, String> {
+    let fields = [
+        ("face_confidence", features.face_confidence),
+        ("avg_eye_openness", features.avg_eye_openness),
+        ("blink_rate", features.blink_rate),
+        ("smile_score", features.smile_score),
+        ("head_pitch", features.head_pitch),
+        ("head_yaw", features.head_yaw),
+    ];
+    if let Some((name, value)) = fields.iter().find(|(_, value)| !value.is_finite()) {
+        return Err(format!("{} must be a finite number, got {}", name, value));
+    }
+
+    Ok(CameraFeatures {
+        face_present: features.face_present,
+        face_confidence: features.face_confidence.clamp(0.0, 1.0),
+        avg_eye_openness: features.avg_eye_openness.clamp(0.0, 1.0),
+        // Blinks per minute
+        blink_rate: features.blink_rate.clamp(0.0, 120.0),
+        smile_score: features.smile_score.clamp(0.0, 1.0),
+        // Degrees
+        head_pitch: features.head_pitch.clamp(-90.0, 90.0),
+        head_yaw: features.head_yaw.clamp(-90.0, 90.0),
+    })
+}
+
+// Result of pushing one camera frame through the tracker
+struct FrameOutcome {
+    // Smoothed state to hand to Aira, when the change was significant
+    update: Option<EmotionalContext>,
+    // When the frame was read
+    read_at: u64,
+    // Tracker state after this frame
+    state: EmotionalContext,
+    // Some(compact) when this frame should be logged
+    log_compact: Option<bool>,
+}
+
+fn process_frame(tracker: &mut EmotionalStateTracker, features: &CameraFeatures) -> FrameOutcome {
     // Calculate raw emotional state from camera features
-    let raw_state = calculate_emotional_state(&features);
+    let raw_state = calculate_emotional_state(features, tracker.neutral);
 
     // Apply temporal smoothing and change detection
-    let smoothed_state = {
-        let mut tracker = STATE_TRACKER.lock().unwrap();
-        tracker.update(raw_state)
-    };
+    let update = tracker.update(raw_state);
+    tracker.maybe_persist(raw_state.timestamp);
+    let log_compact = tracker
+        .should_log(raw_state.timestamp)
+        .then_some(tracker.log_throttle.compact);
 
-    // Only update Aira and log if there's a significant change
-    let final_state = if let Some(smoothed) = smoothed_state {
-        // Log real-time emotion data
-        log_emotional_state(&features, &smoothed);
+    FrameOutcome {
+        update,
+        read_at: raw_state.timestamp,
+        // No significant change, report current smoothed state
+        state: update.unwrap_or_else(|| tracker.get_current()),
+        log_compact,
+    }
+}
 
-        // Update Aira's state with the smoothed emotional context
-        {
-            let guard = aira_state.lock().unwrap();
-            guard.update_emotional_context(smoothed);
+// A reading's timestamp as sent to clients
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ApiTimestamp {
+    Unix(u64),
+    Iso(String),
+}
+
+// EmotionalContext as sent to clients; the tracker keeps the raw unix timestamp
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EmotionalContextDto {
+    pub fatigue: f32,
+    pub engagement: f32,
+    pub stress: f32,
+    pub positive_affect: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<ApiTimestamp>,
+    pub face_present: bool,
+}
+
+impl EmotionalContextDto {
+    fn new(context: &EmotionalContext, format: TimestampFormat) -> Self {
+        Self {
+            fatigue: context.fatigue,
+            engagement: context.engagement,
+            stress: context.stress,
+            positive_affect: context.positive_affect,
+            timestamp: match format {
+                TimestampFormat::Unix => Some(ApiTimestamp::Unix(context.timestamp)),
+                TimestampFormat::Iso => Some(ApiTimestamp::Iso(iso8601(context.timestamp))),
+                TimestampFormat::Omit => None,
+            },
+            face_present: context.face_present,
         }
+    }
+}
 
-        smoothed
-    } else {
-        // No significant change, return current smoothed state without logging
-        STATE_TRACKER.lock().unwrap().get_current()
+// Format unix seconds as an ISO 8601 UTC time, e.g. "2024-03-01T12:00:00Z"
+fn iso8601(unix: u64) -> String {
+    let days = (unix / 86_400) as i64;
+    let secs = unix % 86_400;
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[derive(Serialize)]
+pub struct CameraFeaturesResponse {
+    // Smoothed state after the last frame
+    #[serde(flatten)]
+    pub state: EmotionalContextDto,
+    // Dominant emotion of `state` under AIRA_EMOTION_PRIORITY, so clients needn't pick one
+    pub dominant_emotion: &'static str,
+    // Smoothed state after each frame, with ?per_frame=true
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frames: Option<Vec<EmotionalContextDto>>,
+}
+
+// Process camera features (one frame or a batch) and return emotional state with rate limiting
+pub async fn process_camera_features(
+    State((aira_state, _semaphore)): State<(SharedAira, &'static Semaphore)>,
+    Query(query): Query<CameraFeaturesQuery>,
+    Json(input): Json<CameraFeaturesInput>,
+) -> Result<Json<CameraFeaturesResponse>, (StatusCode, String)> {
+    // Validate the whole batch before any frame touches the tracker
+    let frames = input
+        .into_frames()
+        .iter()
+        .map(sanitize_features)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let (outcomes, current, priority) = {
+        let mut tracker = lock_or_recover(&STATE_TRACKER);
+        let outcomes: Vec<FrameOutcome> = frames
+            .iter()
+            .map(|features| process_frame(&mut tracker, features))
+            .collect();
+        (outcomes, tracker.get_current(), tracker.priority())
     };
 
-    Json(final_state)
+    // Only update Aira if there's a significant change, using the latest one; every
+    // frame still counts as a fresh reading, so the emotion TTL runs from the last frame
+    if let Some(last) = outcomes.last() {
+        let guard = lock_or_recover(&aira_state);
+        if let Some(smoothed) = outcomes.iter().rev().find_map(|o| o.update) {
+            guard.update_emotional_context(smoothed);
+        }
+        guard.touch_emotional_context(last.read_at);
+    }
+
+    // Log real-time emotion data when the discrete state changed
+    for (features, outcome) in frames.iter().zip(&outcomes) {
+        match outcome.log_compact {
+            Some(true) => log_emotional_state_compact(&outcome.state, &priority),
+            Some(false) => log_emotional_state(features, &outcome.state, &priority),
+            None => {}
+        }
+    }
+
+    let format = query.timestamp;
+    let latest = outcomes.last().map_or(current, |o| o.state);
+    Ok(Json(CameraFeaturesResponse {
+        state: EmotionalContextDto::new(&latest, format),
+        dominant_emotion: dominant_name(&latest, &priority),
+        frames: query.per_frame.then(|| {
+            outcomes
+                .iter()
+                .map(|o| EmotionalContextDto::new(&o.state, format))
+                .collect()
+        }),
+    }))
+}
+
+// Log emotional state as a single line
+fn log_emotional_state_compact(state: &EmotionalContext, priority: &EmotionPriority) {
+    println!(
+        "🎭 {} | fatigue {:.0}% | engagement {:.0}% | stress {:.0}% | positivity {:.0}%",
+        dominant_label(state, priority),
+        state.fatigue * 100.0,
+        state.engagement * 100.0,
+        state.stress * 100.0,
+        state.positive_affect * 100.0
+    );
+}
+
+// Dominant emotion as clients see it, in the configured priority order
+fn dominant_name(state: &EmotionalContext, priority: &EmotionPriority) -> &'static str {
+    if !state.face_present {
+        return "absent";
+    }
+    match priority.dominant(state) {
+        DominantEmotion::Fatigued => "fatigued",
+        DominantEmotion::Stressed => "stressed",
+        DominantEmotion::Happy => "happy",
+        DominantEmotion::Engaged => "focused",
+        DominantEmotion::Disengaged => "disengaged",
+        DominantEmotion::Neutral => "neutral",
+    }
+}
+
+// Dominant emotion label used by the console logs
+fn dominant_label(state: &EmotionalContext, priority: &EmotionPriority) -> &'static str {
+    match priority.dominant(state) {
+        DominantEmotion::Fatigued => "😴 FATIGUED",
+        DominantEmotion::Stressed => "😰 STRESSED",
+        DominantEmotion::Happy => "😊 HAPPY",
+        DominantEmotion::Engaged => "🎯 FOCUSED",
+        DominantEmotion::Disengaged => "😶 DISENGAGED",
+        DominantEmotion::Neutral => "😐 NEUTRAL",
+    }
 }
 
 // Log emotional state with visual indicators for real-time monitoring
-fn log_emotional_state(features: &CameraFeatures, state: &EmotionalContext) {
+fn log_emotional_state(
+    features: &CameraFeatures,
+    state: &EmotionalContext,
+    priority: &EmotionPriority,
+) {
     // Create visual bars (0-10 scale)
     let fatigue_bar = format!(
         "{}{}",
@@ -251,19 +758,7 @@ fn log_emotional_state(features: &CameraFeatures, state: &EmotionalContext) {
     );
 
     // Determine dominant emotion
-    let dominant = if state.fatigue > 0.7 {
-        "😴 FATIGUED"
-    } else if state.stress > 0.6 {
-        "😰 STRESSED"
-    } else if state.positive_affect > 0.6 {
-        "😊 HAPPY"
-    } else if state.engagement > 0.7 {
-        "🎯 FOCUSED"
-    } else if state.engagement < 0.3 {
-        "😶 DISENGAGED"
-    } else {
-        "😐 NEUTRAL"
-    };
+    let dominant = dominant_label(state, priority);
 
     println!("\n╔════════════════════════════════════════════════════════╗");
     println!("║           AIRA EMOTIONAL STATE DETECTED                ║");
@@ -307,21 +802,19 @@ fn log_emotional_state(features: &CameraFeatures, state: &EmotionalContext) {
 
 // Calculate emotional state from camera features
 // This is a privacy-preserving inference - no images, only numerical analysis
-fn calculate_emotional_state(features: &CameraFeatures) -> EmotionalContext {
+// Without a face the reading is `neutral`, so smoothing settles back to it
+fn calculate_emotional_state(
+    features: &CameraFeatures,
+    neutral: NeutralLevels,
+) -> EmotionalContext {
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
         .as_secs();
 
     if !features.face_present {
-        // No face detected - return neutral state
-        return EmotionalContext {
-            fatigue: 0.5,
-            engagement: 0.0,
-            stress: 0.5,
-            positive_affect: 0.5,
-            timestamp: now,
-        };
+        // No face detected - return neutral state, flagged so it isn't read as distraction
+        return neutral.context(now, false);
     }
 
     // Fatigue calculation with improved sensitivity
@@ -374,13 +867,14 @@ fn calculate_emotional_state(features: &CameraFeatures) -> EmotionalContext {
         stress,
         positive_affect,
         timestamp: now,
+        face_present: true,
     }
 }
 
 // Get current emotional state (for prompt injection)
 #[allow(dead_code)]
 pub fn get_current_emotional_state(aira_state: &SharedAira) -> Option<EmotionalContext> {
-    let guard = aira_state.lock().unwrap();
+    let guard = lock_or_recover(aira_state);
     guard.get_emotional_context()
 }
 
@@ -389,23 +883,53 @@ pub struct CameraStatusResponse {
     pub enabled: bool,
     pub face_detected: bool,
     pub last_update: Option<u64>,
+    // Seconds since the last reading, if there is one
+    pub age_seconds: Option<u64>,
+}
+
+// Readings older than this many seconds mean the camera has stopped sending
+const DEFAULT_CAMERA_FRESHNESS_SECS: u64 = 10;
+
+// Freshness window from AIRA_CAMERA_FRESHNESS_SECS (default: 10)
+fn camera_freshness_window() -> u64 {
+    std::env::var("AIRA_CAMERA_FRESHNESS_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_CAMERA_FRESHNESS_SECS)
+}
+
+// A reading counts only while it is at most `freshness` seconds old at `now`
+fn camera_status(
+    context: Option<&EmotionalContext>,
+    now: u64,
+    freshness: u64,
+) -> CameraStatusResponse {
+    let age_seconds = context.map(|c| now.saturating_sub(c.timestamp));
+    let fresh = age_seconds.is_some_and(|age| age <= freshness);
+
+    CameraStatusResponse {
+        enabled: fresh,
+        face_detected: fresh && context.is_some_and(|c| c.face_present),
+        last_update: context.map(|c| c.timestamp),
+        age_seconds,
+    }
 }
 
 // Get camera sensor status
 pub async fn get_camera_status(
     State((aira_state, _semaphore)): State<(SharedAira, &'static Semaphore)>,
 ) -> Json<CameraStatusResponse> {
-    let guard = aira_state.lock().unwrap();
-    let context = guard.get_emotional_context();
-
-    Json(CameraStatusResponse {
-        enabled: context.is_some(),
-        face_detected: context
-            .as_ref()
-            .map(|c| c.engagement > 0.1)
-            .unwrap_or(false),
-        last_update: context.as_ref().map(|c| c.timestamp),
-    })
+    let context = lock_or_recover(&aira_state).get_emotional_context();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    Json(camera_status(
+        context.as_ref(),
+        now,
+        camera_freshness_window(),
+    ))
 }
 
 // Detailed emotion response for real-time monitoring
@@ -417,31 +941,105 @@ pub struct EmotionDetailsResponse {
     pub stress: f32,
     pub positive_affect: f32,
     pub timestamp: u64,
-    pub smoothed: bool, // Indicates if values are smoothed
+    // How far to trust the reading (0.0 = no reading or no face)
+    pub confidence: f32,
+    // Debounced state from the state machine; steadier than the metrics above
+    pub emotion_state: EmotionState,
+    // Seconds `emotion_state` has been held
+    pub state_duration: u64,
+    pub smoothed: bool,       // Indicates if values are smoothed
+    pub source: &'static str, // "smoothed" or "raw"
+    // Whether replies are being steered right now (enabled, with a reading inside its TTL)
+    pub injection_active: bool,
 }
 
-// Get detailed emotional state with all metrics
+// Get detailed emotional state with all metrics (`?raw=true` for the unsmoothed reading)
 pub async fn get_emotion_details(
     State((aira_state, _semaphore)): State<(SharedAira, &'static Semaphore)>,
+    Query(query): Query<EmotionDetailsQuery>,
 ) -> Json<EmotionDetailsResponse> {
-    let guard = aira_state.lock().unwrap();
-    let context = guard.get_emotional_context();
+    let (raw, held, priority) = {
+        let tracker = lock_or_recover(&STATE_TRACKER);
+        (tracker.get_raw(), tracker.held_state(), tracker.priority())
+    };
+    let (smoothed, injection_active) = {
+        let guard = lock_or_recover(&aira_state);
+        (
+            guard.get_emotional_context(),
+            guard.is_emotion_injection_active(),
+        )
+    };
+    let mut details = if query.raw {
+        emotion_details(raw, false, held, &priority)
+    } else {
+        emotion_details(smoothed, true, held, &priority)
+    };
+    details.injection_active = injection_active;
+
+    Json(details)
+}
 
+#[derive(Serialize)]
+pub struct EmotionToggleResponse {
+    pub enabled: bool,
+}
+
+// Turn emotion-adaptive replies on or off; camera readings are still tracked
+pub async fn set_emotion_enabled(
+    State((aira_state, _semaphore)): State<(SharedAira, &'static Semaphore)>,
+    Json(req): Json<EmotionToggleRequest>,
+) -> Json<EmotionToggleResponse> {
+    let mut guard = lock_or_recover(&aira_state);
+    guard.set_emotion_enabled(req.enabled);
+    println!(
+        "🎭 Emotion-adaptive replies {}",
+        if req.enabled { "enabled" } else { "disabled" }
+    );
+
+    Json(EmotionToggleResponse {
+        enabled: guard.is_emotion_enabled(),
+    })
+}
+
+#[derive(Serialize)]
+pub struct EmotionAlphaResponse {
+    pub alpha: f32,
+}
+
+// Current EMA smoothing weight of new camera frames
+pub async fn get_emotion_alpha() -> Json<EmotionAlphaResponse> {
+    Json(EmotionAlphaResponse {
+        alpha: lock_or_recover(&STATE_TRACKER).alpha,
+    })
+}
+
+// Retune smoothing live; the next frame is smoothed with the new alpha
+// 0 is rejected as it would freeze the state on its current value
+pub async fn set_emotion_alpha(
+    Json(req): Json<EmotionAlphaRequest>,
+) -> Result<Json<EmotionAlphaResponse>, (StatusCode, String)> {
+    if !(req.alpha > 0.0 && req.alpha <= 1.0) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("alpha must be in (0.0, 1.0], got {}", req.alpha),
+        ));
+    }
+
+    lock_or_recover(&STATE_TRACKER).alpha = req.alpha;
+    println!("🎚️  Emotion smoothing alpha set to {}", req.alpha);
+    Ok(Json(EmotionAlphaResponse { alpha: req.alpha }))
+}
+
+// Build the details response; the timestamp is always the capture time of the frame
+fn emotion_details(
+    context: Option<EmotionalContext>,
+    smoothed: bool,
+    held: HeldState,
+    priority: &EmotionPriority,
+) -> EmotionDetailsResponse {
+    let confidence = context.map_or(0.0, |state| state.get_confidence());
     let (dominant, details) = if let Some(state) = context {
-        let dom = if state.fatigue > 0.7 {
-            "fatigued"
-        } else if state.stress > 0.6 {
-            "stressed"
-        } else if state.positive_affect > 0.6 {
-            "happy"
-        } else if state.engagement > 0.7 {
-            "focused"
-        } else if state.engagement < 0.3 {
-            "disengaged"
-        } else {
-            "neutral"
-        };
-        (dom.to_string(), state)
+        (dominant_name(&state, priority).to_string(), state)
     } else {
         (
             "unknown".to_string(),
@@ -451,17 +1049,387 @@ pub async fn get_emotion_details(
                 stress: 0.0,
                 positive_affect: 0.0,
                 timestamp: 0,
+                face_present: false,
             },
         )
     };
 
-    Json(EmotionDetailsResponse {
+    EmotionDetailsResponse {
         dominant_emotion: dominant,
         fatigue: details.fatigue,
         engagement: details.engagement,
         stress: details.stress,
         positive_affect: details.positive_affect,
         timestamp: details.timestamp,
-        smoothed: true,
-    })
+        confidence,
+        emotion_state: held.state,
+        state_duration: held.state_duration,
+        smoothed,
+        source: if smoothed { "smoothed" } else { "raw" },
+        injection_active: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(stress: f32, timestamp: u64) -> EmotionalContext {
+        EmotionalContext {
+            fatigue: 0.2,
+            engagement: 0.5,
+            stress,
+            positive_affect: 0.2,
+            timestamp,
+            face_present: true,
+        }
+    }
+
+    #[test]
+    fn test_rapid_changes_log_at_most_once_per_window() {
+        let mut tracker = EmotionalStateTracker::new(NeutralLevels::default());
+        tracker.log_throttle = EmotionLogThrottle::new(5, true);
+
+        // Jittery camera: stress flips around the threshold every frame
+        let mut logs = 0;
+        for (i, stress) in [0.95, 0.2, 0.97, 0.1, 0.96, 0.15].iter().enumerate() {
+            let raw = context(*stress, 1000 + i as u64 / 2);
+            tracker.update(raw);
+            if tracker.should_log(raw.timestamp) {
+                logs += 1;
+            }
+        }
+
+        assert!(logs <= 1, "expected at most one log, got {}", logs);
+    }
+
+    #[test]
+    fn test_unchanged_state_is_not_logged_again() {
+        let mut throttle = EmotionLogThrottle::new(5, false);
+
+        assert!(throttle.should_log(EmotionState::Neutral, 0));
+        // Same discrete state, even after the window, stays silent
+        assert!(!throttle.should_log(EmotionState::Neutral, 60));
+        // A real change after the window is logged
+        assert!(throttle.should_log(EmotionState::Stressed, 61));
+        // A change inside the window is suppressed
+        assert!(!throttle.should_log(EmotionState::Happy, 62));
+    }
+
+    #[test]
+    fn test_raw_and_smoothed_differ_after_step_change() {
+        let mut tracker = EmotionalStateTracker::new(NeutralLevels::default());
+
+        // Baseline is 0.5 stress; step straight to 0.9
+        let raw = context(0.9, 2000);
+        let smoothed = tracker
+            .update(raw)
+            .expect("step change should be significant");
+
+        let priority = tracker.priority();
+        let raw_details =
+            emotion_details(tracker.get_raw(), false, tracker.held_state(), &priority);
+        let smoothed_details =
+            emotion_details(Some(smoothed), true, tracker.held_state(), &priority);
+
+        assert_eq!(raw_details.stress, 0.9);
+        assert!(smoothed_details.stress < raw_details.stress);
+        assert_eq!(raw_details.source, "raw");
+        assert!(!raw_details.smoothed);
+        assert_eq!(smoothed_details.source, "smoothed");
+        assert!(smoothed_details.smoothed);
+        // Both report the capture time of the same frame
+        assert_eq!(raw_details.timestamp, 2000);
+        assert_eq!(smoothed_details.timestamp, 2000);
+    }
+
+    #[test]
+    fn test_details_expose_the_debounced_state() {
+        let mut tracker = EmotionalStateTracker::new(NeutralLevels::default());
+
+        // Smoothing takes a few frames before the stress threshold is crossed
+        for t in 1000..1010 {
+            tracker.update(context(0.95, t));
+        }
+        let held = tracker.held_state();
+        assert_eq!(held.state, EmotionState::Stressed);
+
+        // Held for as long as the frames keep agreeing
+        tracker.update(context(0.95, 1015));
+        let details = emotion_details(
+            Some(tracker.get_current()),
+            true,
+            tracker.held_state(),
+            &tracker.priority(),
+        );
+        assert_eq!(details.emotion_state, tracker.state_machine.current_state);
+        assert_eq!(details.state_duration, tracker.state_machine.state_duration);
+        assert!(details.state_duration > held.state_duration);
+    }
+
+    #[test]
+    fn test_priority_order_picks_the_held_state_and_label() {
+        let mut default = EmotionalStateTracker::new(NeutralLevels::default());
+        let mut stress_first = EmotionalStateTracker::new(NeutralLevels::default());
+        stress_first.state_machine.priority = EmotionPriority::parse("stress").unwrap();
+
+        // Tired and tense at once
+        for t in 1000..1020 {
+            let reading = EmotionalContext {
+                fatigue: 0.9,
+                ..context(0.9, t)
+            };
+            default.update(reading);
+            stress_first.update(reading);
+        }
+
+        assert_eq!(default.held_state().state, EmotionState::Fatigued);
+        assert_eq!(stress_first.held_state().state, EmotionState::Stressed);
+        let details = emotion_details(
+            Some(stress_first.get_current()),
+            true,
+            stress_first.held_state(),
+            &stress_first.priority(),
+        );
+        assert_eq!(details.dominant_emotion, "stressed");
+        // Camera responses name the same emotion the prompt and details do
+        assert_eq!(
+            dominant_name(&default.get_current(), &default.priority()),
+            "fatigued"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_round_trip_resumes_smoothing() {
+        let path = std::env::temp_dir().join(format!("aira_{}_tracker.json", std::process::id()));
+
+        // Settle a tracker on a high-stress baseline
+        let mut tracker = EmotionalStateTracker::new(NeutralLevels::default());
+        for t in 0..20 {
+            tracker.update(context(0.9, 1000 + t));
+        }
+        let saved_stress = tracker.get_current().stress;
+        save_snapshot(&path, &tracker.snapshot(1020)).unwrap();
+
+        let snapshot = load_snapshot(&path, 1100, 3600).expect("fresh snapshot should load");
+        let mut restored = EmotionalStateTracker::new(NeutralLevels::default());
+        restored.restore(snapshot);
+        assert_eq!(restored.get_current().stress, saved_stress);
+
+        // Smoothing continues from the loaded baseline rather than neutral
+        let mut fresh = EmotionalStateTracker::new(NeutralLevels::default());
+        restored.update(context(0.9, 1101));
+        fresh.update(context(0.9, 1101));
+        assert!(restored.get_current().stress >= saved_stress);
+        assert!(fresh.get_current().stress < saved_stress - 0.1);
+
+        // A snapshot past its TTL is ignored
+        assert!(load_snapshot(&path, 1020 + 7200, 3600).is_none());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_custom_neutral_is_the_start_and_no_face_target() {
+        let calm = NeutralLevels::parse("0.2, 0.6, 0.1, 0.7").unwrap();
+        let mut custom = EmotionalStateTracker::new(calm);
+        let mut default = EmotionalStateTracker::new(NeutralLevels::default());
+        assert_eq!(custom.get_current().stress, 0.1);
+
+        let no_face = CameraFeatures {
+            face_present: false,
+            face_confidence: 0.0,
+            avg_eye_openness: 0.0,
+            blink_rate: 0.0,
+            smile_score: 0.0,
+            head_pitch: 0.0,
+            head_yaw: 0.0,
+        };
+        // Push both away from neutral, then lose the face
+        for tracker in [&mut custom, &mut default] {
+            for t in 0..20 {
+                tracker.update(context(0.9, 1000 + t));
+            }
+            for _ in 0..40 {
+                process_frame(tracker, &no_face);
+            }
+        }
+
+        // Each settles near its own neutral; the change threshold stops it just short
+        let tolerance = custom.change_threshold / custom.alpha;
+        let settled = custom.get_current();
+        assert!((settled.stress - 0.1).abs() <= tolerance, "{:?}", settled);
+        assert!(
+            (settled.positive_affect - 0.7).abs() <= tolerance,
+            "{:?}",
+            settled
+        );
+        let baseline = default.get_current();
+        assert!((baseline.stress - 0.5).abs() <= tolerance, "{:?}", baseline);
+        assert!(settled.stress < baseline.stress - 0.2);
+
+        assert!(NeutralLevels::parse("0.2,0.6,0.1").is_err());
+        assert!(NeutralLevels::parse("0.2,0.6,0.1,1.5").is_err());
+    }
+
+    #[test]
+    fn test_batched_frames_advance_tracker_in_order() {
+        let body = r#"[
+            {"face_present": true, "face_confidence": 1.0, "avg_eye_openness": 1.0,
+             "blink_rate": 15.0, "smile_score": 0.0, "head_pitch": 0.0, "head_yaw": 0.0},
+            {"face_present": true, "face_confidence": 1.0, "avg_eye_openness": 1.0,
+             "blink_rate": 15.0, "smile_score": 0.0, "head_pitch": 0.0, "head_yaw": 0.0},
+            {"face_present": true, "face_confidence": 1.0, "avg_eye_openness": 1.0,
+             "blink_rate": 15.0, "smile_score": 0.0, "head_pitch": 0.0, "head_yaw": 0.0}
+        ]"#;
+        let frames = serde_json::from_str::<CameraFeaturesInput>(body)
+            .unwrap()
+            .into_frames();
+        assert_eq!(frames.len(), 3);
+
+        let mut tracker = EmotionalStateTracker::new(NeutralLevels::default());
+        let outcomes: Vec<FrameOutcome> = frames
+            .iter()
+            .map(|features| process_frame(&mut tracker, features))
+            .collect();
+
+        // Each frame pulls the smoothed engagement further toward the raw reading
+        assert!(outcomes.iter().all(|o| o.update.is_some()));
+        assert!(outcomes[0].state.engagement < outcomes[1].state.engagement);
+        assert!(outcomes[1].state.engagement < outcomes[2].state.engagement);
+        assert_eq!(
+            tracker.get_current().engagement,
+            outcomes[2].state.engagement
+        );
+    }
+
+    #[test]
+    fn test_single_frame_body_is_still_accepted() {
+        let body = r#"{"face_present": false, "face_confidence": 0.0, "avg_eye_openness": 0.0,
+            "blink_rate": 0.0, "smile_score": 0.0, "head_pitch": 0.0, "head_yaw": 0.0}"#;
+        let frames = serde_json::from_str::<CameraFeaturesInput>(body)
+            .unwrap()
+            .into_frames();
+        assert_eq!(frames.len(), 1);
+        assert!(!frames[0].face_present);
+    }
+
+    fn features() -> CameraFeatures {
+        CameraFeatures {
+            face_present: true,
+            face_confidence: 0.9,
+            avg_eye_openness: 0.8,
+            blink_rate: 15.0,
+            smile_score: 0.4,
+            head_pitch: 5.0,
+            head_yaw: -10.0,
+        }
+    }
+
+    #[test]
+    fn test_out_of_range_features_are_clamped() {
+        let wild = CameraFeatures {
+            face_confidence: 3.0,
+            avg_eye_openness: 5.0,
+            blink_rate: -4.0,
+            smile_score: -1.0,
+            head_pitch: 400.0,
+            head_yaw: -400.0,
+            ..features()
+        };
+
+        let clamped = sanitize_features(&wild).unwrap();
+        assert_eq!(clamped.face_confidence, 1.0);
+        assert_eq!(clamped.avg_eye_openness, 1.0);
+        assert_eq!(clamped.blink_rate, 0.0);
+        assert_eq!(clamped.smile_score, 0.0);
+        assert_eq!(clamped.head_pitch, 90.0);
+        assert_eq!(clamped.head_yaw, -90.0);
+
+        // In-range values pass through untouched
+        let valid = sanitize_features(&features()).unwrap();
+        assert_eq!(valid.avg_eye_openness, 0.8);
+        assert_eq!(valid.head_yaw, -10.0);
+    }
+
+    #[test]
+    fn test_non_finite_features_are_rejected() {
+        let nan = CameraFeatures {
+            avg_eye_openness: f32::NAN,
+            ..features()
+        };
+        let err = sanitize_features(&nan).unwrap_err();
+        assert!(err.contains("avg_eye_openness"));
+
+        let inf = CameraFeatures {
+            blink_rate: f32::INFINITY,
+            ..features()
+        };
+        assert!(sanitize_features(&inf).is_err());
+    }
+
+    #[test]
+    fn test_stale_reading_reports_camera_inactive() {
+        let reading = context(0.3, 1000);
+
+        let fresh = camera_status(Some(&reading), 1005, 10);
+        assert!(fresh.enabled);
+        assert!(fresh.face_detected);
+        assert_eq!(fresh.age_seconds, Some(5));
+
+        let stale = camera_status(Some(&reading), 1000 + 3600, 10);
+        assert!(!stale.enabled);
+        assert!(!stale.face_detected);
+        assert_eq!(stale.age_seconds, Some(3600));
+        assert_eq!(stale.last_update, Some(1000));
+
+        let none = camera_status(None, 1000, 10);
+        assert!(!none.enabled);
+        assert_eq!(none.age_seconds, None);
+    }
+
+    #[test]
+    fn test_api_dto_renders_timestamp_as_requested() {
+        let state = context(0.4, 1_709_294_400);
+
+        let unix = serde_json::to_value(EmotionalContextDto::new(&state, TimestampFormat::Unix));
+        assert_eq!(unix.unwrap()["timestamp"], 1_709_294_400);
+
+        let iso = serde_json::to_value(EmotionalContextDto::new(&state, TimestampFormat::Iso));
+        assert_eq!(iso.unwrap()["timestamp"], "2024-03-01T12:00:00Z");
+
+        let omitted =
+            serde_json::to_value(EmotionalContextDto::new(&state, TimestampFormat::Omit)).unwrap();
+        assert!(omitted.get("timestamp").is_none());
+        assert_eq!(omitted["face_present"], true);
+
+        // The stored reading keeps its unix timestamp
+        assert_eq!(state.timestamp, 1_709_294_400);
+        assert_eq!(
+            serde_json::to_value(state).unwrap()["timestamp"],
+            1_709_294_400
+        );
+        assert_eq!(iso8601(0), "1970-01-01T00:00:00Z");
+    }
+
+    #[tokio::test]
+    async fn test_alpha_set_live_applies_to_the_next_frame() {
+        let Json(before) = get_emotion_alpha().await;
+
+        let rejected = set_emotion_alpha(Json(EmotionAlphaRequest { alpha: 1.5 })).await;
+        assert_eq!(rejected.err().unwrap().0, StatusCode::BAD_REQUEST);
+
+        let Json(set) = set_emotion_alpha(Json(EmotionAlphaRequest { alpha: 1.0 }))
+            .await
+            .unwrap();
+        let Json(after) = get_emotion_alpha().await;
+        assert_eq!(after.alpha, set.alpha);
+        assert_eq!(after.alpha, 1.0);
+
+        // With alpha 1.0 the next frame replaces the smoothed state outright
+        let smoothed = lock_or_recover(&STATE_TRACKER).apply_ema(context(0.9, 3000));
+        assert_eq!(smoothed.stress, 0.9);
+
+        lock_or_recover(&STATE_TRACKER).alpha = before.alpha;
+    }
 }
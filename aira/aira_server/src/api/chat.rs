@@ -1,17 +1,26 @@
-use crate::models::ChatRequest;
-use crate::states::SharedAira;
+use crate::api::tts::{output_channels, supported_channels, voice_for};
+use crate::models::{ChatRequest, StreamGranularity};
+use crate::states::{SharedAira, acquire_permit, ensure_loaded, lock_or_recover};
+use aira_brain::aira::{Aira, AiraError, EngineKind, ReloadError};
+use aira_brain::audio::{apply_gain, upmix};
+use aira_brain::language::Language;
+use aira_brain::llm::{FinishReason, GenerationConfig, GenerationMetrics, ResponseLength};
+use aira_brain::tts::{TtsEngine, normalize_for_speech};
 use axum::{
     Json,
     extract::State,
     response::{
         IntoResponse,
-        sse::{Event, Sse},
+        sse::{Event, KeepAlive, KeepAliveStream, Sse},
     },
 };
+use serde::Serialize;
+use std::collections::VecDeque;
 use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
-use tokio::sync::{Semaphore, mpsc};
-use tokio::time::timeout;
+use tokio::sync::{Notify, Semaphore, SemaphorePermit, mpsc};
 
 // Remove markdown formatting artifacts from LLM output
 fn clean_llm_output(text: &str) -> String {
@@ -58,169 +67,950 @@ fn clean_llm_output(text: &str) -> String {
     result
 }
 
+// Shortest run of "." that is always kept, so an ellipsis stays an ellipsis
+const ELLIPSIS_LEN: usize = 3;
+
+// Collapses runs of repeated terminal punctuation ("?????", "!!!") across tokens
+// Only the last character and its run length carry over, so nothing is held back
+struct PunctuationRuns {
+    // Longest run of "!" or "?" kept ("." keeps at least ELLIPSIS_LEN); None passes text through
+    max_run: Option<usize>,
+    last: Option<char>,
+    run: usize,
+}
+
+impl PunctuationRuns {
+    fn new(max_run: Option<usize>) -> Self {
+        Self {
+            max_run,
+            last: None,
+            run: 0,
+        }
+    }
+
+    fn push(&mut self, text: &str) -> String {
+        let Some(max_run) = self.max_run else {
+            return text.to_string();
+        };
+
+        let mut result = String::with_capacity(text.len());
+        for c in text.chars() {
+            self.run = if self.last == Some(c) {
+                self.run + 1
+            } else {
+                1
+            };
+            self.last = Some(c);
+            let limit = match c {
+                '.' => max_run.max(ELLIPSIS_LEN),
+                '!' | '?' => max_run,
+                _ => usize::MAX,
+            };
+            if self.run > limit {
+                continue;
+            }
+            result.push(c);
+        }
+        result
+    }
+}
+
+// Groups streamed tokens into TTS chunks and numbers each chunk
+// Each token is tagged with the id of the chunk it starts in, and the chunk's
+// audio_complete event carries the same id, so clients can align captions to audio
+struct SentenceChunker {
+    buffer: String,
+    next_id: u64,
+    // Release the first sentence as soon as it ends, however short
+    eager_first: bool,
+}
+
+impl SentenceChunker {
+    fn new() -> Self {
+        Self {
+            buffer: String::with_capacity(128),
+            next_id: 0,
+            eager_first: false,
+        }
+    }
+
+    // Chunker for fast-start replies, whose short first sentence should be spoken right away
+    fn eager_first() -> Self {
+        Self {
+            eager_first: true,
+            ..Self::new()
+        }
+    }
+
+    // Add a token, returning its chunk id and a chunk once a sentence boundary is buffered
+    fn push(&mut self, token: &str) -> (u64, Option<(u64, String)>) {
+        let token_start = self.buffer.len();
+        let chunk = self.split_sentence(token);
+
+        // A token that begins after the split belongs to the next chunk
+        let token_id = match &chunk {
+            Some((id, text)) if token_start < text.len() => *id,
+            _ => self.next_id,
+        };
+        (token_id, chunk)
+    }
+
+    fn split_sentence(&mut self, token: &str) -> Option<(u64, String)> {
+        self.buffer.push_str(token);
+
+        // Wait for complete sentences (more robust boundary detection)
+        let eager = self.eager_first && self.next_id == 0;
+        if !eager && self.buffer.len() < 50 {
+            return None;
+        }
+
+        // Find last sentence boundary
+        let last_boundary = self.buffer.rfind(['.', '?', '!', '\n']).unwrap_or(0);
+        if last_boundary == 0 {
+            return None;
+        }
+
+        let chunk = self.buffer[..=last_boundary].to_string();
+        self.buffer = self.buffer[last_boundary + 1..].to_string();
+        self.take(chunk)
+    }
+
+    // Split whatever is left once generation ends
+    fn finish(mut self) -> Vec<(u64, String)> {
+        let mut chunks = Vec::new();
+
+        // Don't send tiny fragments - wait for meaningful content
+        while self.buffer.len() > 20 {
+            // Find last sentence boundary
+            let last_boundary = self
+                .buffer
+                .rfind(['.', '?', '!', '\n', ','])
+                .unwrap_or(self.buffer.len().saturating_sub(1));
+
+            if last_boundary == 0 {
+                break;
+            }
+
+            let chunk = self.buffer[..=last_boundary].to_string();
+            self.buffer = self.buffer[last_boundary + 1..].to_string();
+            chunks.extend(self.take(chunk));
+        }
+
+        // Send final chunk if there's content
+        if self.buffer.len() > 5 {
+            let rest = std::mem::take(&mut self.buffer);
+            chunks.extend(self.take(rest));
+        }
+
+        chunks
+    }
+
+    // Number a non-empty chunk
+    fn take(&mut self, chunk: String) -> Option<(u64, String)> {
+        if chunk.trim().is_empty() {
+            return None;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        Some((id, chunk))
+    }
+}
+
+// Buffers streamed text into words or sentences for clients that want fewer events
+// A batch never spans two TTS chunks, so each event still carries a single chunk id
+struct TokenBatcher {
+    granularity: StreamGranularity,
+    buffer: String,
+    chunk_id: u64,
+}
+
+impl TokenBatcher {
+    fn new(granularity: StreamGranularity) -> Self {
+        Self {
+            granularity,
+            buffer: String::new(),
+            chunk_id: 0,
+        }
+    }
+
+    // Add a token from `chunk_id`, returning any text ready to send, in order
+    fn push(&mut self, chunk_id: u64, token: &str) -> Vec<(u64, String)> {
+        if self.granularity == StreamGranularity::Token {
+            return vec![(chunk_id, token.to_string())];
+        }
+
+        let mut ready = Vec::new();
+        if chunk_id != self.chunk_id && !self.buffer.is_empty() {
+            ready.push((self.chunk_id, std::mem::take(&mut self.buffer)));
+        }
+        self.chunk_id = chunk_id;
+        self.buffer.push_str(token);
+
+        let boundary = match self.granularity {
+            StreamGranularity::Token => None,
+            StreamGranularity::Word => self
+                .buffer
+                .char_indices()
+                .rev()
+                .find(|(_, c)| c.is_whitespace())
+                .map(|(i, c)| i + c.len_utf8()),
+            StreamGranularity::Sentence => self.buffer.rfind(['.', '?', '!', '\n']).map(|i| i + 1),
+        };
+        if let Some(end) = boundary {
+            let rest = self.buffer.split_off(end);
+            ready.push((chunk_id, std::mem::replace(&mut self.buffer, rest)));
+        }
+        ready
+    }
+
+    // Whatever is still buffered once generation ends
+    fn finish(self) -> Option<(u64, String)> {
+        (!self.buffer.is_empty()).then_some((self.chunk_id, self.buffer))
+    }
+}
+
+type EventStream =
+    std::pin::Pin<Box<dyn tokio_stream::Stream<Item = Result<Event, Infallible>> + Send>>;
+
+type ChatSse = Sse<KeepAliveStream<EventStream>>;
+
+// Seconds between keep-alives, from AIRA_SSE_KEEPALIVE_SECS (default: 15)
+fn keep_alive_interval() -> Duration {
+    let secs = std::env::var("AIRA_SSE_KEEPALIVE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(15);
+    Duration::from_secs(secs)
+}
+
+// Ping idle streams so proxies don't drop them before the first token arrives
+// Keep-alives are SSE comments, which the client's event parser skips
+fn with_keep_alive(stream: EventStream, interval: Duration) -> ChatSse {
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(interval))
+}
+
+fn sse_response(stream: EventStream) -> ChatSse {
+    with_keep_alive(stream, keep_alive_interval())
+}
+
+// SSE response carrying a single error event
+fn error_stream(message: &'static str) -> ChatSse {
+    let stream: EventStream = Box::pin(tokio_stream::iter(vec![Ok::<_, Infallible>(
+        Event::default().event("error").data(message),
+    )]));
+    sse_response(stream)
+}
+
 // Chat endpoint with semaphore-based rate limiting to prevent memory corruption
 pub async fn chat(
     State((aira_state, semaphore)): State<(SharedAira, &'static Semaphore)>,
     Json(req): Json<ChatRequest>,
 ) -> impl IntoResponse {
-    // Try to acquire a permit with timeout
-    let _permit = match timeout(Duration::from_secs(5), semaphore.acquire()).await {
-        Ok(Ok(permit)) => permit,
-        Ok(Err(_)) => {
-            let stream: std::pin::Pin<
-                Box<dyn tokio_stream::Stream<Item = Result<Event, Infallible>> + Send>,
-            > = Box::pin(tokio_stream::iter(vec![Ok::<_, Infallible>(
-                Event::default()
-                    .event("error")
-                    .data("Server is shutting down"),
-            )]));
-            return Sse::new(stream);
-        }
-        Err(_) => {
-            let stream: std::pin::Pin<
-                Box<dyn tokio_stream::Stream<Item = Result<Event, Infallible>> + Send>,
-            > = Box::pin(tokio_stream::iter(vec![Ok::<_, Infallible>(
-                Event::default()
-                    .event("error")
-                    .data("Server is busy, please try again"),
-            )]));
-            return Sse::new(stream);
+    let permit = match acquire_permit(semaphore, error_stream).await {
+        Ok(permit) => permit,
+        Err(response) => return response,
+    };
+
+    let config = GenerationConfig {
+        length: req
+            .length
+            .or_else(|| ResponseLength::detect(&req.message))
+            .unwrap_or_default(),
+        temperature: req.temperature,
+        fast_first_sentence: req.fast_start.unwrap_or_else(fast_first_sentence),
+        // An empty language in the request drops the server's default
+        response_language: match req.response_language.as_deref() {
+            Some(language) => Language::parse(language),
+            None => response_language(),
+        },
+        max_words: req.max_words.filter(|&words| words > 0),
+    };
+    let fast_start = config.fast_first_sentence;
+    let voice = ReplyVoice {
+        volume: req.volume,
+        channels: req.channels,
+        streaming: req.audio_streaming,
+        language: config.response_language.clone(),
+    };
+    let message = req.message;
+    let history = req.messages;
+    let granularity = req.stream_granularity;
+
+    stream_generation(
+        permit,
+        aira_state,
+        voice,
+        granularity,
+        fast_start,
+        move |aira, on_token| {
+            // Stateless clients send the whole conversation, so any instance can answer
+            match &history {
+                Some(history) => aira.think_stateless(history, &message, &config, on_token),
+                None => aira.think_with(&message, &config, on_token),
+            }
+        },
+    )
+}
+
+// Pipeline stage an `error` event reports on, so clients can retry the right thing
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Stage {
+    Llm,
+    Stt,
+    Tts,
+}
+
+impl From<EngineKind> for Stage {
+    fn from(engine: EngineKind) -> Self {
+        match engine {
+            EngineKind::Llm => Stage::Llm,
+            EngineKind::Stt => Stage::Stt,
+            EngineKind::Tts => Stage::Tts,
         }
+    }
+}
+
+// Stage a failed model reload belongs to; the LLM when the failure doesn't say
+fn reload_failure_stage(error: &anyhow::Error) -> Stage {
+    error
+        .downcast_ref::<ReloadError>()
+        .map_or(Stage::Llm, |e| e.engine.into())
+}
+
+// Body of a stream `error` event; details stay in the server log, clients get a safe message
+#[derive(Serialize)]
+struct StageError<'a> {
+    stage: Stage,
+    message: &'a str,
+}
+
+fn stage_error(stage: Stage, message: &str) -> Event {
+    let body = StageError { stage, message };
+    Event::default()
+        .event("error")
+        .json_data(&body)
+        .unwrap_or_else(|_| Event::default().event("error").data(message))
+}
+
+// Final metrics of a generation, sent as the `done` event
+#[derive(Serialize)]
+struct DoneEvent {
+    finish_reason: FinishReason,
+    tokens_per_second: f64,
+    input_truncated: bool,
+    // Replayed from the response cache (AIRA_RESPONSE_CACHE) rather than generated
+    cached: bool,
+}
+
+// Stop flag of the stream currently speaking, if any
+// Streams hold the only chat permit (CHAT_CONCURRENCY) until their audio is done, so a
+// single slot is enough
+static ACTIVE_AUDIO: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+
+#[derive(Serialize)]
+pub struct StopAudioResponse {
+    pub stopped: bool,
+}
+
+// Stop speaking the current reply while its text keeps streaming
+pub async fn stop_audio(
+    _state: State<(SharedAira, &'static Semaphore)>,
+) -> Json<StopAudioResponse> {
+    let active = lock_or_recover(&ACTIVE_AUDIO).clone();
+    let stopped = match active {
+        Some(stop) => !stop.swap(true, Ordering::SeqCst),
+        None => false,
+    };
+    Json(StopAudioResponse { stopped })
+}
+
+// Whether chats start with a quick greedy first sentence, set from AIRA_FAST_FIRST_SENTENCE
+static FAST_FIRST_SENTENCE: OnceLock<bool> = OnceLock::new();
+
+pub fn set_fast_first_sentence(enabled: bool) {
+    let _ = FAST_FIRST_SENTENCE.set(enabled);
+}
+
+pub fn fast_first_sentence() -> bool {
+    FAST_FIRST_SENTENCE.get().copied().unwrap_or(false)
+}
+
+// Longest run of the same terminal punctuation kept in replies, set from
+// AIRA_MAX_REPEATED_PUNCTUATION; None when collapsing is off
+static PUNCTUATION_RUN_LIMIT: OnceLock<Option<usize>> = OnceLock::new();
+
+// "Really?????" is spoken as "Really?", while "Well..." keeps its ellipsis
+const DEFAULT_PUNCTUATION_RUN_LIMIT: usize = 1;
+
+pub fn set_punctuation_run_limit(limit: Option<usize>) {
+    let _ = PUNCTUATION_RUN_LIMIT.set(limit.filter(|&limit| limit > 0));
+}
+
+pub fn punctuation_run_limit() -> Option<usize> {
+    *PUNCTUATION_RUN_LIMIT.get_or_init(|| Some(DEFAULT_PUNCTUATION_RUN_LIMIT))
+}
+
+// Language replies are written in unless a request names one, set from AIRA_RESPONSE_LANGUAGE
+static RESPONSE_LANGUAGE: OnceLock<Language> = OnceLock::new();
+
+pub fn set_response_language(language: Language) {
+    let _ = RESPONSE_LANGUAGE.set(language);
+}
+
+pub fn response_language() -> Option<Language> {
+    RESPONSE_LANGUAGE.get().cloned()
+}
+
+// Silence after each spoken sentence, set from AIRA_INTER_SENTENCE_PAUSE_MS
+static SENTENCE_PAUSE: OnceLock<Duration> = OnceLock::new();
+
+// A breath between sentences; Piper leaves almost none on its own
+const DEFAULT_SENTENCE_PAUSE: Duration = Duration::from_millis(150);
+
+pub fn set_inter_sentence_pause(pause: Duration) {
+    let _ = SENTENCE_PAUSE.set(pause);
+}
+
+pub fn inter_sentence_pause() -> Duration {
+    SENTENCE_PAUSE
+        .get()
+        .copied()
+        .unwrap_or(DEFAULT_SENTENCE_PAUSE)
+}
+
+// Pad a sentence's audio with `pause` of silence, so pacing needs no re-synthesis
+fn append_pause(samples: &mut Vec<f32>, sample_rate: u32, pause: Duration) {
+    let silence = (sample_rate as f64 * pause.as_secs_f64()).round() as usize;
+    samples.resize(samples.len() + silence, 0.0);
+}
+
+// A short pre-synthesized clip ("hmm...") to play while the first sentence is generated
+#[derive(Clone)]
+struct ThinkingFiller {
+    wav_base64: String,
+    // Time between repeats of the clip, including the clip itself
+    interval: Duration,
+}
+
+// Set once at startup when AIRA_THINKING_FILLER is configured; off by default
+static THINKING_FILLER: OnceLock<ThinkingFiller> = OnceLock::new();
+
+// Silence between repeats of the filler clip
+const FILLER_PAUSE: Duration = Duration::from_millis(1500);
+
+// Synthesize `text` once so chat streams can send it before their first sentence is spoken
+pub fn prepare_thinking_filler(tts: &TtsEngine, text: &str) -> anyhow::Result<()> {
+    let mut samples = tts.synthesize(&normalize_for_speech(text))?;
+    if samples.is_empty() {
+        anyhow::bail!("Thinking filler {:?} produced no audio", text);
+    }
+    apply_gain(&mut samples, tts.volume());
+
+    let clip = Duration::from_secs_f64(samples.len() as f64 / tts.sample_rate() as f64);
+    let filler = ThinkingFiller {
+        wav_base64: samples_to_base64_wav(samples, tts.sample_rate(), output_channels())?,
+        interval: clip + FILLER_PAUSE,
+    };
+    let _ = THINKING_FILLER.set(filler);
+    Ok(())
+}
+
+// Send the filler as `filler` events until the reply's first audio is ready
+// `first_audio` is notified by the TTS worker, so the client can cut the filler before playing real audio
+async fn run_filler(
+    filler: ThinkingFiller,
+    first_audio: Arc<Notify>,
+    stop: Arc<AtomicBool>,
+    event_tx: mpsc::Sender<Result<Event, Infallible>>,
+) {
+    let mut ticks = tokio::time::interval(filler.interval);
+    loop {
+        tokio::select! {
+            biased;
+            _ = first_audio.notified() => break,
+            _ = ticks.tick() => {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                let event = Event::default().event("filler").data(&filler.wav_base64);
+                if event_tx.send(Ok(event)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// Syntheses the TTS worker may run at once, set from AIRA_TTS_CONCURRENCY
+static TTS_CONCURRENCY: OnceLock<usize> = OnceLock::new();
+
+pub fn set_tts_concurrency(concurrency: usize) {
+    let _ = TTS_CONCURRENCY.set(concurrency.max(1));
+}
+
+// One at a time by default, so sentences don't contend for the CPU
+pub fn tts_concurrency() -> usize {
+    TTS_CONCURRENCY.get().copied().unwrap_or(1)
+}
+
+// A chunk being synthesized on the blocking pool, with the WAVs it has produced so far
+struct Synthesis {
+    chunk_id: u64,
+    parts: mpsc::Receiver<String>,
+    parts_sent: usize,
+    handle: tokio::task::JoinHandle<anyhow::Result<()>>,
+}
+
+fn start_synthesis<S>(synth: S, chunk_id: u64, text_chunk: String) -> Synthesis
+where
+    S: Fn(&str, &mut dyn FnMut(String)) -> anyhow::Result<()> + Send + 'static,
+{
+    let (part_tx, parts) = mpsc::channel::<String>(8);
+    let handle = tokio::task::spawn_blocking(move || {
+        synth(&text_chunk, &mut |wav_base64| {
+            let _ = part_tx.blocking_send(wav_base64);
+        })
+    });
+    Synthesis {
+        chunk_id,
+        parts,
+        parts_sent: 0,
+        handle,
+    }
+}
+
+// The next WAV of the oldest synthesis; never resolves when nothing is in flight
+async fn next_part(synthesis: Option<&mut Synthesis>) -> Option<String> {
+    match synthesis {
+        Some(synthesis) => synthesis.parts.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+// Synthesize queued chunks and send each as an audio_complete event, in chunk order
+// Up to `concurrency` chunks are synthesized at once; later chunks' audio waits for earlier ones
+// After `stop` is set the remaining chunks are drained unspoken, so the LLM never blocks on the queue
+// `synth` passes each WAV it produces to its callback, and none for chunks with nothing audible
+// A reply that never produced audio gets one no_audio event
+// With `streaming`, every WAV is sent as an audio_chunk event as it arrives, then one with end: true
+// `first_audio` is notified once audio is ready, or when the worker ends without any
+async fn run_tts_worker<S>(
+    mut tts_rx: mpsc::Receiver<(u64, String)>,
+    synth: Option<S>,
+    streaming: bool,
+    concurrency: usize,
+    event_tx: mpsc::Sender<Result<Event, Infallible>>,
+    stop: Arc<AtomicBool>,
+    first_audio: Arc<Notify>,
+) where
+    S: Fn(&str, &mut dyn FnMut(String)) -> anyhow::Result<()> + Clone + Send + 'static,
+{
+    let mut stop_reported = false;
+    let mut sent_audio = false;
+    let mut in_flight: VecDeque<Synthesis> = VecDeque::new();
+    let mut queue_open = true;
+
+    while queue_open || !in_flight.is_empty() {
+        let can_start = queue_open && in_flight.len() < concurrency.max(1);
+        tokio::select! {
+            biased;
+            received = tts_rx.recv(), if can_start => {
+                let Some((chunk_id, text_chunk)) = received else {
+                    queue_open = false;
+                    continue;
+                };
+                if stop.load(Ordering::SeqCst) {
+                    if !stop_reported {
+                        stop_reported = true;
+                        let _ = event_tx
+                            .send(Ok(Event::default().event("audio_stopped").data("")))
+                            .await;
+                    }
+                    continue;
+                }
+
+                // Text-only deployments stream tokens without audio
+                if let Some(synth) = synth.clone() {
+                    in_flight.push_back(start_synthesis(synth, chunk_id, text_chunk));
+                }
+            }
+            part = next_part(in_flight.front_mut()) => {
+                let Some(wav_base64) = part else {
+                    // The oldest chunk is done; mark its last part so the client knows the sentence is complete
+                    let Some(done) = in_flight.pop_front() else {
+                        continue;
+                    };
+                    if streaming && done.parts_sent > 0 {
+                        let _ = event_tx
+                            .send(Ok(audio_chunk_event(done.chunk_id, None)))
+                            .await;
+                    }
+                    match done.handle.await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => report_tts_failure(&event_tx, done.chunk_id, e).await,
+                        Err(e) => report_tts_failure(&event_tx, done.chunk_id, e).await,
+                    }
+                    continue;
+                };
+
+                // Drop audio that finished after the client asked for silence
+                if stop.load(Ordering::SeqCst) {
+                    continue;
+                }
+                let Some(current) = in_flight.front_mut() else {
+                    continue;
+                };
+                if !sent_audio {
+                    first_audio.notify_one();
+                }
+                sent_audio = true;
+                current.parts_sent += 1;
+
+                let event = if streaming {
+                    audio_chunk_event(current.chunk_id, Some(&wav_base64))
+                } else {
+                    Event::default()
+                        .event("audio_complete")
+                        .id(current.chunk_id.to_string())
+                        .data(wav_base64)
+                };
+                let _ = event_tx.send(Ok(event)).await;
+            }
+        }
+    }
+
+    first_audio.notify_one();
+
+    // Tell the client not to wait for audio that will never come
+    if synth.is_some() && !sent_audio && !stop.load(Ordering::SeqCst) {
+        let _ = event_tx
+            .send(Ok(Event::default().event("no_audio").data("")))
+            .await;
+    }
+    println!("TTS worker finished processing all chunks");
+}
+
+// Part of a chunk's audio in streaming mode; the chunk's final event has no audio and end: true
+#[derive(Serialize)]
+struct AudioChunk<'a> {
+    audio: Option<&'a str>,
+    end: bool,
+}
+
+fn audio_chunk_event(chunk_id: u64, audio: Option<&str>) -> Event {
+    let body = AudioChunk {
+        audio,
+        end: audio.is_none(),
+    };
+    Event::default()
+        .event("audio_chunk")
+        .id(chunk_id.to_string())
+        .json_data(&body)
+        .unwrap_or_else(|_| Event::default().event("audio_chunk"))
+}
+
+// Log a failed chunk and tell the client its audio won't come, without the failure's details
+async fn report_tts_failure(
+    event_tx: &mpsc::Sender<Result<Event, Infallible>>,
+    chunk_id: u64,
+    error: impl std::fmt::Display,
+) {
+    eprintln!("TTS synthesis failed for chunk {}: {}", chunk_id, error);
+    let event = stage_error(Stage::Tts, "Speech synthesis failed").id(chunk_id.to_string());
+    let _ = event_tx.send(Ok(event)).await;
+}
+
+// Discard the last reply and stream a fresh answer to the same user message
+pub async fn regenerate(
+    State((aira_state, semaphore)): State<(SharedAira, &'static Semaphore)>,
+) -> impl IntoResponse {
+    let permit = match acquire_permit(semaphore, error_stream).await {
+        Ok(permit) => permit,
+        Err(response) => return response,
+    };
+
+    stream_generation(
+        permit,
+        aira_state,
+        ReplyVoice::default(),
+        StreamGranularity::Token,
+        false,
+        |aira, on_token| aira.regenerate(on_token),
+    )
+}
+
+// Stream more of the last reply, appended to it, e.g. after a `length` finish
+pub async fn continue_reply(
+    State((aira_state, semaphore)): State<(SharedAira, &'static Semaphore)>,
+) -> impl IntoResponse {
+    let permit = match acquire_permit(semaphore, error_stream).await {
+        Ok(permit) => permit,
+        Err(response) => return response,
     };
 
+    stream_generation(
+        permit,
+        aira_state,
+        ReplyVoice::default(),
+        StreamGranularity::Token,
+        false,
+        |aira, on_token| aira.continue_reply(on_token),
+    )
+}
+
+// How a streamed reply is spoken; the defaults use the server's settings
+#[derive(Default)]
+struct ReplyVoice {
+    // Output gain, or the engine's default gain when None
+    volume: Option<f32>,
+    channels: Option<u16>,
+    // Send each sentence's audio in parts as it is synthesized (audio_chunk events)
+    streaming: bool,
+    // Pick a voice that speaks this, when one is loaded
+    language: Option<Language>,
+}
+
+// Run `generate` on the blocking pool, streaming tokens, TTS audio and metrics as SSE
+// Text events are batched to `granularity`; audio chunking is unaffected
+// `fast_start` speaks the first sentence as soon as it ends, for fast-first-sentence replies
+// `permit` is held until the reply has been spoken, so streams never overlap
+fn stream_generation<G>(
+    permit: SemaphorePermit<'static>,
+    aira_state: SharedAira,
+    voice: ReplyVoice,
+    granularity: StreamGranularity,
+    fast_start: bool,
+    generate: G,
+) -> ChatSse
+where
+    G: FnOnce(
+            &mut Aira,
+            &mut dyn FnMut(&str) -> anyhow::Result<()>,
+        ) -> anyhow::Result<GenerationMetrics>
+        + Send
+        + 'static,
+{
+    let ReplyVoice {
+        volume,
+        channels,
+        streaming: audio_streaming,
+        language,
+    } = voice;
     // Use larger channel to reduce backpressure
     let (event_tx, event_rx) = mpsc::channel::<Result<Event, Infallible>>(512);
 
     tokio::spawn(async move {
+        // Released when this task ends, after the last audio, not when the response is returned
+        let _permit = permit;
+
+        // Bring unloaded models back before looking up the TTS engine
+        if let Err(e) = ensure_loaded(&aira_state).await {
+            eprintln!("Model reload failed: {}", e);
+            let _ = event_tx
+                .send(Ok(stage_error(
+                    reload_failure_stage(&e),
+                    "Models failed to load, please try again",
+                )))
+                .await;
+            return;
+        }
+
         // Clone TTS engine ONCE outside the lock for concurrent use
         let tts_engine = {
-            let guard = aira_state.lock().unwrap();
-            guard.get_tts()
+            let guard = lock_or_recover(&aira_state);
+            voice_for(guard.get_tts(), language.as_ref())
         };
 
         // TTS worker channel
-        let (tts_tx, mut tts_rx) = mpsc::channel::<String>(32);
-
-        // Spawn TTS worker that processes chunks sequentially (not concurrently)
-        let event_tx_tts = event_tx.clone();
-        let tts_worker_handle = tokio::spawn(async move {
-            while let Some(text_chunk) = tts_rx.recv().await {
-                let tts = tts_engine.clone();
-                let event_tx = event_tx_tts.clone();
-
-                // Process TTS sequentially with error handling
-                let result = tokio::task::spawn_blocking(move || {
-                    match tts.synthesize(&text_chunk) {
-                        Ok(samples) => {
-                            // Convert to WAV and encode as base64
-                            match samples_to_base64_wav(samples) {
-                                Ok(wav_base64) => {
-                                    let _ = event_tx.blocking_send(Ok(Event::default()
-                                        .event("audio_complete")
-                                        .data(wav_base64)));
-                                }
-                                Err(e) => eprintln!("WAV encoding error: {}", e),
-                            }
-                        }
-                        Err(e) => eprintln!("TTS synthesis error: {}", e),
+        let (tts_tx, tts_rx) = mpsc::channel::<(u64, String)>(32);
+
+        // Register this stream's stop flag for /api/chat/stop-audio
+        let stop = Arc::new(AtomicBool::new(false));
+        *lock_or_recover(&ACTIVE_AUDIO) = Some(stop.clone());
+
+        // Samples spoken for this reply, recorded on its turn once the audio is done
+        let spoken_samples = Arc::new(AtomicUsize::new(0));
+        let spoken_rate = tts_engine.as_ref().map(|tts| tts.sample_rate());
+
+        // Speak a normalized copy; the displayed tokens keep their bullets
+        let synth = tts_engine.map(|tts| {
+            let volume = volume.unwrap_or(tts.volume());
+            let channels = channels.map_or(output_channels(), supported_channels);
+            let pause = inter_sentence_pause();
+            let spoken_samples = spoken_samples.clone();
+            move |text: &str, emit: &mut dyn FnMut(String)| {
+                let text = normalize_for_speech(text);
+                let rate = tts.sample_rate();
+                if audio_streaming {
+                    let mut spoke = false;
+                    tts.synthesize_streamed(&text, &mut |mut samples| {
+                        spoke = true;
+                        apply_gain(&mut samples, volume);
+                        spoken_samples.fetch_add(samples.len(), Ordering::Relaxed);
+                        emit(samples_to_base64_wav(samples, rate, channels)?);
+                        Ok(())
+                    })?;
+                    // The sentence's pause follows as one more, silent part
+                    if spoke && !pause.is_zero() {
+                        let mut silence = Vec::new();
+                        append_pause(&mut silence, rate, pause);
+                        spoken_samples.fetch_add(silence.len(), Ordering::Relaxed);
+                        emit(samples_to_base64_wav(silence, rate, channels)?);
                     }
-                })
-                .await;
+                    return Ok(());
+                }
 
-                if let Err(e) = result {
-                    eprintln!("TTS task panicked: {}", e);
+                let mut samples = tts.synthesize(&text)?;
+                if !samples.is_empty() {
+                    apply_gain(&mut samples, volume);
+                    append_pause(&mut samples, rate, pause);
+                    spoken_samples.fetch_add(samples.len(), Ordering::Relaxed);
+                    emit(samples_to_base64_wav(samples, rate, channels)?);
                 }
+                Ok(())
             }
-            println!("TTS worker finished processing all chunks");
         });
 
+        // Fill the wait for the first sentence, if a filler is configured and there will be audio
+        let first_audio = Arc::new(Notify::new());
+        if let Some(filler) = THINKING_FILLER.get()
+            && synth.is_some()
+        {
+            tokio::spawn(run_filler(
+                filler.clone(),
+                first_audio.clone(),
+                stop.clone(),
+                event_tx.clone(),
+            ));
+        }
+
+        // Spawn TTS worker that speaks chunks in order, synthesizing up to tts_concurrency() at once
+        let tts_worker_handle = tokio::spawn(run_tts_worker(
+            tts_rx,
+            synth,
+            audio_streaming,
+            tts_concurrency(),
+            event_tx.clone(),
+            stop.clone(),
+            first_audio,
+        ));
+
         // LLM inference in blocking thread
         let event_tx_llm = event_tx.clone();
-        let message = req.message.clone();
 
-        let llm_result = tokio::task::spawn_blocking(move || {
-            // Sentence buffer for TTS
-            let mut sentence_buffer = String::with_capacity(128);
+        let llm_result = crate::priority::run_inference(move || {
+            // Sentence chunker for TTS
+            let mut chunker = if fast_start {
+                SentenceChunker::eager_first()
+            } else {
+                SentenceChunker::new()
+            };
+            let mut batcher = TokenBatcher::new(granularity);
+            let mut punctuation = PunctuationRuns::new(punctuation_run_limit());
+            let send_text = |chunk_id: u64, text: String| {
+                let _ = event_tx_llm
+                    .blocking_send(Ok(Event::default().id(chunk_id.to_string()).data(text)));
+            };
 
-            let tps_result = {
-                let mut guard = aira_state.lock().unwrap();
+            let (tps_result, session_reset) = {
+                let mut guard = lock_or_recover(&aira_state);
 
-                guard.think(&message, |token: &str| {
+                let result = generate(&mut guard, &mut |token: &str| {
                     // Clean markdown formatting from token
-                    let cleaned_token = clean_llm_output(token);
+                    let cleaned_token = punctuation.push(&clean_llm_output(token));
 
-                    // Send cleaned token immediately
-                    let _ = event_tx_llm
-                        .blocking_send(Ok(Event::default().data(cleaned_token.clone())));
+                    let (chunk_id, chunk) = chunker.push(&cleaned_token);
 
-                    // Buffer for sentence detection (use original token for detection)
-                    sentence_buffer.push_str(&cleaned_token);
+                    // Send cleaned text as soon as the granularity allows, tagged with its chunk
+                    for (id, text) in batcher.push(chunk_id, &cleaned_token) {
+                        send_text(id, text);
+                    }
 
                     // Send to TTS on sentence boundaries
-                    // Wait for complete sentences (more robust boundary detection)
-                    if sentence_buffer.len() >= 50 {
-                        // Find last sentence boundary
-                        let last_boundary = sentence_buffer
-                            .rfind(|c| c == '.' || c == '?' || c == '!' || c == '\n')
-                            .unwrap_or(0);
-                        
-                        if last_boundary > 0 {
-                            let chunk = sentence_buffer[..=last_boundary].to_string();
-                            if !chunk.trim().is_empty() {
-                                let _ = tts_tx.blocking_send(chunk);
-                            }
-                            sentence_buffer = sentence_buffer[last_boundary + 1..].to_string();
-                        }
+                    if let Some(chunk) = chunk {
+                        let _ = tts_tx.blocking_send(chunk);
                     }
 
                     Ok::<_, anyhow::Error>(())
-                })
+                });
+                (result, guard.take_session_reset())
             };
 
-            // Send tps after generation completes
-            if let Ok(tps) = tps_result {
-                let _ = event_tx_llm.blocking_send(Ok(Event::default()
-                    .event("tps")
-                    .data(format!("{:.2}", tps))));
+            if let Some((id, text)) = batcher.finish() {
+                send_text(id, text);
             }
 
-            // Send remaining buffer to TTS (ensure complete sentences)
-            // Don't send tiny fragments - wait for meaningful content
-            while sentence_buffer.len() > 20 {
-                // Find last sentence boundary
-                let last_boundary = sentence_buffer
-                    .rfind(|c| c == '.' || c == '?' || c == '!' || c == '\n' || c == ',')
-                    .unwrap_or(sentence_buffer.len().saturating_sub(1));
-                
-                if last_boundary > 0 {
-                    let chunk = sentence_buffer[..=last_boundary].to_string();
-                    if !chunk.trim().is_empty() {
-                        let _ = tts_tx.blocking_send(chunk);
+            let replied = tps_result.is_ok();
+
+            // Send tps after generation completes
+            match tps_result {
+                Ok(metrics) => {
+                    let _ = event_tx_llm.blocking_send(Ok(Event::default()
+                        .event("tps")
+                        .data(format!("{:.2}", metrics.tokens_per_second))));
+
+                    // Let the UI warn that the message was cut down
+                    if metrics.input_truncated {
+                        let _ = event_tx_llm.blocking_send(Ok(Event::default()
+                            .event("warning")
+                            .data("input_truncated")));
+                    }
+
+                    // The reply was the last of a session that hit AIRA_MAX_TURNS
+                    if session_reset.is_some() {
+                        let _ = event_tx_llm.blocking_send(Ok(Event::default()
+                            .event("warning")
+                            .data("session_reset")));
+                    }
+
+                    // Tell the client whether the reply ended naturally or was cut off
+                    let done = DoneEvent {
+                        finish_reason: metrics.finish_reason,
+                        tokens_per_second: metrics.tokens_per_second,
+                        input_truncated: metrics.input_truncated,
+                        cached: metrics.cached,
+                    };
+                    if let Ok(event) = Event::default().event("done").json_data(&done) {
+                        let _ = event_tx_llm.blocking_send(Ok(event));
                     }
-                    sentence_buffer = sentence_buffer[last_boundary + 1..].to_string();
-                } else {
-                    break;
+                }
+                Err(e) => {
+                    eprintln!("Generation failed: {}", e);
+                    // Our own errors are safe to show; anything else may carry internals
+                    let message = match e.downcast_ref::<AiraError>() {
+                        Some(e) => e.to_string(),
+                        None => "Generation failed, please try again".to_string(),
+                    };
+                    let _ = event_tx_llm.blocking_send(Ok(stage_error(Stage::Llm, &message)));
                 }
             }
-            
-            // Send final chunk if there's content
-            if !sentence_buffer.trim().is_empty() && sentence_buffer.len() > 5 {
-                let _ = tts_tx.blocking_send(sentence_buffer);
+
+            // Send remaining buffer to TTS (ensure complete sentences)
+            for chunk in chunker.finish() {
+                let _ = tts_tx.blocking_send(chunk);
             }
 
             // Close TTS channel to signal no more chunks
             drop(tts_tx);
+            replied
         })
         .await;
 
-        if let Err(e) = llm_result {
-            eprintln!("LLM task panicked: {}", e);
-            let _ = event_tx
-                .send(Ok(Event::default()
-                    .event("error")
-                    .data("Processing failed, please try again")))
-                .await;
-        }
+        let replied = match llm_result {
+            Ok(replied) => replied,
+            Err(e) => {
+                eprintln!("LLM task panicked: {}", e);
+                let _ = event_tx
+                    .send(Ok(stage_error(
+                        Stage::Llm,
+                        "Processing failed, please try again",
+                    )))
+                    .await;
+                false
+            }
+        };
 
         // Wait for TTS worker to finish processing all queued chunks
         println!("Waiting for TTS worker to complete...");
@@ -229,24 +1019,45 @@ pub async fn chat(
         } else {
             println!("TTS worker completed successfully");
         }
+
+        // A failed reply left no turn of its own to attach the audio to
+        let spoken = spoken_samples.load(Ordering::Relaxed);
+        if let Some(rate) = spoken_rate
+            && replied
+            && spoken > 0
+        {
+            lock_or_recover(&aira_state).set_last_turn_audio_duration(spoken as f32 / rate as f32);
+        }
+
+        // Leave the slot alone if a newer stream already replaced it
+        let mut active = lock_or_recover(&ACTIVE_AUDIO);
+        if active
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, &stop))
+        {
+            *active = None;
+        }
     });
 
     // Convert ReceiverStream to a generic stream trait object
-    let stream: std::pin::Pin<
-        Box<dyn tokio_stream::Stream<Item = Result<Event, Infallible>> + Send>,
-    > = Box::pin(tokio_stream::wrappers::ReceiverStream::new(event_rx));
-    Sse::new(stream)
+    let stream: EventStream = Box::pin(tokio_stream::wrappers::ReceiverStream::new(event_rx));
+    sse_response(stream)
 }
 
 // Optimized WAV creation and base64 encoding in a single pass
-fn samples_to_base64_wav(samples: Vec<f32>) -> anyhow::Result<String> {
+fn samples_to_base64_wav(
+    samples: Vec<f32>,
+    sample_rate: u32,
+    channels: u16,
+) -> anyhow::Result<String> {
     use base64::{Engine as _, engine::general_purpose};
     use hound::{SampleFormat, WavSpec, WavWriter};
     use std::io::Cursor;
 
+    let samples = upmix(samples, channels);
     let spec = WavSpec {
-        channels: 1,
-        sample_rate: 22050,
+        channels,
+        sample_rate,
         bits_per_sample: 16,
         sample_format: SampleFormat::Int,
     };
@@ -262,3 +1073,670 @@ fn samples_to_base64_wav(samples: Vec<f32>) -> anyhow::Result<String> {
     writer.finalize()?;
     Ok(general_purpose::STANDARD.encode(cursor.into_inner()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::timeout;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn test_idle_stream_sends_keep_alive() {
+        let idle: EventStream = Box::pin(tokio_stream::pending());
+        let response = with_keep_alive(idle, Duration::from_millis(20)).into_response();
+
+        let mut body = response.into_body().into_data_stream();
+        let frame = timeout(Duration::from_secs(2), body.next())
+            .await
+            .expect("no keep-alive before the timeout")
+            .unwrap()
+            .unwrap();
+
+        // A bare comment line, not an event the client would treat as a token
+        assert!(frame.starts_with(b":"), "unexpected frame {:?}", frame);
+    }
+
+    #[tokio::test]
+    async fn test_stop_halts_audio_but_not_tokens() {
+        let (event_tx, mut event_rx) = mpsc::channel(64);
+        let (tts_tx, tts_rx) = mpsc::channel(32);
+        let stop = Arc::new(AtomicBool::new(false));
+        let synth = Some(|text: &str, emit: &mut dyn FnMut(String)| {
+            emit(format!("wav:{}", text));
+            Ok(())
+        });
+
+        let worker = tokio::spawn(run_tts_worker(
+            tts_rx,
+            synth,
+            false,
+            1,
+            event_tx.clone(),
+            stop.clone(),
+            Arc::new(Notify::new()),
+        ));
+
+        // Events as the generation loop would send them: a token, then its chunk
+        async fn emit(
+            event_tx: &mpsc::Sender<Result<Event, Infallible>>,
+            tts_tx: &mpsc::Sender<(u64, String)>,
+            id: u64,
+            text: &str,
+        ) {
+            event_tx
+                .send(Ok(Event::default().id(id.to_string()).data(text)))
+                .await
+                .unwrap();
+            tts_tx.send((id, text.to_string())).await.unwrap();
+        }
+
+        emit(&event_tx, &tts_tx, 0, "First sentence.").await;
+
+        // Wait for the first chunk to be spoken before stopping
+        let mut before_stop = Vec::new();
+        while before_stop.len() < 2 {
+            before_stop.push(format!("{:?}", event_rx.recv().await.unwrap().unwrap()));
+        }
+        assert!(before_stop.iter().any(|e| e.contains("audio_complete")));
+
+        stop.store(true, Ordering::SeqCst);
+        emit(&event_tx, &tts_tx, 1, "Second sentence.").await;
+        emit(&event_tx, &tts_tx, 2, "Third sentence.").await;
+
+        drop(tts_tx);
+        drop(event_tx);
+        worker.await.unwrap();
+
+        let mut after_stop = Vec::new();
+        while let Some(event) = event_rx.recv().await {
+            after_stop.push(format!("{:?}", event.unwrap()));
+        }
+
+        // Tokens keep flowing while no further audio is sent
+        assert!(after_stop.iter().any(|e| e.contains("Second sentence.")));
+        assert!(after_stop.iter().any(|e| e.contains("Third sentence.")));
+        assert!(!after_stop.iter().any(|e| e.contains("audio_complete")));
+        assert!(after_stop.iter().any(|e| e.contains("audio_stopped")));
+    }
+
+    #[tokio::test]
+    async fn test_silent_reply_sends_no_audio_event_instead_of_empty_wav() {
+        let (event_tx, mut event_rx) = mpsc::channel(64);
+        let (tts_tx, tts_rx) = mpsc::channel(32);
+        // Stands in for a voice that produced no samples
+        let synth = Some(|_: &str, _: &mut dyn FnMut(String)| Ok(()));
+
+        let worker = tokio::spawn(run_tts_worker(
+            tts_rx,
+            synth,
+            false,
+            1,
+            event_tx,
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(Notify::new()),
+        ));
+        tts_tx.send((0, "...".to_string())).await.unwrap();
+        drop(tts_tx);
+        worker.await.unwrap();
+
+        let mut events = Vec::new();
+        while let Some(event) = event_rx.recv().await {
+            events.push(format!("{:?}", event.unwrap()));
+        }
+        assert_eq!(events.len(), 1);
+        assert!(events[0].contains("no_audio"));
+        assert!(!events[0].contains("audio_complete"));
+    }
+
+    #[tokio::test]
+    async fn test_filler_stops_at_first_audio_complete() {
+        let (event_tx, mut event_rx) = mpsc::channel(64);
+        let (tts_tx, tts_rx) = mpsc::channel(32);
+        let stop = Arc::new(AtomicBool::new(false));
+        let first_audio = Arc::new(Notify::new());
+        // Slow synthesis, so the filler repeats while the first sentence is prepared
+        let synth = Some(|text: &str, emit: &mut dyn FnMut(String)| {
+            std::thread::sleep(Duration::from_millis(200));
+            emit(format!("wav:{}", text));
+            Ok(())
+        });
+
+        let filler = ThinkingFiller {
+            wav_base64: "hmm".to_string(),
+            interval: Duration::from_millis(30),
+        };
+        let filler = tokio::spawn(run_filler(
+            filler,
+            first_audio.clone(),
+            stop.clone(),
+            event_tx.clone(),
+        ));
+        let worker = tokio::spawn(run_tts_worker(
+            tts_rx,
+            synth,
+            false,
+            1,
+            event_tx,
+            stop,
+            first_audio,
+        ));
+        tts_tx.send((0, "First.".to_string())).await.unwrap();
+        tts_tx.send((1, "Second.".to_string())).await.unwrap();
+        drop(tts_tx);
+        worker.await.unwrap();
+        filler.await.unwrap();
+
+        let mut events = Vec::new();
+        while let Some(event) = event_rx.recv().await {
+            events.push(format!("{:?}", event.unwrap()));
+        }
+        let first_audio = events
+            .iter()
+            .position(|e| e.contains("audio_complete"))
+            .unwrap();
+        assert!(
+            first_audio > 1,
+            "expected repeated filler, got {:?}",
+            events
+        );
+        assert!(events[..first_audio].iter().all(|e| e.contains("hmm")));
+        assert!(!events[first_audio..].iter().any(|e| e.contains("hmm")));
+    }
+
+    #[tokio::test]
+    async fn test_tts_failure_reports_tts_stage_while_text_streams() {
+        let (event_tx, mut event_rx) = mpsc::channel(64);
+        let (tts_tx, tts_rx) = mpsc::channel(32);
+        let synth = Some(|_: &str, _: &mut dyn FnMut(String)| {
+            Err(anyhow::anyhow!(
+                "onnx session at /models/voice.onnx crashed"
+            ))
+        });
+
+        let worker = tokio::spawn(run_tts_worker(
+            tts_rx,
+            synth,
+            false,
+            1,
+            event_tx.clone(),
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(Notify::new()),
+        ));
+        event_tx
+            .send(Ok(Event::default().id("0").data("Hello there.")))
+            .await
+            .unwrap();
+        tts_tx.send((0, "Hello there.".to_string())).await.unwrap();
+        drop(tts_tx);
+        drop(event_tx);
+        worker.await.unwrap();
+
+        let mut events = Vec::new();
+        while let Some(event) = event_rx.recv().await {
+            events.push(format!("{:?}", event.unwrap()));
+        }
+        assert!(events[0].contains("Hello there."));
+        let error = events
+            .iter()
+            .find(|e| e.contains("event: error"))
+            .expect("no error event");
+        assert!(error.contains(r#"\"stage\":\"tts\""#), "{}", error);
+        // The underlying failure stays in the server log
+        assert!(!error.contains("onnx"));
+    }
+
+    #[tokio::test]
+    async fn test_streamed_audio_sends_parts_before_end() {
+        let (event_tx, mut event_rx) = mpsc::channel(64);
+        let (tts_tx, tts_rx) = mpsc::channel(32);
+        // A long sentence that Piper voices in three pieces
+        let synth = Some(|_: &str, emit: &mut dyn FnMut(String)| {
+            for part in ["part-a", "part-b", "part-c"] {
+                emit(part.to_string());
+            }
+            Ok(())
+        });
+
+        let worker = tokio::spawn(run_tts_worker(
+            tts_rx,
+            synth,
+            true,
+            1,
+            event_tx,
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(Notify::new()),
+        ));
+        let sentence = "This sentence goes on, and on, and on for quite a while before it ends.";
+        tts_tx.send((0, sentence.to_string())).await.unwrap();
+        drop(tts_tx);
+        worker.await.unwrap();
+
+        let mut events = Vec::new();
+        while let Some(event) = event_rx.recv().await {
+            events.push(format!("{:?}", event.unwrap()));
+        }
+        assert_eq!(events.len(), 4, "{:?}", events);
+        for (event, part) in events.iter().zip(["part-a", "part-b", "part-c"]) {
+            assert!(event.contains("event: audio_chunk"));
+            assert!(event.contains(part));
+            assert!(event.contains(r#"\"end\":false"#));
+        }
+        assert!(events[3].contains("event: audio_chunk"));
+        assert!(events[3].contains(r#"\"end\":true"#));
+        assert!(!events.iter().any(|e| e.contains("audio_complete")));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_synthesis_keeps_audio_in_order() {
+        use std::sync::atomic::AtomicUsize;
+
+        let (event_tx, mut event_rx) = mpsc::channel(64);
+        let (tts_tx, tts_rx) = mpsc::channel(32);
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        // The first sentence takes longest, so it would finish last if emitted as ready
+        let synth = {
+            let (active, peak) = (active.clone(), peak.clone());
+            Some(move |text: &str, emit: &mut dyn FnMut(String)| {
+                let running = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(running, Ordering::SeqCst);
+                let delay = if text == "First." { 300 } else { 50 };
+                std::thread::sleep(Duration::from_millis(delay));
+                active.fetch_sub(1, Ordering::SeqCst);
+                emit(format!("wav:{}", text));
+                Ok(())
+            })
+        };
+
+        let worker = tokio::spawn(run_tts_worker(
+            tts_rx,
+            synth,
+            false,
+            2,
+            event_tx,
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(Notify::new()),
+        ));
+        tts_tx.send((0, "First.".to_string())).await.unwrap();
+        tts_tx.send((1, "Second.".to_string())).await.unwrap();
+        drop(tts_tx);
+        worker.await.unwrap();
+
+        let mut events = Vec::new();
+        while let Some(event) = event_rx.recv().await {
+            events.push(format!("{:?}", event.unwrap()));
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 2, "syntheses never overlapped");
+        assert_eq!(events.len(), 2, "{:?}", events);
+        assert!(events[0].contains("wav:First."), "{:?}", events);
+        assert!(events[1].contains("wav:Second."), "{:?}", events);
+    }
+
+    #[test]
+    fn test_empty_reply_queues_no_speech() {
+        let mut chunker = SentenceChunker::new();
+        let (_, chunk) = chunker.push("   ");
+        assert!(chunk.is_none());
+        assert!(chunker.finish().is_empty());
+    }
+
+    #[test]
+    fn test_repeated_punctuation_collapses_across_tokens() {
+        let mut runs = PunctuationRuns::new(Some(1));
+        let cleaned: String = ["Rea", "lly??", "???", " Yes", "!!!"]
+            .iter()
+            .map(|token| runs.push(token))
+            .collect();
+        assert_eq!(cleaned, "Really? Yes!");
+
+        // Ellipses survive the default limit, but longer runs of dots don't
+        let mut runs = PunctuationRuns::new(Some(1));
+        assert_eq!(runs.push("Well... okay."), "Well... okay.");
+        assert_eq!(runs.push(" Hmm......"), " Hmm...");
+
+        // A limit of two keeps "!!"; off leaves the text alone
+        let mut runs = PunctuationRuns::new(Some(2));
+        assert_eq!(runs.push("Wow!!!"), "Wow!!");
+        let mut runs = PunctuationRuns::new(None);
+        assert_eq!(runs.push("Wow!!!"), "Wow!!!");
+    }
+
+    fn batch(granularity: StreamGranularity, tokens: &[(u64, &str)]) -> Vec<(u64, String)> {
+        let mut batcher = TokenBatcher::new(granularity);
+        let mut events: Vec<_> = tokens
+            .iter()
+            .flat_map(|(id, token)| batcher.push(*id, token))
+            .collect();
+        events.extend(batcher.finish());
+        events
+    }
+
+    #[test]
+    fn test_word_granularity_emits_at_whitespace() {
+        let tokens = [
+            (0, "He"),
+            (0, "llo"),
+            (0, " wor"),
+            (0, "ld, "),
+            (0, "frie"),
+            (0, "nd"),
+        ];
+        let events = batch(StreamGranularity::Word, &tokens);
+
+        let texts: Vec<&str> = events.iter().map(|(_, t)| t.as_str()).collect();
+        assert_eq!(texts, ["Hello ", "world, ", "friend"]);
+    }
+
+    #[test]
+    fn test_sentence_granularity_emits_at_punctuation() {
+        let tokens = [
+            (0, "Hi"),
+            (0, " there"),
+            (0, "! How"),
+            (0, " are"),
+            (1, " you?"),
+        ];
+        let events = batch(StreamGranularity::Sentence, &tokens);
+
+        // A batch is cut short rather than spanning two chunks
+        let expected = [(0, "Hi there!"), (0, " How are"), (1, " you?")];
+        let expected: Vec<(u64, String)> =
+            expected.iter().map(|(i, t)| (*i, t.to_string())).collect();
+        assert_eq!(events, expected);
+    }
+
+    #[test]
+    fn test_token_granularity_passes_tokens_through() {
+        let tokens = [(0, "Hi"), (0, ""), (0, " there")];
+        assert_eq!(batch(StreamGranularity::Token, &tokens).len(), 3);
+        assert_eq!(StreamGranularity::default(), StreamGranularity::Token);
+    }
+
+    #[test]
+    fn test_audio_chunk_id_matches_its_sentence_tokens() {
+        let tokens = [
+            "The weather today is",
+            " sunny with a light breeze.",
+            " Tomorrow looks",
+            " rainy, so bring an umbrella.",
+        ];
+
+        let mut chunker = SentenceChunker::new();
+        let mut tagged = Vec::new();
+        let mut chunks = Vec::new();
+        for token in tokens {
+            let (id, chunk) = chunker.push(token);
+            tagged.push((id, token));
+            chunks.extend(chunk);
+        }
+        chunks.extend(chunker.finish());
+
+        assert_eq!(chunks.len(), 2);
+        for (id, text) in &chunks {
+            let sentence: String = tagged
+                .iter()
+                .filter(|(token_id, _)| token_id == id)
+                .map(|(_, token)| *token)
+                .collect();
+            assert_eq!(&sentence, text);
+        }
+        assert_eq!(chunks[0].0 + 1, chunks[1].0);
+    }
+
+    // Tokens generated before the first chunk is handed to TTS, i.e. before the first audio_complete
+    fn tokens_before_first_audio(mut chunker: SentenceChunker, tokens: &[&str]) -> usize {
+        tokens
+            .iter()
+            .position(|token| chunker.push(token).1.is_some())
+            .map_or(tokens.len(), |i| i + 1)
+    }
+
+    #[test]
+    fn test_fast_start_speaks_the_first_sentence_sooner() {
+        let tokens = [
+            "Sure",
+            " thing.",
+            " Here",
+            " is",
+            " the",
+            " longer",
+            " explanation",
+            " you",
+            " asked",
+            " for.",
+        ];
+
+        let normal = tokens_before_first_audio(SentenceChunker::new(), &tokens);
+        let fast = tokens_before_first_audio(SentenceChunker::eager_first(), &tokens);
+        assert_eq!(fast, 2);
+        assert!(fast < normal, "fast {} vs normal {}", fast, normal);
+
+        // Only the first sentence skips the wait; later ones are batched as usual
+        let mut chunker = SentenceChunker::eager_first();
+        let chunks: Vec<_> = tokens
+            .iter()
+            .filter_map(|token| chunker.push(token).1)
+            .collect();
+        assert_eq!(chunks, vec![(0, "Sure thing.".to_string())]);
+    }
+
+    struct ScriptedBackend(Vec<&'static str>);
+
+    impl aira_brain::llm::CompletionBackend for ScriptedBackend {
+        fn complete(
+            &mut self,
+            _prompt: &str,
+            _params: &aira_brain::llm::SamplingParams,
+            on_piece: &mut dyn FnMut(&str) -> bool,
+        ) -> anyhow::Result<()> {
+            for piece in &self.0 {
+                if !on_piece(piece) {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    // Holds its reply until the test drops the gate's sender, reporting when it has started
+    struct GatedBackend {
+        started: mpsc::UnboundedSender<()>,
+        gate: std::sync::mpsc::Receiver<()>,
+    }
+
+    impl aira_brain::llm::CompletionBackend for GatedBackend {
+        fn complete(
+            &mut self,
+            _prompt: &str,
+            _params: &aira_brain::llm::SamplingParams,
+            on_piece: &mut dyn FnMut(&str) -> bool,
+        ) -> anyhow::Result<()> {
+            let _ = self.started.send(());
+            let _ = self.gate.recv();
+            on_piece("Hello there.");
+            Ok(())
+        }
+    }
+
+    // ACTIVE_AUDIO is shared, so tests that stream a chat take turns
+    static STREAM_TESTS: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    fn gated_aira() -> (
+        SharedAira,
+        mpsc::UnboundedReceiver<()>,
+        std::sync::mpsc::Sender<()>,
+    ) {
+        let (started_tx, started_rx) = mpsc::unbounded_channel();
+        let (gate_tx, gate_rx) = std::sync::mpsc::channel();
+        let backend = GatedBackend {
+            started: started_tx,
+            gate: gate_rx,
+        };
+        let llm = aira_brain::llm::LlmEngine::with_backend(Box::new(backend), "");
+        let aira = Arc::new(Mutex::new(Aira::builder(llm).build()));
+        (aira, started_rx, gate_tx)
+    }
+
+    async fn chat_body(state: (SharedAira, &'static Semaphore)) -> String {
+        let request: ChatRequest =
+            serde_json::from_value(serde_json::json!({ "message": "Hi" })).unwrap();
+        let response = chat(State(state), Json(request)).await.into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stop_audio_silences_the_stream_that_is_speaking() {
+        static SEMAPHORE: Semaphore = Semaphore::const_new(1);
+        let _turn = STREAM_TESTS.lock().await;
+        let (aira, mut started, gate) = gated_aira();
+
+        let speaking = tokio::spawn(chat_body((aira.clone(), &SEMAPHORE)));
+        timeout(Duration::from_secs(5), started.recv())
+            .await
+            .expect("first reply never started");
+
+        // The second chat waits for the first to finish rather than taking over its audio
+        let queued = tokio::spawn(chat_body((aira.clone(), &SEMAPHORE)));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!queued.is_finished());
+
+        let Json(response) = stop_audio(State((aira, &SEMAPHORE))).await;
+        assert!(response.stopped);
+        drop(gate);
+
+        let speaking = timeout(Duration::from_secs(10), speaking)
+            .await
+            .unwrap()
+            .unwrap();
+        let queued = timeout(Duration::from_secs(10), queued)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(speaking.contains("Hello there."));
+        assert!(speaking.contains("audio_stopped"), "{}", speaking);
+        assert!(queued.contains("Hello there."));
+        assert!(!queued.contains("audio_stopped"), "{}", queued);
+    }
+
+    #[tokio::test]
+    async fn test_clear_waits_for_the_streaming_reply() {
+        static SEMAPHORE: Semaphore = Semaphore::const_new(1);
+        let _turn = STREAM_TESTS.lock().await;
+        let (aira, mut started, gate) = gated_aira();
+
+        let reply = tokio::spawn(chat_body((aira.clone(), &SEMAPHORE)));
+        timeout(Duration::from_secs(5), started.recv())
+            .await
+            .expect("reply never started");
+
+        let clear = tokio::spawn(crate::api::session::clear_session(State((
+            aira.clone(),
+            &SEMAPHORE,
+        ))));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!clear.is_finished());
+
+        drop(gate);
+        let reply = timeout(Duration::from_secs(10), reply)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(reply.contains("Hello there."));
+        let cleared = timeout(Duration::from_secs(10), clear)
+            .await
+            .expect("clear never ran")
+            .unwrap()
+            .into_response();
+        assert_eq!(cleared.status(), axum::http::StatusCode::OK);
+
+        // The finished reply was cleared along with the rest, not added after the reset
+        assert_eq!(lock_or_recover(&aira).get_conversation_stats().0, 0);
+    }
+
+    #[test]
+    fn test_reload_failure_reports_the_stage_that_failed() {
+        use aira_brain::aira::ModelLoaders;
+        use aira_brain::llm::{CompletionBackend, LlmEngine};
+        use aira_brain::stt::SttEngine;
+
+        let loaders = ModelLoaders {
+            llm: Box::new(|| Ok(Box::new(ScriptedBackend(vec![])) as Box<dyn CompletionBackend>)),
+            stt: Some(Box::new(|| -> anyhow::Result<SttEngine> {
+                anyhow::bail!("ggml-small.en.bin not found")
+            })),
+            tts: None,
+        };
+        let llm = LlmEngine::with_backend(Box::new(ScriptedBackend(vec![])), "");
+        let mut aira = Aira::builder(llm).loaders(loaders).build();
+        aira.unload().unwrap();
+
+        let err = aira.reload().unwrap_err();
+        assert_eq!(reload_failure_stage(&err), Stage::Stt);
+        // A failure that doesn't name a model is blamed on the LLM
+        let unknown = anyhow::anyhow!("out of memory");
+        assert_eq!(reload_failure_stage(&unknown), Stage::Llm);
+    }
+
+    #[tokio::test]
+    async fn test_client_round_trips_a_chat_stream() {
+        use aira_client::{AiraClient, ChatEvent, FinishReason};
+
+        static SEMAPHORE: Semaphore = Semaphore::const_new(1);
+        let _turn = STREAM_TESTS.lock().await;
+        let llm = aira_brain::llm::LlmEngine::with_backend(
+            Box::new(ScriptedBackend(vec!["Hello", " there!"])),
+            "",
+        );
+        let aira: SharedAira = Arc::new(Mutex::new(Aira::builder(llm).build()));
+        let app = axum::Router::new()
+            .route("/chat", axum::routing::post(chat))
+            .with_state((aira, &SEMAPHORE));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = AiraClient::new(format!("http://{}", addr));
+        let mut events = Vec::new();
+        let request = aira_client::ChatRequest::new("Hi");
+        timeout(
+            Duration::from_secs(10),
+            client.chat_stream(&request, |event| events.push(event)),
+        )
+        .await
+        .expect("chat stream never closed")
+        .unwrap();
+
+        let text: String = events
+            .iter()
+            .filter_map(|event| match event {
+                ChatEvent::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, "Hello there!");
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ChatEvent::Done(done) if done.finish_reason == FinishReason::Stop
+        )));
+    }
+
+    #[test]
+    fn test_sentence_audio_includes_the_configured_pause() {
+        use base64::{Engine as _, engine::general_purpose};
+
+        let rate = 22_050;
+        let mut samples = vec![0.5; rate as usize / 2];
+        append_pause(&mut samples, rate, Duration::from_millis(300));
+
+        let wav = general_purpose::STANDARD
+            .decode(samples_to_base64_wav(samples, rate, 1).unwrap())
+            .unwrap();
+        let reader = hound::WavReader::new(std::io::Cursor::new(wav)).unwrap();
+        let seconds = reader.duration() as f64 / rate as f64;
+        assert!((seconds - 0.8).abs() < 1e-3, "lasted {}s", seconds);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::models::PromptDebugRequest;
+use crate::states::{SharedAira, lock_or_recover};
+use aira_brain::llm::{GenerationConfig, ResponseLength};
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+#[derive(Serialize)]
+pub struct PromptDebugResponse {
+    // Prompt a chat request with this message would send right now
+    pub prompt: String,
+    // Prompt sent by the previous reply, if one was captured
+    pub last_prompt: Option<String>,
+}
+
+// Show the fully rendered prompt for a message, for debugging chat templates
+// Only served when the server runs with AIRA_DEBUG_PROMPTS=1
+pub async fn debug_prompt(
+    State((aira_state, _semaphore)): State<(SharedAira, &'static Semaphore)>,
+    Json(req): Json<PromptDebugRequest>,
+) -> impl IntoResponse {
+    let config = GenerationConfig {
+        length: req
+            .length
+            .or_else(|| ResponseLength::detect(&req.message))
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+
+    let mut guard = lock_or_recover(&aira_state);
+    if !guard.debug_prompts_enabled() {
+        return (StatusCode::NOT_FOUND, "Prompt debugging is disabled").into_response();
+    }
+
+    Json(PromptDebugResponse {
+        prompt: guard.render_prompt(&req.message, &config),
+        last_prompt: guard.last_prompt().map(str::to_string),
+    })
+    .into_response()
+}
@@ -0,0 +1,23 @@
+use crate::models::EstimateRequest;
+use crate::states::{SharedAira, lock_or_recover};
+use aira_brain::llm::{GenerationConfig, ResponseLength, TokenEstimate};
+use axum::{Json, extract::State};
+use tokio::sync::Semaphore;
+
+// Estimate prompt/response tokens for a message against the current session
+// Nothing is generated and the history is left untouched
+pub async fn estimate(
+    State((aira_state, _semaphore)): State<(SharedAira, &'static Semaphore)>,
+    Json(req): Json<EstimateRequest>,
+) -> Json<TokenEstimate> {
+    let config = GenerationConfig {
+        length: req
+            .length
+            .or_else(|| ResponseLength::detect(&req.message))
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+
+    let guard = lock_or_recover(&aira_state);
+    Json(guard.estimate(&req.message, &config))
+}
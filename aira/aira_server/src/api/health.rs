@@ -0,0 +1,276 @@
+use crate::models::HealthQuery;
+use crate::states::{SharedAira, ensure_loaded, lock_or_recover, run_blocking};
+use aira_brain::audio::WHISPER_SAMPLE_RATE;
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::time::timeout;
+
+// Longest each stage of the deep check may take before it counts as failed
+const STAGE_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Spoken and transcribed by the deep check; short so the check stays cheap
+const PROBE_TEXT: &str = "Hello.";
+
+// Shortest gap between deep checks; callers inside it get the last report again,
+// so an open endpoint can't be used to keep the models busy
+const DEEP_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+// The last deep check's report and when it finished
+static LAST_DEEP_CHECK: Mutex<Option<(Instant, DeepHealthResponse)>> = Mutex::new(None);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    // Not configured, or an earlier stage failed so there was nothing to check
+    Skipped,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StageCheck {
+    pub status: CheckStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl StageCheck {
+    fn passed() -> Self {
+        Self {
+            status: CheckStatus::Pass,
+            error: None,
+        }
+    }
+
+    fn skipped() -> Self {
+        Self {
+            status: CheckStatus::Skipped,
+            error: None,
+        }
+    }
+
+    fn failed(error: impl Into<String>) -> Self {
+        Self {
+            status: CheckStatus::Fail,
+            error: Some(error.into()),
+        }
+    }
+}
+
+// Result of running a tiny synthesis and transcription through the real engines
+#[derive(Serialize, Debug, Clone)]
+pub struct DeepHealthResponse {
+    // False when any configured stage failed
+    pub healthy: bool,
+    pub tts: StageCheck,
+    pub stt: StageCheck,
+}
+
+// Liveness by default; `?deep=true` also proves speech can be synthesized and transcribed
+// Deep checks take a chat permit like any other inference, and run at most once per
+// DEEP_CHECK_INTERVAL
+pub async fn health(
+    State((aira_state, semaphore)): State<(SharedAira, &'static Semaphore)>,
+    Query(query): Query<HealthQuery>,
+) -> Response {
+    if !query.deep {
+        return "OK".into_response();
+    }
+
+    if let Some(report) = recent_report(Instant::now()) {
+        return deep_response(report);
+    }
+    let _permit = match timeout(STAGE_TIMEOUT, semaphore.acquire()).await {
+        Ok(Ok(permit)) => permit,
+        _ => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Server is busy, please try again",
+            )
+                .into_response();
+        }
+    };
+
+    // Unloaded models come back for the check, as they would for a real request
+    if let Err(e) = ensure_loaded(&aira_state).await {
+        let report = DeepHealthResponse {
+            healthy: false,
+            tts: StageCheck::failed(e.to_string()),
+            stt: StageCheck::failed(e.to_string()),
+        };
+        return deep_response(remember(report));
+    }
+
+    let (tts, stt) = {
+        let guard = lock_or_recover(&aira_state);
+        (guard.get_tts(), guard.get_stt())
+    };
+    let synthesize = tts.map(|tts| move || tts.synthesize_at(PROBE_TEXT, WHISPER_SAMPLE_RATE));
+    let transcribe = stt.map(|stt| move |audio: Vec<f32>| lock_or_recover(&stt).transcribe(&audio));
+
+    let report = deep_check(synthesize, transcribe, STAGE_TIMEOUT).await;
+    deep_response(remember(report))
+}
+
+fn deep_response(report: DeepHealthResponse) -> Response {
+    let status = if report.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report)).into_response()
+}
+
+// The last report, if it is younger than DEEP_CHECK_INTERVAL at `now`
+fn recent_report(now: Instant) -> Option<DeepHealthResponse> {
+    lock_or_recover(&LAST_DEEP_CHECK)
+        .as_ref()
+        .filter(|(at, _)| now.saturating_duration_since(*at) < DEEP_CHECK_INTERVAL)
+        .map(|(_, report)| report.clone())
+}
+
+fn remember(report: DeepHealthResponse) -> DeepHealthResponse {
+    *lock_or_recover(&LAST_DEEP_CHECK) = Some((Instant::now(), report.clone()));
+    report
+}
+
+// Lowercase words without punctuation, so "Hello." and "hello" match
+fn normalize_transcript(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Synthesize the probe, then transcribe what was spoken; None skips a stage
+async fn deep_check<S, T>(
+    synthesize: Option<S>,
+    transcribe: Option<T>,
+    limit: Duration,
+) -> DeepHealthResponse
+where
+    S: FnOnce() -> anyhow::Result<Vec<f32>> + Send + 'static,
+    T: FnOnce(Vec<f32>) -> anyhow::Result<String> + Send + 'static,
+{
+    let (tts, audio) = match synthesize {
+        Some(synthesize) => match run_stage(synthesize, limit).await {
+            Ok(audio) if audio.is_empty() => {
+                (StageCheck::failed("Synthesis produced no audio"), None)
+            }
+            Ok(audio) => (StageCheck::passed(), Some(audio)),
+            Err(check) => (check, None),
+        },
+        None => (StageCheck::skipped(), None),
+    };
+
+    // Without probe audio there is nothing meaningful to transcribe
+    let stt = match (transcribe, audio) {
+        (Some(transcribe), Some(audio)) => {
+            match run_stage(move || transcribe(audio), limit).await {
+                Ok(heard) if normalize_transcript(&heard) == normalize_transcript(PROBE_TEXT) => {
+                    StageCheck::passed()
+                }
+                Ok(heard) => StageCheck::failed(format!(
+                    "Heard {:?} instead of {:?}",
+                    heard.trim(),
+                    PROBE_TEXT
+                )),
+                Err(check) => check,
+            }
+        }
+        _ => StageCheck::skipped(),
+    };
+
+    DeepHealthResponse {
+        healthy: tts.status != CheckStatus::Fail && stt.status != CheckStatus::Fail,
+        tts,
+        stt,
+    }
+}
+
+// Run one stage on the blocking pool, failing it if it errors or outlasts `limit`
+async fn run_stage<F, T>(work: F, limit: Duration) -> Result<T, StageCheck>
+where
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    match timeout(limit, run_blocking(work)).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => Err(StageCheck::failed(e.to_string())),
+        Err(_) => Err(StageCheck::failed(format!(
+            "Timed out after {}s",
+            limit.as_secs_f32()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn speaks() -> Option<impl FnOnce() -> anyhow::Result<Vec<f32>> + Send + 'static> {
+        Some(|| Ok(vec![0.1; 1600]))
+    }
+
+    fn hears() -> Option<impl FnOnce(Vec<f32>) -> anyhow::Result<String> + Send + 'static> {
+        Some(|_audio: Vec<f32>| Ok("Hello.".to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_tts_failure_fails_the_deep_check() {
+        let broken_voice =
+            Some(|| -> anyhow::Result<Vec<f32>> { anyhow::bail!("espeak-ng data not found") });
+        let report = deep_check(broken_voice, hears(), STAGE_TIMEOUT).await;
+
+        assert!(!report.healthy);
+        assert_eq!(report.tts.status, CheckStatus::Fail);
+        assert_eq!(
+            report.tts.error.as_deref(),
+            Some("espeak-ng data not found")
+        );
+        // Nothing was synthesized, so transcription wasn't attempted
+        assert_eq!(report.stt.status, CheckStatus::Skipped);
+
+        let report = deep_check(speaks(), hears(), STAGE_TIMEOUT).await;
+        assert!(report.healthy);
+        assert_eq!(report.tts.status, CheckStatus::Pass);
+        assert_eq!(report.stt.status, CheckStatus::Pass);
+    }
+
+    #[tokio::test]
+    async fn test_transcript_must_match_the_probe() {
+        let shouting = Some(|_audio: Vec<f32>| Ok(" HELLO ".to_string()));
+        let report = deep_check(speaks(), shouting, STAGE_TIMEOUT).await;
+        assert_eq!(report.stt.status, CheckStatus::Pass);
+
+        let mishears = Some(|_audio: Vec<f32>| Ok("Yellow!".to_string()));
+        let report = deep_check(speaks(), mishears, STAGE_TIMEOUT).await;
+        assert!(!report.healthy);
+        assert_eq!(report.stt.status, CheckStatus::Fail);
+        assert!(report.stt.error.unwrap().contains("Yellow!"));
+    }
+
+    #[tokio::test]
+    async fn test_hung_stage_times_out() {
+        let slow_voice = Some(|| {
+            std::thread::sleep(Duration::from_millis(200));
+            Ok(vec![0.1; 1600])
+        });
+        let report = deep_check(slow_voice, hears(), Duration::from_millis(20)).await;
+
+        assert!(!report.healthy);
+        assert_eq!(report.tts.status, CheckStatus::Fail);
+        assert!(report.tts.error.unwrap().starts_with("Timed out"));
+    }
+}
@@ -1,4 +1,5 @@
-use crate::states::SharedAira;
+use crate::states::{SharedAira, lock_or_recover};
+use aira_brain::audio::{apply_gain, upmix};
 use axum::extract::State;
 use axum::Json;
 use serde::{Deserialize, Serialize};
@@ -7,17 +8,29 @@ use tokio::sync::Semaphore;
 
 pub mod camera;
 pub mod chat;
+pub mod config;
+pub mod debug;
+pub mod estimate;
+pub mod health;
+pub mod session;
 pub mod stt;
 pub mod tts;
 
-pub use camera::{get_camera_status, get_emotion_details, process_camera_features};
-pub use chat::chat;
-pub use stt::transcribe_audio;
-pub use tts::tts;
-
-pub async fn health(_state: State<(SharedAira, &'static Semaphore)>) -> &'static str {
-    "OK"
-}
+pub use camera::{
+    get_camera_status, get_emotion_alpha, get_emotion_details, process_camera_features,
+    set_emotion_alpha, set_emotion_enabled,
+};
+pub use chat::{chat, continue_reply, regenerate, stop_audio};
+pub use config::get_config;
+pub use debug::debug_prompt;
+pub use estimate::estimate;
+pub use health::health;
+pub use session::{
+    clear_session, export_session, greet_session, reload_models, set_mode, summarize_session,
+    unload_models,
+};
+pub use stt::{transcribe_audio, transcribe_audio_stream};
+pub use tts::{set_speed, tts, tts_captioned};
 
 #[derive(Deserialize, Default)]
 pub struct TestStressRequest {
@@ -43,7 +56,7 @@ pub struct AlertResponse {
 
 /// Get pending alert - frontend polls this
 pub async fn get_alert(_state: State<(SharedAira, &'static Semaphore)>) -> Json<AlertResponse> {
-    let mut alert = PENDING_ALERT.lock().unwrap();
+    let mut alert = lock_or_recover(&PENDING_ALERT);
     let alert_data = alert.clone();
     *alert = None; // Clear after reading
     
@@ -101,19 +114,27 @@ pub async fn test_stress(
     
     // Synthesize speech
     let aira_for_tts = {
-        let guard = aira.lock().unwrap();
+        let guard = lock_or_recover(&aira);
         guard.get_tts()
     };
     
     let text = alert.to_string();
     let audio_base64: Option<String> = {
-        type TtsResult = anyhow::Result<Vec<f32>>;
-        let result: std::result::Result<TtsResult, tokio::task::JoinError> = 
-            tokio::task::spawn_blocking(move || aira_for_tts.synthesize(&text)).await;
+        type TtsResult = anyhow::Result<(Vec<f32>, u32)>;
+        let result: std::result::Result<TtsResult, tokio::task::JoinError> = match aira_for_tts {
+            Some(tts) => tokio::task::spawn_blocking(move || {
+                let mut samples = tts.synthesize(&text)?;
+                apply_gain(&mut samples, tts.volume());
+                Ok((samples, tts.sample_rate()))
+            })
+            .await,
+            None => Ok(Err(anyhow::anyhow!("Text-to-speech is not configured"))),
+        };
         
         match result {
-            Ok(Ok(samples)) => {
-                let wav_data = create_wav_sync(&samples);
+            // No samples would make an empty WAV, so send no audio instead
+            Ok(Ok((samples, sample_rate))) if !samples.is_empty() => {
+                let wav_data = create_wav_sync(&samples, sample_rate);
                 Some(base64_encode(&wav_data))
             }
             _ => None,
@@ -122,7 +143,7 @@ pub async fn test_stress(
     
     // Store alert for frontend polling (as backup)
     {
-        let mut pending = PENDING_ALERT.lock().unwrap();
+        let mut pending = lock_or_recover(&PENDING_ALERT);
         *pending = Some((alert.to_string(), audio_base64.clone()));
     }
     
@@ -134,10 +155,12 @@ pub async fn test_stress(
     })
 }
 
-fn create_wav_sync(samples: &[f32]) -> Vec<u8> {
+fn create_wav_sync(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let channels = tts::output_channels();
+    let samples = upmix(samples.to_vec(), channels);
     let spec = hound::WavSpec {
-        channels: 1,
-        sample_rate: 22050,
+        channels,
+        sample_rate,
         bits_per_sample: 16,
         sample_format: hound::SampleFormat::Int,
     };
@@ -0,0 +1,196 @@
+use crate::models::ModeRequest;
+use crate::states::{SharedAira, acquire_permit, ensure_loaded, lock_or_recover};
+use aira_brain::aira::TurnRecord;
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+#[derive(Serialize)]
+pub struct ClearSessionResponse {
+    pub cleared: bool,
+    pub history_length: usize,
+}
+
+#[derive(Serialize)]
+pub struct GreetingResponse {
+    // None without AIRA_GREETING, or once the conversation has started
+    pub greeting: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TranscriptResponse {
+    // Oldest first, with each turn's metrics, emotion and audio length
+    pub turns: Vec<TurnRecord>,
+}
+
+#[derive(Serialize)]
+pub struct SummarizeSessionResponse {
+    pub summarized: bool,
+    pub history_length: usize,
+    pub history_tokens: usize,
+}
+
+#[derive(Serialize)]
+pub struct ModeResponse {
+    pub mode: String,
+    pub modes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ModelsResponse {
+    pub loaded: bool,
+}
+
+// Hold the chat permit, so the session never changes under a reply that is still streaming
+async fn wait_for_chat(
+    semaphore: &'static Semaphore,
+) -> Result<SemaphorePermit<'static>, Response> {
+    acquire_permit(semaphore, |message| {
+        (StatusCode::SERVICE_UNAVAILABLE, message).into_response()
+    })
+    .await
+}
+
+// Reset conversation history and emotional context
+pub async fn clear_session(
+    State((aira_state, semaphore)): State<(SharedAira, &'static Semaphore)>,
+) -> impl IntoResponse {
+    let _permit = match wait_for_chat(semaphore).await {
+        Ok(permit) => permit,
+        Err(response) => return response,
+    };
+
+    let (history_length, _) = {
+        let mut guard = lock_or_recover(&aira_state);
+        guard.clear_conversation();
+        guard.get_conversation_stats()
+    };
+    super::stt::clear_decode_cache();
+
+    println!("🧹 Conversation cleared via API");
+
+    Json(ClearSessionResponse {
+        cleared: true,
+        history_length,
+    })
+    .into_response()
+}
+
+// Aira's opening line for a new conversation, to show or speak before the user says anything
+pub async fn greet_session(
+    State((aira_state, semaphore)): State<(SharedAira, &'static Semaphore)>,
+) -> impl IntoResponse {
+    let _permit = match wait_for_chat(semaphore).await {
+        Ok(permit) => permit,
+        Err(response) => return response,
+    };
+
+    let greeting = lock_or_recover(&aira_state).greet();
+    Json(GreetingResponse { greeting }).into_response()
+}
+
+// Export the current conversation's turns for analysis
+pub async fn export_session(
+    State((aira_state, semaphore)): State<(SharedAira, &'static Semaphore)>,
+) -> impl IntoResponse {
+    let _permit = match wait_for_chat(semaphore).await {
+        Ok(permit) => permit,
+        Err(response) => return response,
+    };
+
+    let turns = lock_or_recover(&aira_state).turns().to_vec();
+    Json(TranscriptResponse { turns }).into_response()
+}
+
+// Compress older turns into a model-written summary to free up context
+pub async fn summarize_session(
+    State((aira_state, semaphore)): State<(SharedAira, &'static Semaphore)>,
+) -> impl IntoResponse {
+    let _permit = match wait_for_chat(semaphore).await {
+        Ok(permit) => permit,
+        Err(response) => return response,
+    };
+
+    // Summarizing runs the model, so keep it off the async workers
+    let result = tokio::task::spawn_blocking(move || {
+        let mut guard = lock_or_recover(&aira_state);
+        let summarized = guard.summarize_history()?;
+        let (history_length, history_tokens) = guard.get_conversation_stats();
+        anyhow::Ok(SummarizeSessionResponse {
+            summarized,
+            history_length,
+            history_tokens,
+        })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(response)) => Json(response).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Summarization failed: {}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Summarization task failed: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+// Switch the system prompt mode; history is kept, so the conversation carries on
+pub async fn set_mode(
+    State((aira_state, semaphore)): State<(SharedAira, &'static Semaphore)>,
+    Json(req): Json<ModeRequest>,
+) -> impl IntoResponse {
+    let _permit = match wait_for_chat(semaphore).await {
+        Ok(permit) => permit,
+        Err(response) => return response,
+    };
+
+    let mut guard = lock_or_recover(&aira_state);
+    if let Err(e) = guard.set_mode(&req.mode) {
+        return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+    }
+
+    Json(ModeResponse {
+        mode: guard.mode().to_string(),
+        modes: guard.mode_names().into_iter().map(String::from).collect(),
+    })
+    .into_response()
+}
+
+// Drop the models to free memory while idle; the next request that needs them reloads them
+pub async fn unload_models(
+    State((aira_state, semaphore)): State<(SharedAira, &'static Semaphore)>,
+) -> impl IntoResponse {
+    let _permit = match wait_for_chat(semaphore).await {
+        Ok(permit) => permit,
+        Err(response) => return response,
+    };
+
+    let mut guard = lock_or_recover(&aira_state);
+    if let Err(e) = guard.unload() {
+        return (StatusCode::CONFLICT, e.to_string()).into_response();
+    }
+    Json(ModelsResponse {
+        loaded: guard.is_loaded(),
+    })
+    .into_response()
+}
+
+// Load the models now instead of on the next request
+pub async fn reload_models(
+    State((aira_state, _semaphore)): State<(SharedAira, &'static Semaphore)>,
+) -> impl IntoResponse {
+    match ensure_loaded(&aira_state).await {
+        Ok(()) => Json(ModelsResponse { loaded: true }).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
@@ -1,87 +1,560 @@
-use crate::states::SharedAira;
+use crate::states::{SharedAira, ensure_loaded, lock_or_recover, run_blocking};
+use aira_brain::aira::AiraError;
+use aira_brain::audio::{AudioBuffer, MAX_SAMPLE_RATE, MIN_SAMPLE_RATE};
+use aira_brain::stt::{SpeechSegment, SttEngine, TranscribedSegment, Transcription};
+use sha2::{Digest, Sha256};
 use axum::{
     extract::{multipart::Multipart, State},
     http::StatusCode,
-    response::IntoResponse,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     Json,
 };
 use serde::Serialize;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::future::Future;
 use std::io::Cursor;
 use std::process::Command;
-use tokio::sync::Semaphore;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::{mpsc, Semaphore};
+
+// Number of recent uploads whose decoded samples are kept
+const DECODE_CACHE_CAPACITY: usize = 8;
+
+// Decoded 16kHz samples keyed by a SHA-256 of the uploaded bytes
+// Lets clients retry transcription of the same clip without re-running FFmpeg
+struct DecodedAudioCache {
+    entries: VecDeque<([u8; 32], Arc<Vec<f32>>)>,
+    capacity: usize,
+}
+
+impl DecodedAudioCache {
+    const fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    fn get(&self, key: &[u8; 32]) -> Option<Arc<Vec<f32>>> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, samples)| samples.clone())
+    }
+
+    // Insert, evicting the oldest entry when full
+    fn insert(&mut self, key: [u8; 32], samples: Arc<Vec<f32>>) {
+        if self.capacity == 0 || self.get(&key).is_some() {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, samples));
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+static DECODE_CACHE: Mutex<DecodedAudioCache> =
+    Mutex::new(DecodedAudioCache::new(DECODE_CACHE_CAPACITY));
+
+// Forget cached audio, e.g. when the conversation is reset
+pub fn clear_decode_cache() {
+    lock_or_recover(&DECODE_CACHE).clear();
+}
+
+// Return cached samples for `audio_data`, or hand it to `decode` and cache the result
+async fn get_or_decode<F, Fut>(
+    cache: &Mutex<DecodedAudioCache>,
+    audio_data: Vec<u8>,
+    decode: F,
+) -> anyhow::Result<Arc<Vec<f32>>>
+where
+    F: FnOnce(Vec<u8>) -> Fut,
+    Fut: Future<Output = anyhow::Result<Vec<f32>>>,
+{
+    let key: [u8; 32] = Sha256::digest(&audio_data).into();
+    if let Some(samples) = lock_or_recover(cache).get(&key) {
+        println!("♻️  Reusing decoded audio from an earlier request");
+        return Ok(samples);
+    }
+
+    let samples = Arc::new(decode(audio_data).await?);
+    lock_or_recover(cache).insert(key, samples.clone());
+    Ok(samples)
+}
 
 // STT transcription response
 #[derive(Serialize)]
 pub struct TranscribeResponse {
     pub text: String,
+    // Mean segment confidence, 0.0 - 1.0
     pub confidence: f32,
+    // Where speech was heard, for highlighting the clip's waveform
+    pub segments: Vec<SpeechSegment>,
 }
 
-// Transcribe audio to text using Whisper STT with rate limiting
-pub async fn transcribe_audio(
-    State((aira_state, _semaphore)): State<(SharedAira, &'static Semaphore)>,
-    mut multipart: Multipart,
-) -> impl IntoResponse {
-    let result = async {
-        let mut audio_data: Vec<u8> = Vec::new();
-
-        // Extract audio data from multipart form
-        while let Some(field) = multipart.next_field().await.map_err(|e| anyhow::anyhow!("Multipart error: {}", e))? {
-            let name = field.name().ok_or_else(|| anyhow::anyhow!("Field name not found"))?;
-            if name == "audio" {
-                audio_data = field.bytes().await.map_err(|e| anyhow::anyhow!("Failed to read audio: {}", e))?.to_vec();
-                break;
+impl From<Transcription> for TranscribeResponse {
+    fn from(transcription: Transcription) -> Self {
+        Self {
+            text: transcription.text,
+            confidence: transcription.confidence,
+            segments: transcription.segments,
+        }
+    }
+}
+
+// Audio received by the transcription endpoint
+#[derive(Debug)]
+enum AudioUpload {
+    // WAV, webm/opus or anything else FFmpeg can read
+    Encoded(Vec<u8>),
+    // Already-decoded f32 little-endian samples, interleaved when multi-channel
+    RawPcm {
+        data: Vec<u8>,
+        sample_rate: u32,
+        channels: u16,
+    },
+}
+
+// Multipart field names accepted for an encoded clip, set from AIRA_STT_AUDIO_FIELDS
+static AUDIO_FIELDS: OnceLock<Vec<String>> = OnceLock::new();
+
+// Clients and upload libraries disagree on what to call the file
+const DEFAULT_AUDIO_FIELDS: [&str; 3] = ["audio", "file", "data"];
+
+pub fn set_audio_fields(fields: Vec<String>) {
+    let _ = AUDIO_FIELDS.set(fields);
+}
+
+fn audio_fields() -> &'static [String] {
+    AUDIO_FIELDS.get_or_init(|| DEFAULT_AUDIO_FIELDS.map(String::from).to_vec())
+}
+
+// An upload the client got wrong, answered with 400 rather than a transcription failure
+#[derive(Debug)]
+struct InvalidUpload(String);
+
+impl std::fmt::Display for InvalidUpload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidUpload {}
+
+fn invalid_upload(message: impl Into<String>) -> anyhow::Error {
+    InvalidUpload(message.into()).into()
+}
+
+// Read an encoded clip from any of `audio_fields()`, or a `pcm` field with `sample_rate`
+// and `channels` (default 1); failing both, the first file field under any other name
+async fn read_upload(multipart: &mut Multipart) -> anyhow::Result<AudioUpload> {
+    let fields = audio_fields();
+    let mut audio = None;
+    let mut unnamed_file = None;
+    let mut pcm = None;
+    let mut sample_rate = None;
+    let mut channels = 1;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| anyhow::anyhow!("Multipart error: {}", e))?
+    {
+        let name = field
+            .name()
+            .ok_or_else(|| anyhow::anyhow!("Field name not found"))?
+            .to_string();
+        match name.as_str() {
+            "pcm" => pcm = Some(read_bytes(field).await?),
+            "sample_rate" | "channels" => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", name, e))?;
+                let invalid = |_| invalid_upload(format!("Invalid {}: {:?}", name, text));
+                if name == "sample_rate" {
+                    sample_rate = Some(text.trim().parse().map_err(invalid)?);
+                } else {
+                    channels = text.trim().parse().map_err(invalid)?;
+                }
             }
+            name if fields.iter().any(|f| f == name) => {
+                let data = read_bytes(field).await?;
+                audio.get_or_insert(data);
+            }
+            // Anything sent as a file is likely the clip under a name we don't know
+            _ if field.file_name().is_some() && unnamed_file.is_none() => {
+                unnamed_file = Some(read_bytes(field).await?);
+            }
+            _ => {}
         }
+    }
 
-        if audio_data.is_empty() {
-            return Err::<_, anyhow::Error>(anyhow::anyhow!("No audio data received"));
+    if let Some(data) = pcm {
+        if audio.is_some() || unnamed_file.is_some() {
+            return Err(invalid_upload(
+                "Send either raw PCM or an encoded audio file, not both",
+            ));
         }
+        if data.is_empty() {
+            return Err(invalid_upload("The pcm field is empty"));
+        }
+        let sample_rate =
+            sample_rate.ok_or_else(|| invalid_upload("sample_rate is required for raw PCM"))?;
+        if !(MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&sample_rate) {
+            return Err(invalid_upload(format!(
+                "sample_rate must be between {} and {} Hz, got {}",
+                MIN_SAMPLE_RATE, MAX_SAMPLE_RATE, sample_rate
+            )));
+        }
+        return Ok(AudioUpload::RawPcm {
+            data,
+            sample_rate,
+            channels,
+        });
+    }
 
-        println!("Received audio data: {} bytes", audio_data.len());
+    match audio.or(unnamed_file) {
+        Some(data) if !data.is_empty() => Ok(AudioUpload::Encoded(data)),
+        _ => Err(anyhow::anyhow!(
+            "No audio data received; send it as a file field named one of: {}",
+            fields.join(", ")
+        )),
+    }
+}
+
+async fn read_bytes(field: axum::extract::multipart::Field<'_>) -> anyhow::Result<Vec<u8>> {
+    Ok(field
+        .bytes()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to read audio: {}", e))?
+        .to_vec())
+}
 
-        // Convert audio to f32 samples
-        let samples = decode_audio(&audio_data).await?;
+// Turn raw f32 PCM into 16kHz mono for Whisper; nothing to decode, so no FFmpeg or cache
+fn raw_pcm_to_whisper(data: &[u8], sample_rate: u32, channels: u16) -> anyhow::Result<Vec<f32>> {
+    if sample_rate == 0 || channels == 0 {
+        return Err(invalid_upload("sample_rate and channels must be positive"));
+    }
+    let frame_bytes = 4 * channels as usize;
+    if !data.len().is_multiple_of(frame_bytes) {
+        return Err(invalid_upload(format!(
+            "Raw PCM length {} is not a whole number of {}-channel f32 frames",
+            data.len(),
+            channels
+        )));
+    }
 
-        println!("Decoded {} samples", samples.len());
+    let interleaved: Vec<f32> = data
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    Ok(AudioBuffer::new(interleaved, sample_rate, channels).for_whisper())
+}
 
-        if samples.is_empty() {
-            return Err::<_, anyhow::Error>(anyhow::anyhow!("No audio samples decoded"));
+// Samples ready for transcription, decoding (and caching) encoded uploads
+// Decoding runs on the blocking pool: FFmpeg and long clips take a while
+async fn upload_to_samples(upload: AudioUpload) -> anyhow::Result<Arc<Vec<f32>>> {
+    match upload {
+        AudioUpload::Encoded(audio_data) => {
+            println!("Received audio data: {} bytes", audio_data.len());
+            get_or_decode(&DECODE_CACHE, audio_data, |audio_data| {
+                run_blocking(move || decode_audio(&audio_data))
+            })
+            .await
         }
+        AudioUpload::RawPcm {
+            data,
+            sample_rate,
+            channels,
+        } => {
+            println!(
+                "Received raw PCM: {} bytes at {} Hz, {} channel(s)",
+                data.len(),
+                sample_rate,
+                channels
+            );
+            let samples =
+                run_blocking(move || raw_pcm_to_whisper(&data, sample_rate, channels)).await?;
+            Ok(Arc::new(samples))
+        }
+    }
+}
 
-        // Transcribe using Whisper
-        let transcription = {
-            let guard = aira_state.lock().unwrap();
-            guard.transcribe(&samples)?
-        };
+// Decode the uploaded clip and get the STT engine, ready for a Whisper pass
+async fn prepare_transcription(
+    aira_state: &SharedAira,
+    multipart: &mut Multipart,
+) -> anyhow::Result<(Arc<Vec<f32>>, Arc<Mutex<SttEngine>>)> {
+    let upload = read_upload(multipart).await?;
+
+    // Convert audio to f32 samples
+    let samples = upload_to_samples(upload).await?;
+
+    println!("Decoded {} samples", samples.len());
+
+    if samples.is_empty() {
+        return Err(anyhow::anyhow!("No audio samples decoded"));
+    }
+
+    ensure_loaded(aira_state).await?;
+    let stt = lock_or_recover(aira_state)
+        .get_stt()
+        .ok_or(AiraError::SttNotConfigured)?;
+    Ok((samples, stt))
+}
+
+fn transcription_error(e: anyhow::Error) -> Response {
+    if e.downcast_ref::<InvalidUpload>().is_some() {
+        return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+    }
+    if e.downcast_ref::<AiraError>().is_some() {
+        return (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response();
+    }
+    eprintln!("STT Error: {}", e);
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("Transcription failed: {}", e)).into_response()
+}
+
+// Transcribe audio to text using Whisper STT with rate limiting
+pub async fn transcribe_audio(
+    State((aira_state, _semaphore)): State<(SharedAira, &'static Semaphore)>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let result = async {
+        let (samples, stt) = prepare_transcription(&aira_state, &mut multipart).await?;
+
+        // Transcribe using Whisper, holding only the STT engine's lock
+        let transcription = transcribe_blocking(samples, move |samples| {
+            lock_or_recover(&stt).transcribe_long(samples)
+        })
+        .await?;
 
-        Ok(Json(TranscribeResponse {
-            text: transcription,
-            confidence: 0.95,
-        }))
+        Ok::<_, anyhow::Error>(Json(TranscribeResponse::from(transcription)))
     }.await;
 
     match result {
         Ok(response) => response.into_response(),
-        Err(e) => {
-            eprintln!("STT Error: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Transcription failed: {}", e)).into_response()
+        Err(e) => transcription_error(e),
+    }
+}
+
+// A `partial` event: one segment with its confidence, so the UI can flag unsure words live
+fn partial_event(segment: &TranscribedSegment) -> Event {
+    Event::default()
+        .event("partial")
+        .json_data(segment)
+        .unwrap_or_else(|_| Event::default().event("partial").data(&segment.text))
+}
+
+// Transcribe like `transcribe_audio`, streaming each segment as a `partial` event as it is decoded
+// The stream ends with a `done` event carrying the full TranscribeResponse, or an `error` event
+pub async fn transcribe_audio_stream(
+    State((aira_state, _semaphore)): State<(SharedAira, &'static Semaphore)>,
+    mut multipart: Multipart,
+) -> Response {
+    let (samples, stt) = match prepare_transcription(&aira_state, &mut multipart).await {
+        Ok(prepared) => prepared,
+        Err(e) => return transcription_error(e),
+    };
+
+    let (event_tx, event_rx) = mpsc::channel::<Result<Event, Infallible>>(64);
+    tokio::spawn(async move {
+        let partial_tx = event_tx.clone();
+        let result = transcribe_blocking(samples, move |samples| {
+            lock_or_recover(&stt).transcribe_streamed(samples, &mut |segment| {
+                let _ = partial_tx.blocking_send(Ok(partial_event(&segment)));
+            })
+        })
+        .await;
+
+        let event = match result {
+            Ok(transcription) => Event::default()
+                .event("done")
+                .json_data(TranscribeResponse::from(transcription))
+                .unwrap_or_else(|_| Event::default().event("done")),
+            Err(e) => {
+                eprintln!("STT Error: {}", e);
+                Event::default().event("error").data("Transcription failed")
+            }
+        };
+        let _ = event_tx.send(Ok(event)).await;
+    });
+
+    Sse::new(tokio_stream::wrappers::ReceiverStream::new(event_rx))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+// Run a Whisper pass off the async workers so the runtime keeps serving requests
+async fn transcribe_blocking<F, T>(samples: Arc<Vec<f32>>, transcribe: F) -> anyhow::Result<T>
+where
+    F: FnOnce(&[f32]) -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    crate::priority::run_inference(move || transcribe(&samples)).await?
+}
+
+// Container/codec recognised from the first bytes of an upload
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AudioFormat {
+    Wav,
+    Ogg,
+    Flac,
+    WebM,
+    Mp3,
+    Unknown,
+}
+
+impl AudioFormat {
+    fn detect(data: &[u8]) -> Self {
+        match data {
+            [b'R', b'I', b'F', b'F', ..] => Self::Wav,
+            [b'O', b'g', b'g', b'S', ..] => Self::Ogg,
+            [b'f', b'L', b'a', b'C', ..] => Self::Flac,
+            [0x1A, 0x45, 0xDF, 0xA3, ..] => Self::WebM,
+            [b'I', b'D', b'3', ..] => Self::Mp3,
+            // MPEG audio frame sync; layer bits 00 are AAC (ADTS), not MP3
+            [0xFF, b, ..] if b & 0xE0 == 0xE0 && (b >> 1) & 0x03 != 0 => Self::Mp3,
+            _ => Self::Unknown,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Wav => "WAV",
+            Self::Ogg => "OGG",
+            Self::Flac => "FLAC",
+            Self::WebM => "WebM",
+            Self::Mp3 => "MP3",
+            Self::Unknown => "unrecognised",
+        }
+    }
+
+    // File extension handed to the in-process probe as a hint
+    fn extension(self) -> Option<&'static str> {
+        match self {
+            Self::Wav => Some("wav"),
+            Self::Ogg => Some("ogg"),
+            Self::Flac => Some("flac"),
+            Self::WebM => Some("webm"),
+            Self::Mp3 => Some("mp3"),
+            Self::Unknown => None,
         }
     }
 }
 
-// Decode audio bytes to f32 samples
-// Tries multiple methods: WAV, FFmpeg conversion
-async fn decode_audio(audio_data: &[u8]) -> anyhow::Result<Vec<f32>> {
-    // Try WAV first (simplest)
-    if audio_data.starts_with(b"RIFF") {
-        println!("Detected WAV format, decoding...");
-        return decode_wav(audio_data);
+// Decode audio bytes to 16kHz mono f32 samples
+// WAV, OGG (Vorbis), FLAC and MP3 are decoded in-process; WebM and anything
+// unrecognised go through FFmpeg
+// Blocks on decoding and the FFmpeg process, so call it from the blocking pool
+fn decode_audio(audio_data: &[u8]) -> anyhow::Result<Vec<f32>> {
+    let format = AudioFormat::detect(audio_data);
+    println!("Detected {} audio", format.name());
+
+    match format {
+        AudioFormat::Wav => decode_wav(audio_data),
+        AudioFormat::Ogg | AudioFormat::Flac | AudioFormat::Mp3 => {
+            match decode_in_process(audio_data, format) {
+                Ok(samples) => Ok(samples),
+                // e.g. Ogg Opus, which the bundled codecs don't cover
+                Err(e) => {
+                    println!(
+                        "In-process {} decode failed ({}), trying FFmpeg...",
+                        format.name(),
+                        e
+                    );
+                    decode_with_ffmpeg_named(audio_data, format)
+                }
+            }
+        }
+        AudioFormat::WebM | AudioFormat::Unknown => {
+            decode_with_ffmpeg_named(audio_data, format)
+        }
     }
+}
 
-    // For webm/opus, try ffmpeg conversion
+fn decode_with_ffmpeg_named(audio_data: &[u8], format: AudioFormat) -> anyhow::Result<Vec<f32>> {
     println!("Attempting FFmpeg conversion...");
-    decode_with_ffmpeg(audio_data).await
+    decode_with_ffmpeg(audio_data).map_err(|e| anyhow::anyhow!("Could not decode {} audio: {}", format.name(), e))
+}
+
+// Decode a compressed upload with symphonia, then downmix and resample for Whisper
+fn decode_in_process(audio_data: &[u8], format: AudioFormat) -> anyhow::Result<Vec<f32>> {
+    use symphonia::core::{
+        audio::SampleBuffer, codecs::DecoderOptions, errors::Error, formats::FormatOptions,
+        io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+    };
+
+    let source = MediaSourceStream::new(
+        Box::new(Cursor::new(audio_data.to_vec())),
+        Default::default(),
+    );
+    let mut hint = Hint::new();
+    if let Some(extension) = format.extension() {
+        hint.with_extension(extension);
+    }
+    let mut reader = symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )?
+        .format;
+
+    let track = reader
+        .default_track()
+        .ok_or_else(|| anyhow::anyhow!("No audio track"))?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .ok_or_else(|| anyhow::anyhow!("Unknown sample rate"))?;
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut interleaved = Vec::new();
+    let mut channels = 1;
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let spec = *decoded.spec();
+                channels = spec.channels.count() as u16;
+                let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                buffer.copy_interleaved_ref(decoded);
+                interleaved.extend_from_slice(buffer.samples());
+            }
+            // A corrupt packet only loses that packet
+            Err(Error::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    println!(
+        "{} format: {} channels, {} Hz",
+        format.name(),
+        channels,
+        sample_rate
+    );
+    Ok(AudioBuffer::new(interleaved, sample_rate, channels).for_whisper())
 }
 
 // Decode WAV file to f32 samples
@@ -99,11 +572,42 @@ fn decode_wav(audio_data: &[u8]) -> anyhow::Result<Vec<f32>> {
         .collect();
     
     println!("Decoded {} WAV samples", samples.len());
-    Ok(samples)
+    // Browsers record at 44.1/48 kHz, often in stereo; Whisper needs 16 kHz mono
+    Ok(AudioBuffer::new(samples, spec.sample_rate, spec.channels).for_whisper())
+}
+
+// Default FFmpeg arguments: quiet, never read stdin, 16kHz mono PCM (Whisper expects this)
+const DEFAULT_FFMPEG_ARGS: &str =
+    "-nostdin -loglevel error -i {input} -ar 16000 -ac 1 -c:a pcm_s16le -y {output}";
+
+// FFmpeg binary and argument template, read from AIRA_FFMPEG_PATH / AIRA_FFMPEG_ARGS
+struct FfmpegCommand {
+    path: String,
+    // Whitespace separated; {input} and {output} are replaced with the temp file paths
+    args_template: String,
+}
+
+impl FfmpegCommand {
+    fn from_env() -> Self {
+        Self {
+            path: std::env::var("AIRA_FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".to_string()),
+            args_template: std::env::var("AIRA_FFMPEG_ARGS")
+                .unwrap_or_else(|_| DEFAULT_FFMPEG_ARGS.to_string()),
+        }
+    }
+
+    fn build(&self, input_path: &str, output_path: &str) -> Command {
+        let mut command = Command::new(&self.path);
+        command.args(self.args_template.split_whitespace().map(|arg| {
+            arg.replace("{input}", input_path)
+                .replace("{output}", output_path)
+        }));
+        command
+    }
 }
 
 // Use FFmpeg to convert webm/opus to WAV, then decode
-async fn decode_with_ffmpeg(audio_data: &[u8]) -> anyhow::Result<Vec<f32>> {
+fn decode_with_ffmpeg(audio_data: &[u8]) -> anyhow::Result<Vec<f32>> {
     // Create unique temporary files to avoid collisions
     let timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -116,15 +620,8 @@ async fn decode_with_ffmpeg(audio_data: &[u8]) -> anyhow::Result<Vec<f32>> {
     std::fs::write(&input_path, audio_data)?;
     
     // Run ffmpeg to convert to WAV (16kHz mono, which Whisper expects)
-    let output = Command::new("ffmpeg")
-        .args(&[
-            "-i", &input_path,
-            "-ar", "16000",      // 16kHz sample rate (Whisper expects this)
-            "-ac", "1",          // Mono
-            "-c:a", "pcm_s16le", // 16-bit PCM
-            "-y",                // Overwrite output
-            &output_path,
-        ])
+    let output = FfmpegCommand::from_env()
+        .build(&input_path, &output_path)
         .output();
     
     match output {
@@ -156,3 +653,344 @@ async fn decode_with_ffmpeg(audio_data: &[u8]) -> anyhow::Result<Vec<f32>> {
             }
         }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::{FromRequest, Query};
+    use axum::http::Request;
+    use std::time::Duration;
+
+    // Backend for an Aira that is never asked to generate
+    struct IdleBackend;
+
+    impl aira_brain::llm::CompletionBackend for IdleBackend {
+        fn complete(
+            &mut self,
+            _prompt: &str,
+            _params: &aira_brain::llm::SamplingParams,
+            _on_piece: &mut dyn FnMut(&str) -> bool,
+        ) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health_responds_while_transcription_runs() {
+        static SEMAPHORE: Semaphore = Semaphore::const_new(1);
+        let llm = aira_brain::llm::LlmEngine::with_backend(Box::new(IdleBackend), "");
+        let aira: SharedAira = Arc::new(Mutex::new(aira_brain::aira::Aira::builder(llm).build()));
+
+        // A slow Whisper stand-in that blocks its thread
+        let transcription = tokio::spawn(transcribe_blocking(Arc::new(vec![0.0; 16000]), |_| {
+            std::thread::sleep(Duration::from_millis(500));
+            Ok("hello".to_string())
+        }));
+        tokio::task::yield_now().await;
+
+        let health = tokio::time::timeout(
+            Duration::from_millis(100),
+            crate::api::health(
+                State((aira, &SEMAPHORE)),
+                Query(crate::models::HealthQuery::default()),
+            ),
+        )
+        .await;
+        assert_eq!(health.expect("health check was blocked").status(), StatusCode::OK);
+        assert!(!transcription.is_finished());
+
+        assert_eq!(transcription.await.unwrap().unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_wav_magic_is_detected() {
+        assert_eq!(
+            AudioFormat::detect(b"RIFF\x24\x00\x00\x00WAVEfmt "),
+            AudioFormat::Wav
+        );
+    }
+
+    #[test]
+    fn test_ogg_magic_is_detected() {
+        assert_eq!(AudioFormat::detect(b"OggS\x00\x02"), AudioFormat::Ogg);
+    }
+
+    #[test]
+    fn test_flac_magic_is_detected() {
+        assert_eq!(
+            AudioFormat::detect(b"fLaC\x00\x00\x00\x22"),
+            AudioFormat::Flac
+        );
+    }
+
+    #[test]
+    fn test_webm_magic_is_detected() {
+        assert_eq!(
+            AudioFormat::detect(&[0x1A, 0x45, 0xDF, 0xA3, 0x9F]),
+            AudioFormat::WebM
+        );
+    }
+
+    #[test]
+    fn test_mp3_magic_is_detected() {
+        assert_eq!(AudioFormat::detect(b"ID3\x04\x00"), AudioFormat::Mp3);
+        // Bare MPEG-1 Layer III frame header
+        assert_eq!(
+            AudioFormat::detect(&[0xFF, 0xFB, 0x90, 0x64]),
+            AudioFormat::Mp3
+        );
+        // ADTS AAC shares the sync word but not the layer bits
+        assert_eq!(
+            AudioFormat::detect(&[0xFF, 0xF1, 0x50, 0x80]),
+            AudioFormat::Unknown
+        );
+    }
+
+    #[test]
+    fn test_unknown_bytes_are_not_guessed() {
+        assert_eq!(AudioFormat::detect(b"hello"), AudioFormat::Unknown);
+        assert_eq!(AudioFormat::detect(b""), AudioFormat::Unknown);
+        assert_eq!(AudioFormat::Unknown.name(), "unrecognised");
+    }
+
+    fn multipart_request(parts: &[(&str, &[u8])]) -> Request<Body> {
+        let boundary = "aira-test-boundary";
+        let mut body = Vec::new();
+        for (name, value) in parts {
+            body.extend_from_slice(
+                format!(
+                    "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                    boundary, name
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(value);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+        Request::builder()
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_raw_pcm_upload_is_resampled_for_whisper() {
+        // One second of 48 kHz stereo f32 PCM
+        let pcm: Vec<u8> = (0..48000)
+            .flat_map(|i| {
+                let s = (i as f32 * 0.01).sin() * 0.5;
+                [s, s]
+            })
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        let request = multipart_request(&[
+            ("pcm", &pcm),
+            ("sample_rate", b"48000"),
+            ("channels", b"2"),
+        ]);
+        let mut multipart = Multipart::from_request(request, &()).await.unwrap();
+
+        let upload = read_upload(&mut multipart).await.unwrap();
+        assert!(matches!(
+            upload,
+            AudioUpload::RawPcm {
+                sample_rate: 48000,
+                channels: 2,
+                ..
+            }
+        ));
+
+        // Goes straight to Whisper's 16 kHz mono input without touching FFmpeg
+        let samples = upload_to_samples(upload).await.unwrap();
+        assert_eq!(samples.len(), 16000);
+        assert!(samples.iter().all(|s| s.abs() <= 0.5 + f32::EPSILON));
+    }
+
+    #[test]
+    fn test_48khz_stereo_wav_is_decoded_for_whisper() {
+        // One second as a browser records it: loud left channel, silent right
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = Vec::new();
+        let mut writer = hound::WavWriter::new(Cursor::new(&mut wav), spec).unwrap();
+        for _ in 0..48000 {
+            writer.write_sample(i16::MAX / 2).unwrap();
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let samples = decode_wav(&wav).unwrap();
+        // 16 kHz mono, with the channels averaged rather than interleaved
+        assert_eq!(samples.len(), 16000);
+        assert!((samples[8000] - 0.25).abs() < 0.01);
+    }
+
+    // Whisper stand-in that hears "hello" in any clip
+    struct HelloBackend;
+
+    impl aira_brain::stt::SttBackend for HelloBackend {
+        fn decode(
+            &self,
+            audio: &[f32],
+            _config: &aira_brain::stt::SttConfig,
+        ) -> anyhow::Result<Vec<aira_brain::stt::TimedSegment>> {
+            Ok(vec![aira_brain::stt::TimedSegment {
+                start: 0,
+                end: (audio.len() * 100 / aira_brain::audio::WHISPER_SAMPLE_RATE as usize) as i64,
+                text: "hello".to_string(),
+                confidence: 0.9,
+            }])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_audio_under_a_file_field_is_transcribed() {
+        static SEMAPHORE: Semaphore = Semaphore::const_new(1);
+        let llm = aira_brain::llm::LlmEngine::with_backend(Box::new(IdleBackend), "");
+        let stt = SttEngine::with_backend(Box::new(HelloBackend), Default::default());
+        let aira: SharedAira = Arc::new(Mutex::new(
+            aira_brain::aira::Aira::builder(llm).stt(stt).build(),
+        ));
+
+        // Half a second of 16 kHz WAV, as an upload library would name it
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = Vec::new();
+        let mut writer = hound::WavWriter::new(Cursor::new(&mut wav), spec).unwrap();
+        for i in 0..8000 {
+            writer
+                .write_sample(((i as f32 * 0.05).sin() * 8000.0) as i16)
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let request = multipart_request(&[("file", &wav)]);
+        let multipart = Multipart::from_request(request, &()).await.unwrap();
+        let response = transcribe_audio(State((aira, &SEMAPHORE)), multipart)
+            .await
+            .into_response();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let transcription: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(transcription["text"], "hello");
+    }
+
+    #[tokio::test]
+    async fn test_bad_raw_pcm_uploads_are_rejected_as_client_errors() {
+        let second = [0u8; 4 * 16000];
+        let cases: [&[(&str, &[u8])]; 4] = [
+            &[("pcm", &second), ("sample_rate", b"4000")],
+            &[("pcm", &second), ("sample_rate", b"96000")],
+            &[("pcm", b""), ("sample_rate", b"16000")],
+            &[
+                ("pcm", &second),
+                ("sample_rate", b"16000"),
+                ("audio", b"RIFF"),
+            ],
+        ];
+
+        for fields in cases {
+            let request = multipart_request(fields);
+            let mut multipart = Multipart::from_request(request, &()).await.unwrap();
+            let error = read_upload(&mut multipart).await.unwrap_err();
+            assert_eq!(
+                transcription_error(error).status(),
+                StatusCode::BAD_REQUEST,
+                "{:?}",
+                fields.iter().map(|(name, _)| name).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_raw_pcm_with_partial_frame_is_rejected() {
+        assert!(raw_pcm_to_whisper(&[0u8; 12], 16000, 2).is_err());
+        assert!(raw_pcm_to_whisper(&[0u8; 16], 0, 1).is_err());
+    }
+
+    #[test]
+    fn test_partial_event_carries_segment_confidence() {
+        let segment = TranscribedSegment {
+            text: "Maybe?".to_string(),
+            start: 2.5,
+            end: 4.0,
+            confidence: 0.42,
+        };
+        let event = format!("{:?}", partial_event(&segment));
+        assert!(event.contains("event: partial"), "{}", event);
+        assert!(event.contains("confidence") && event.contains("0.42"), "{}", event);
+    }
+
+    #[tokio::test]
+    async fn test_identical_audio_reuses_decoded_samples() {
+        let cache = Mutex::new(DecodedAudioCache::new(4));
+        let decodes = std::sync::atomic::AtomicUsize::new(0);
+        let decode = |_: Vec<u8>| async {
+            decodes.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(vec![0.25; 160])
+        };
+
+        let first = get_or_decode(&cache, b"same clip".to_vec(), decode).await.unwrap();
+        let second = get_or_decode(&cache, b"same clip".to_vec(), decode).await.unwrap();
+        assert_eq!(decodes.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        get_or_decode(&cache, b"other clip".to_vec(), decode).await.unwrap();
+        assert_eq!(decodes.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_decode_cache_evicts_oldest() {
+        let mut cache = DecodedAudioCache::new(2);
+        for key in 0..3u8 {
+            cache.insert([key; 32], Arc::new(vec![key as f32]));
+        }
+
+        assert!(cache.get(&[0; 32]).is_none());
+        assert!(cache.get(&[2; 32]).is_some());
+    }
+
+    #[test]
+    fn test_build_command_with_custom_path() {
+        let ffmpeg = FfmpegCommand {
+            path: "/opt/ffmpeg/bin/ffmpeg".to_string(),
+            args_template: DEFAULT_FFMPEG_ARGS.to_string(),
+        };
+        let command = ffmpeg.build("/tmp/in.webm", "/tmp/out.wav");
+        let args: Vec<_> = command.get_args().map(|a| a.to_str().unwrap()).collect();
+
+        assert_eq!(command.get_program(), "/opt/ffmpeg/bin/ffmpeg");
+        assert_eq!(&args[..4], ["-nostdin", "-loglevel", "error", "-i"]);
+        assert_eq!(args[4], "/tmp/in.webm");
+        assert_eq!(args.last(), Some(&"/tmp/out.wav"));
+    }
+
+    #[test]
+    fn test_custom_args_template_substitutes_paths() {
+        let ffmpeg = FfmpegCommand {
+            path: "ffmpeg".to_string(),
+            args_template: "-hide_banner -i {input} -ar 16000 {output}".to_string(),
+        };
+        let command = ffmpeg.build("a.webm", "b.wav");
+        let args: Vec<_> = command.get_args().map(|a| a.to_str().unwrap()).collect();
+
+        assert_eq!(args, ["-hide_banner", "-i", "a.webm", "-ar", "16000", "b.wav"]);
+    }
+}
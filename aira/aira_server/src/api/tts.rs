@@ -1,41 +1,211 @@
-use crate::models::TtsRequest;
-use crate::states::SharedAira;
+use crate::models::{SpeedRequest, TtsRequest};
+use crate::states::{SharedAira, ensure_loaded, lock_or_recover, run_blocking};
+use aira_brain::aira::AiraError;
+use aira_brain::audio::{MAX_SAMPLE_RATE, MIN_SAMPLE_RATE, apply_gain, resample, upmix};
+use aira_brain::language::Language;
+use aira_brain::ssml;
+use aira_brain::tts::{TtsEngine, WordTiming};
 use anyhow::Result;
 use axum::{
     Json,
     body::Body,
     extract::State,
     http::{StatusCode, header},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
+use base64::{Engine as _, engine::general_purpose};
 use hound::{SampleFormat, WavSpec, WavWriter};
+use serde::Serialize;
 use std::io::Cursor;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 use tokio::sync::Semaphore;
 
+// Channels of synthesized WAVs unless a request asks otherwise (AIRA_TTS_CHANNELS)
+static OUTPUT_CHANNELS: OnceLock<u16> = OnceLock::new();
+
+pub fn set_output_channels(channels: u16) {
+    let _ = OUTPUT_CHANNELS.set(supported_channels(channels));
+}
+
+pub fn output_channels() -> u16 {
+    OUTPUT_CHANNELS.get().copied().unwrap_or(1)
+}
+
+// Voices are mono, so output is mono or the same signal on both stereo channels
+pub fn supported_channels(channels: u16) -> u16 {
+    channels.clamp(1, 2)
+}
+
+// Voices besides the main one, for replies in other languages (AIRA_TTS_VOICES)
+static EXTRA_VOICES: OnceLock<Vec<TtsEngine>> = OnceLock::new();
+
+pub fn set_extra_voices(voices: Vec<TtsEngine>) {
+    let _ = EXTRA_VOICES.set(voices);
+}
+
+// The voice to speak `language` with: the main voice unless it speaks another language
+// and an extra voice speaks this one; without a match the main voice is kept
+pub fn voice_for(main: Option<TtsEngine>, language: Option<&Language>) -> Option<TtsEngine> {
+    let Some(language) = language else {
+        return main;
+    };
+    if main
+        .as_ref()
+        .and_then(TtsEngine::language)
+        .is_some_and(|voice| language.is_spoken_by(voice))
+    {
+        return main;
+    }
+    let voices = EXTRA_VOICES.get().map(Vec::as_slice).unwrap_or_default();
+    match language.matching_voice(voices.iter().map(TtsEngine::language)) {
+        Some(index) => Some(voices[index].clone()),
+        None => main,
+    }
+}
+
+// How long synthesis took against how much audio it made, for capacity planning
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SynthesisTiming {
+    synthesis: Duration,
+    audio: Duration,
+}
+
+impl SynthesisTiming {
+    // Run `synthesize` and time it; the audio is `sample_rate` mono samples
+    fn measure<F>(sample_rate: u32, synthesize: F) -> Result<(Vec<f32>, Self)>
+    where
+        F: FnOnce() -> Result<Vec<f32>>,
+    {
+        let started = Instant::now();
+        let samples = synthesize()?;
+        let timing = Self {
+            synthesis: started.elapsed(),
+            audio: Duration::from_secs_f64(samples.len() as f64 / sample_rate as f64),
+        };
+        Ok((samples, timing))
+    }
+
+    // Seconds of synthesis per second of audio; below 1.0 is faster than real time
+    fn real_time_factor(&self) -> f64 {
+        if self.audio.is_zero() {
+            return 0.0;
+        }
+        self.synthesis.as_secs_f64() / self.audio.as_secs_f64()
+    }
+
+    fn headers(&self) -> [(&'static str, String); 3] {
+        [
+            (
+                "x-synthesis-ms",
+                format!("{:.1}", self.synthesis.as_secs_f64() * 1000.0),
+            ),
+            (
+                "x-audio-duration-ms",
+                format!("{:.1}", self.audio.as_secs_f64() * 1000.0),
+            ),
+            (
+                "x-real-time-factor",
+                format!("{:.3}", self.real_time_factor()),
+            ),
+        ]
+    }
+}
+
+// Rate to render a request at: the one it asks for, or the voice's own
+// A rate no player or resampler should be handed is the client's mistake
+fn output_sample_rate(requested: Option<u32>, native: u32) -> Result<u32, Response> {
+    match requested {
+        None => Ok(native),
+        Some(rate) if (MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&rate) => Ok(rate),
+        Some(rate) => Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "sample_rate must be between {} and {} Hz, got {}",
+                MIN_SAMPLE_RATE, MAX_SAMPLE_RATE, rate
+            ),
+        )
+            .into_response()),
+    }
+}
+
+// The loaded TTS engine, or the response explaining why there is none
+async fn loaded_tts(aira: &SharedAira) -> Result<TtsEngine, Response> {
+    if let Err(e) = ensure_loaded(aira).await {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response());
+    }
+
+    // Clone TTS engine to avoid holding lock during synthesis
+    let tts_engine = {
+        let guard = lock_or_recover(aira);
+        guard.get_tts()
+    };
+    tts_engine.ok_or_else(|| {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            AiraError::TtsNotConfigured.to_string(),
+        )
+            .into_response()
+    })
+}
+
 pub async fn tts(
     State((aira, _semaphore)): State<(SharedAira, &'static Semaphore)>,
     Json(req): Json<TtsRequest>,
 ) -> impl IntoResponse {
-    // Clone TTS engine to avoid holding lock during synthesis
-    let tts_engine = {
-        let guard = aira.lock().unwrap();
-        guard.get_tts()
+    let tts_engine = match loaded_tts(&aira).await {
+        Ok(tts_engine) => tts_engine,
+        Err(response) => return response,
+    };
+
+    // Text with tags is SSML-lite (see aira_brain::ssml); bad markup is the client's mistake
+    let spans = if ssml::has_markup(&req.text) {
+        match ssml::parse(&req.text) {
+            Ok(spans) => Some(spans),
+            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        }
+    } else {
+        None
     };
 
     // Run TTS in blocking thread
     let text = req.text.clone();
-    let result = tokio::task::spawn_blocking(move || tts_engine.synthesize(&text)).await;
+    let sample_rate = match output_sample_rate(req.sample_rate, tts_engine.sample_rate()) {
+        Ok(sample_rate) => sample_rate,
+        Err(response) => return response,
+    };
+    let volume = req.volume.unwrap_or(tts_engine.volume());
+    let channels = req.channels.map_or(output_channels(), supported_channels);
+    let result = tokio::task::spawn_blocking(move || {
+        let (mut samples, timing) = SynthesisTiming::measure(sample_rate, || {
+            Ok(match spans {
+                Some(spans) => resample(
+                    tts_engine.synthesize_spans(&spans)?,
+                    tts_engine.sample_rate(),
+                    sample_rate,
+                ),
+                None => tts_engine.synthesize_at(&text, sample_rate)?,
+            })
+        })?;
+        apply_gain(&mut samples, volume);
+        Ok::<_, anyhow::Error>((samples, timing))
+    })
+    .await;
 
     match result {
-        Ok(Ok(samples)) => match create_wav(samples) {
+        // Blank text has nothing to speak; an empty WAV would only confuse players
+        Ok(Ok((samples, _))) if samples.is_empty() => StatusCode::NO_CONTENT.into_response(),
+        Ok(Ok((samples, timing))) => match create_wav(samples, sample_rate, channels) {
             Ok(wav_data) => {
                 let content_length = wav_data.len().to_string();
+                // Synthesis time and audio length let clients work out the real-time factor
                 (
                     StatusCode::OK,
                     [
                         (header::CONTENT_TYPE, "audio/wav"),
                         (header::CONTENT_LENGTH, &content_length),
                     ],
+                    timing.headers(),
                     Body::from(wav_data),
                 )
                     .into_response()
@@ -47,10 +217,98 @@ pub async fn tts(
     }
 }
 
-fn create_wav(samples: Vec<f32>) -> Result<Vec<u8>> {
+// Speech with a caption track, for clients that show words as they are spoken
+#[derive(Serialize)]
+pub struct CaptionedSpeechResponse {
+    // Base64 WAV
+    pub audio: String,
+    pub sample_rate: u32,
+    pub duration_secs: f32,
+    pub words: Vec<WordTiming>,
+}
+
+// Synthesize plain text and return the audio with estimated word timings
+pub async fn tts_captioned(
+    State((aira, _semaphore)): State<(SharedAira, &'static Semaphore)>,
+    Json(req): Json<TtsRequest>,
+) -> impl IntoResponse {
+    // Breaks and rate changes would need their own alignment; captions are for plain text
+    if ssml::has_markup(&req.text) {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Captioned speech takes plain text, not SSML",
+        )
+            .into_response();
+    }
+    let tts_engine = match loaded_tts(&aira).await {
+        Ok(tts_engine) => tts_engine,
+        Err(response) => return response,
+    };
+
+    let sample_rate = match output_sample_rate(req.sample_rate, tts_engine.sample_rate()) {
+        Ok(sample_rate) => sample_rate,
+        Err(response) => return response,
+    };
+    let volume = req.volume.unwrap_or(tts_engine.volume());
+    let channels = req.channels.map_or(output_channels(), supported_channels);
+    let result = tokio::task::spawn_blocking(move || {
+        let (samples, words) = tts_engine.synthesize_captioned(&req.text)?;
+        // Timings are in seconds, so they survive the resample unchanged
+        let mut samples = resample(samples, tts_engine.sample_rate(), sample_rate);
+        apply_gain(&mut samples, volume);
+        Ok::<_, anyhow::Error>((samples, words))
+    })
+    .await;
+
+    match result {
+        Ok(Ok((samples, _))) if samples.is_empty() => StatusCode::NO_CONTENT.into_response(),
+        Ok(Ok((samples, words))) => {
+            let duration_secs = samples.len() as f32 / sample_rate as f32;
+            match create_wav(samples, sample_rate, channels) {
+                Ok(wav_data) => Json(CaptionedSpeechResponse {
+                    audio: general_purpose::STANDARD.encode(wav_data),
+                    sample_rate,
+                    duration_secs,
+                    words,
+                })
+                .into_response(),
+                Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+            }
+        }
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Serialize)]
+pub struct SpeedResponse {
+    pub speed: f32,
+}
+
+// Change the speaking speed of all later synthesis, for the lifetime of the process
+pub async fn set_speed(
+    State((aira, _semaphore)): State<(SharedAira, &'static Semaphore)>,
+    Json(req): Json<SpeedRequest>,
+) -> impl IntoResponse {
+    let mut tts_engine = match loaded_tts(&aira).await {
+        Ok(tts_engine) => tts_engine,
+        Err(response) => return response,
+    };
+
+    // Every clone shares the voice's speed, so this one can change it without holding Aira;
+    // the change waits for in-progress synthesis, so it runs off the async workers
+    match run_blocking(move || tts_engine.set_speed(req.speed)).await {
+        Ok(speed) => Json(SpeedResponse { speed }).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+// 16-bit WAV of mono `samples`, duplicated onto each of `channels`
+fn create_wav(samples: Vec<f32>, sample_rate: u32, channels: u16) -> Result<Vec<u8>> {
+    let samples = upmix(samples, channels);
     let spec = WavSpec {
-        channels: 1,
-        sample_rate: 22050,
+        channels,
+        sample_rate,
         bits_per_sample: 16,
         sample_format: SampleFormat::Int,
     };
@@ -65,3 +323,52 @@ fn create_wav(samples: Vec<f32>) -> Result<Vec<u8>> {
     writer.finalize()?;
     Ok(cursor.into_inner())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::WavReader;
+
+    #[test]
+    fn test_stereo_wav_interleaves_the_mono_source() {
+        let mono = vec![0.0, 0.5, -0.5];
+        let wav = create_wav(mono.clone(), 22_050, 2).unwrap();
+
+        let mut reader = WavReader::new(Cursor::new(wav)).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        let samples: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        assert_eq!(samples.len(), mono.len() * 2);
+        // Each frame carries the same mono sample on left and right
+        assert_eq!(samples, vec![0, 0, 16383, 16383, -16383, -16383]);
+        assert_eq!(supported_channels(6), 2);
+    }
+
+    #[test]
+    fn test_output_rate_is_validated() {
+        assert_eq!(output_sample_rate(None, 22_050).unwrap(), 22_050);
+        assert_eq!(output_sample_rate(Some(16_000), 22_050).unwrap(), 16_000);
+        for rate in [0, 4_000, 192_000] {
+            let response = output_sample_rate(Some(rate), 22_050).unwrap_err();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", rate);
+        }
+    }
+
+    #[test]
+    fn test_timing_reports_synthesis_time_and_audio_length() {
+        let (samples, timing) = SynthesisTiming::measure(22_050, || {
+            std::thread::sleep(Duration::from_millis(5));
+            Ok(vec![0.0; 33_075])
+        })
+        .unwrap();
+
+        assert_eq!(samples.len(), 33_075);
+        assert!(timing.synthesis >= Duration::from_millis(5));
+        // 33075 samples at 22.05 kHz is a second and a half of speech
+        assert_eq!(timing.audio, Duration::from_millis(1500));
+        assert!(timing.real_time_factor() > 0.0);
+
+        let headers = timing.headers();
+        assert_eq!(headers[1], ("x-audio-duration-ms", "1500.0".to_string()));
+        assert!(headers[0].1.parse::<f64>().unwrap() > 0.0);
+    }
+}
@@ -0,0 +1,83 @@
+use axum::{
+    Router,
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+// Require `Authorization: Bearer <token>` on every route of `router`
+// Without a configured token the routes stay open, as before
+pub fn protect<S>(router: Router<S>, token: Option<&Arc<str>>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    match token {
+        Some(token) => {
+            router.route_layer(middleware::from_fn_with_state(token.clone(), require_token))
+        }
+        None => router,
+    }
+}
+
+async fn require_token(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    if bearer_matches(request.headers(), &token) {
+        next.run(request).await
+    } else {
+        (StatusCode::UNAUTHORIZED, "Missing or invalid bearer token").into_response()
+    }
+}
+
+fn bearer_matches(headers: &HeaderMap, token: &str) -> bool {
+    let Some(presented) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+
+    // Compare every byte so the response time doesn't reveal how much of the token matched
+    presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::post};
+    use tower::ServiceExt;
+
+    async fn status_for(router: &Router, authorization: Option<&str>) -> StatusCode {
+        let mut request = Request::post("/api/session/clear");
+        if let Some(value) = authorization {
+            request = request.header(header::AUTHORIZATION, value);
+        }
+        let request = request.body(Body::empty()).unwrap();
+        router.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_guarded_endpoint_requires_configured_token() {
+        let token: Arc<str> = Arc::from("s3cret");
+        let router = protect(
+            Router::new().route("/api/session/clear", post(|| async { "cleared" })),
+            Some(&token),
+        );
+
+        assert_eq!(status_for(&router, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status_for(&router, Some("Bearer wrong!")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status_for(&router, Some("Bearer s3cret")).await,
+            StatusCode::OK
+        );
+    }
+}
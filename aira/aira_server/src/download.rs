@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Download a missing model if `<PREFIX>_URL` is set
+// The file is verified against `<PREFIX>_SHA256` (when set) before it is moved into place
+pub async fn ensure_model(path: &Path, env_prefix: &str) -> Result<()> {
+    if path.exists() {
+        return Ok(());
+    }
+
+    let Ok(url) = env::var(format!("{}_URL", env_prefix)) else {
+        return Ok(());
+    };
+    let expected_sha256 = env::var(format!("{}_SHA256", env_prefix)).ok();
+
+    println!("⬇️  Downloading {} from {}", path.display(), url);
+    // Say so before the download, so it's seen even if the download is interrupted
+    if expected_sha256.is_none() {
+        eprintln!(
+            "⚠️  {}_SHA256 is not set, so {} will be used without checksum verification",
+            env_prefix,
+            path.display()
+        );
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Download next to the target so a partial file never looks like a model
+    let partial_path = partial_path(path);
+    download(&url, &partial_path).await?;
+
+    if let Some(expected) = &expected_sha256 {
+        verify_sha256(&partial_path, expected)?;
+    }
+
+    std::fs::rename(&partial_path, path)?;
+    let verified = if expected_sha256.is_some() {
+        ""
+    } else {
+        " (unverified)"
+    };
+    println!("✅ Downloaded {}{}", path.display(), verified);
+    Ok(())
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+// Stream the response body to disk while printing a progress bar
+async fn download(url: &str, destination: &Path) -> Result<()> {
+    let mut response = reqwest::get(url)
+        .await
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("failed to download {}", url))?;
+
+    let total = response.content_length();
+    let mut file = std::fs::File::create(destination)?;
+    let mut downloaded: u64 = 0;
+
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk)?;
+        downloaded += chunk.len() as u64;
+        print_progress(downloaded, total);
+    }
+    println!();
+
+    file.flush()?;
+    Ok(())
+}
+
+fn print_progress(downloaded: u64, total: Option<u64>) {
+    let mb = downloaded as f64 / 1_048_576.0;
+    match total {
+        Some(total) if total > 0 => {
+            let fraction = (downloaded as f64 / total as f64).min(1.0);
+            let filled = (fraction * 30.0) as usize;
+            print!(
+                "\r   [{}{}] {:.0}% ({:.1} MB)",
+                "█".repeat(filled),
+                "░".repeat(30 - filled),
+                fraction * 100.0,
+                mb
+            );
+        }
+        _ => print!("\r   {:.1} MB", mb),
+    }
+    let _ = std::io::stdout().flush();
+}
+
+// Check the file's SHA-256 against a hex digest, deleting the file on mismatch
+pub fn verify_sha256(path: &Path, expected_hex: &str) -> Result<()> {
+    let actual = sha256_file(path)?;
+    if actual.eq_ignore_ascii_case(expected_hex.trim()) {
+        return Ok(());
+    }
+
+    let _ = std::fs::remove_file(path);
+    Err(anyhow::anyhow!(
+        "checksum mismatch for {}: expected {}, got {}",
+        path.display(),
+        expected_hex.trim(),
+        actual
+    ))
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // SHA-256 of "hello world"
+    const HELLO_SHA256: &str = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = env::temp_dir().join(format!("aira_{}_{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_checksum_match_accepts_file() {
+        let path = temp_file("match.bin", b"hello world");
+        assert!(verify_sha256(&path, HELLO_SHA256).is_ok());
+        assert!(path.exists());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_checksum_mismatch_rejects_file() {
+        let path = temp_file("mismatch.bin", b"hello world, tampered");
+        let err = verify_sha256(&path, HELLO_SHA256).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_partial_path_keeps_extension() {
+        let path = Path::new("models/ggml-small.en-q5_1.bin");
+        assert_eq!(
+            partial_path(path),
+            Path::new("models/ggml-small.en-q5_1.bin.part")
+        );
+    }
+}
@@ -1,25 +1,39 @@
-use aira_brain::{aira::Aira, llm::LlmEngine, stt::SttEngine, tts::TtsEngine};
+use aira_brain::{
+    aira::{Aira, EmotionPriority, Loader, ModelLoaders},
+    config::{DEFAULT_ASSISTANT_NAME, default_system_prompt, with_assistant_name},
+    language::Language,
+    llm::{GpuConfig, LlmEngine, ThreadConfig},
+    observer::JsonLinesObserver,
+    stt::{SttConfig, SttEngine},
+    tts::TtsEngine,
+};
 use axum::{
     Router,
     routing::{get, post},
 };
 use std::{
+    collections::BTreeMap,
     env,
     net::SocketAddr,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
+    time::Duration,
 };
 use tokio::net::TcpListener;
 use tokio::sync::Semaphore;
 use tower_http::cors::CorsLayer;
 
 mod api;
+mod auth;
+mod download;
 mod models;
+mod priority;
 mod states;
 
 // Global semaphore to limit concurrent AI operations and prevent memory corruption
 // Only allow 1 concurrent chat request at a time to prevent race conditions
-static CHAT_SEMAPHORE: Semaphore = Semaphore::const_new(1);
+const CHAT_CONCURRENCY: usize = 1;
+static CHAT_SEMAPHORE: Semaphore = Semaphore::const_new(CHAT_CONCURRENCY);
 
 // Get model path from environment variable or use default
 // Priority: 1. Environment variable, 2. Current working dir, 3. Executable directory
@@ -52,6 +66,8 @@ fn print_usage() {
     eprintln!("  --stt-model <PATH>     Path to Whisper STT model (default: models/ggml-small.en-q5_1.bin)");
     eprintln!("  --llm-model <PATH>     Path to LLM model (default: models/qwen2.5-3b-instruct-q4_0.gguf)");
     eprintln!("  --tts-model <PATH>     Path to TTS model config (default: tts_models/en_US-hfc_female-medium.onnx.json)");
+    eprintln!("  --no-stt               Run without speech-to-text (transcription returns 503)");
+    eprintln!("  --no-tts               Run without text-to-speech (chat streams text only)");
     eprintln!("  --help                 Show this help message");
     eprintln!();
     eprintln!("Environment Variables:");
@@ -59,10 +75,154 @@ fn print_usage() {
     eprintln!("  AIRA_LLM_MODEL         Override LLM model path");
     eprintln!("  AIRA_TTS_MODEL         Override TTS model path");
     eprintln!("  AIRA_SYSTEM_PROMPT     Custom system prompt for the AI");
+    eprintln!("  AIRA_PROMPT_MODES      JSON file of named system prompts, switchable via POST /api/mode");
+    eprintln!("  AIRA_ASSISTANT_NAME    Name the assistant introduces itself by; fills in {{name}} in configured prompts and replies (default: Aira)");
+    eprintln!("  AIRA_LLM_THREADS       CPU threads for token generation (default: available cores)");
+    eprintln!("  AIRA_LLM_BATCH_THREADS CPU threads for prompt processing (default: available cores)");
+    eprintln!("  AIRA_LLM_GPU_LAYERS    Model layers offloaded to the GPU (default: 99, 0 = CPU only)");
+    eprintln!("  AIRA_INFERENCE_NICENESS  Run LLM/STT inference at this niceness, 0 - 19, so audio is not starved (default: normal priority)");
+    eprintln!("  AIRA_LLM_CPU_FALLBACK  Retry on the CPU if the GPU load fails (default: true)");
+    eprintln!("  AIRA_LLM_TEMPERATURE   Default sampling temperature; requests may override it (default: 0.8)");
+    eprintln!("  AIRA_LLM_TIMEOUT_SECS  Cut replies off after this many seconds of generation (default: no limit)");
+    eprintln!("  AIRA_MAX_INPUT_FRACTION  Share of the context a user message may take (default: 0.5)");
+    eprintln!("  AIRA_SSE_KEEPALIVE_SECS  Seconds between keep-alive pings on chat streams (default: 15)");
+    eprintln!("  AIRA_EXCHANGE_LOG      Append every completed exchange to this JSON-lines file");
+    eprintln!("  AIRA_DEBUG_PROMPTS     Log rendered LLM prompts and serve POST /api/debug/prompt (default: false)");
+    eprintln!("  AIRA_RESPONSE_CACHE    Greedy (temperature 0) replies kept to answer identical requests instantly (default: 0, off)");
+    eprintln!("  AIRA_HISTORY_WINDOW    Prompt with at most this many recent turns, even if more fit (default: no limit)");
+    eprintln!("  AIRA_MAX_TURNS         Reset the conversation after this many exchanges, e.g. for kiosks (default: no limit)");
+    eprintln!("  AIRA_SUMMARIZE_ON_RESET  Start a reset conversation from a summary of the old one (default: false)");
+    eprintln!("  AIRA_AUTO_SUMMARIZE    Summarize old history instead of dropping it near the context limit (default: false)");
+    eprintln!("  AIRA_STT_SUPPRESS_NON_SPEECH  Have Whisper suppress non-speech tokens while decoding (default: true)");
+    eprintln!("  AIRA_STT_STRIP_ANNOTATIONS  Remove non-speech like [BLANK_AUDIO] from transcripts (default: true)");
+    eprintln!("  AIRA_STT_TEMPERATURE   Initial Whisper decoding temperature (default: 0.0)");
+    eprintln!("  AIRA_STT_BEST_OF       Candidates per token on Whisper's greedy path; higher is slower but more accurate (default: 1)");
+    eprintln!("  AIRA_STT_PRE_EMPHASIS  Pre-emphasis coefficient for mic audio before transcription, e.g. 0.97 (default: off)");
+    eprintln!("  AIRA_STT_TEMPERATURE_INC  Temperature step for Whisper's decode fallback (default: 0.2, 0 disables)");
+    eprintln!("  AIRA_API_TOKEN         Require `Authorization: Bearer <token>` on admin endpoints (default: open)");
+    eprintln!("                         Build the frontend with VITE_AIRA_API_TOKEN set to the same token");
+    eprintln!("  AIRA_API_TOKEN_ALL     Also require the token on chat, speech and camera endpoints (default: false)");
+    eprintln!("  AIRA_STT_MODEL_URL     Download the STT model from this URL if it is missing");
+    eprintln!("  AIRA_LLM_MODEL_URL     Download the LLM model from this URL if it is missing");
+    eprintln!("  AIRA_TTS_MODEL_URL     Download the Piper .onnx voice from this URL if it is missing");
+    eprintln!("  AIRA_TTS_CONFIG_URL    Download the Piper .onnx.json config from this URL if it is missing");
+    eprintln!("  AIRA_*_SHA256          Expected SHA-256 of the matching download (e.g. AIRA_LLM_MODEL_SHA256)");
+    eprintln!("  AIRA_TTS_CROSSFADE_MS  Crossfade between synthesized speech chunks (default: 10, 0 disables)");
+    eprintln!("  AIRA_TTS_MAX_CHARS     Longest text synthesized in one Piper call; longer text is split by sentence (default: 500)");
+    eprintln!("  AIRA_TTS_LINE_PAUSE_MS Silence between spoken lines and list items; \"off\" runs lines together (default: 300)");
+    eprintln!("  AIRA_TTS_VOLUME        Output gain for synthesized speech, 0.0 - 4.0 (default: 1.0)");
+    eprintln!("  AIRA_TTS_CHANNELS      Channels of synthesized audio: 1 (mono) or 2 (stereo, duplicated) (default: 1)");
+    eprintln!("  AIRA_TTS_SPEED         Speaking speed multiplier, 0.5 - 2.0 (default: 1.0)");
+    eprintln!("  AIRA_INTER_SENTENCE_PAUSE_MS  Silence after each spoken chat sentence (default: 150, 0 disables)");
+    eprintln!("  AIRA_WORKER_THREADS    Async threads that route requests; heavy work runs on the blocking pool (default: 2)");
+    eprintln!("  AIRA_BLOCKING_THREADS  Most threads for inference, synthesis and audio decoding at once (default: 512)");
+    eprintln!("  AIRA_TTS_CONCURRENCY   Chat sentences synthesized at once; audio is still sent in order (default: 1)");
+    eprintln!("  AIRA_MAX_REPEATED_PUNCTUATION  Longest run of \"!\" or \"?\" kept in replies, and of \".\" beyond an ellipsis; \"off\" keeps them all (default: 1)");
+    eprintln!("  AIRA_FAST_FIRST_SENTENCE  Start chat replies with a quick greedy first sentence to speak sooner (default: false)");
+    eprintln!("  AIRA_RESPONSE_LANGUAGE Language chat replies are written in, e.g. \"French\" or \"fr\" (default: the model's choice)");
+    eprintln!("  AIRA_TTS_VOICES        Comma-separated extra Piper voice configs, used when a reply's language needs one");
+    eprintln!("  AIRA_GREETING          Opening line returned by /api/session/greet before the first message (default: none)");
+    eprintln!("  AIRA_THINKING_FILLER   Speak this (e.g. \"Hmm...\") while the first sentence is generated (default: off)");
+    eprintln!("  AIRA_EMOTION_PRIORITY  Order the dominant emotion is picked in, e.g. \"stress,fatigue\" (default: fatigue,stress,positive,engagement)");
+    eprintln!("  AIRA_EMOTION_TTL_SECS  Stop steering replies with a camera reading older than this (default: no limit)");
+    eprintln!("  AIRA_EMOTION_CHANGE_THRESHOLD  Metric drift that refreshes the emotional context in the prompt (default: 0.1)");
+    eprintln!("  AIRA_CAMERA_FRESHNESS_SECS  Camera readings older than this count as inactive (default: 10)");
+    eprintln!("  AIRA_EMOTION_LOG_INTERVAL  Minimum seconds between emotion logs (default: 5)");
+    eprintln!("  AIRA_EMOTION_LOG_COMPACT   Log emotion changes as a single line (true/false)");
+    eprintln!("  AIRA_EMOTION_STATE_PATH    Save the smoothed emotion baseline here and restore it on startup");
+    eprintln!("  AIRA_EMOTION_NEUTRAL       Resting fatigue,engagement,stress,positive_affect levels used without a face (default: 0.5,0.5,0.5,0.5)");
+    eprintln!("  AIRA_EMOTION_STATE_TTL     Ignore saved emotion baselines older than this many seconds (default: 3600)");
+    eprintln!("  AIRA_STT_AUDIO_FIELDS  Comma-separated multipart field names accepted for uploaded audio (default: audio,file,data)");
+    eprintln!("  AIRA_FFMPEG_PATH       FFmpeg binary used to decode browser audio (default: ffmpeg)");
+    eprintln!("  AIRA_FFMPEG_ARGS       FFmpeg argument template with {{input}} and {{output}} placeholders");
+}
+
+// Load Whisper with the AIRA_STT_* settings
+fn load_stt(path: &Path) -> anyhow::Result<SttEngine> {
+    let mut stt_config = SttConfig::default();
+    if let Ok(value) = env::var("AIRA_STT_SUPPRESS_NON_SPEECH") {
+        stt_config.suppress_non_speech = value == "1" || value.eq_ignore_ascii_case("true");
+    }
+    if let Ok(value) = env::var("AIRA_STT_STRIP_ANNOTATIONS") {
+        stt_config.strip_annotations = value == "1" || value.eq_ignore_ascii_case("true");
+    }
+    if let Some(inc) = env::var("AIRA_STT_TEMPERATURE_INC")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        stt_config.temperature_inc = inc;
+    }
+    if let Some(temperature) = env::var("AIRA_STT_TEMPERATURE").ok().and_then(|v| v.parse().ok()) {
+        stt_config.temperature = temperature;
+    }
+    if let Some(best_of) = env::var("AIRA_STT_BEST_OF").ok().and_then(|v| v.parse().ok()) {
+        stt_config.best_of = best_of;
+    }
+    if let Some(coefficient) = env::var("AIRA_STT_PRE_EMPHASIS").ok().and_then(|v| v.parse().ok()) {
+        stt_config.pre_emphasis = Some(coefficient);
+    }
+    SttEngine::load_with_config(path.to_str().unwrap(), stt_config)
 }
 
-#[tokio::main(flavor = "multi_thread", worker_threads = 2)]
-async fn main() -> anyhow::Result<()> {
+// Load the Piper voice with the AIRA_TTS_* settings
+fn load_tts(path: &Path) -> anyhow::Result<TtsEngine> {
+    let mut tts = TtsEngine::load(path.to_str().unwrap())?;
+    if let Some(ms) = env::var("AIRA_TTS_CROSSFADE_MS").ok().and_then(|v| v.parse().ok()) {
+        tts.set_crossfade_ms(ms);
+    }
+    if let Some(max) = env::var("AIRA_TTS_MAX_CHARS").ok().and_then(|v| v.parse().ok()) {
+        tts.set_max_text_chars(max);
+    }
+    if let Ok(value) = env::var("AIRA_TTS_LINE_PAUSE_MS") {
+        if value.eq_ignore_ascii_case("off") {
+            tts.set_line_pause_ms(None);
+        } else if let Ok(ms) = value.parse() {
+            tts.set_line_pause_ms(Some(ms));
+        }
+    }
+    if let Some(volume) = env::var("AIRA_TTS_VOLUME").ok().and_then(|v| v.parse().ok()) {
+        tts.set_volume(volume);
+    }
+    if let Some(channels) = env::var("AIRA_TTS_CHANNELS").ok().and_then(|v| v.parse().ok()) {
+        api::tts::set_output_channels(channels);
+    }
+    if let Some(speed) = env::var("AIRA_TTS_SPEED").ok().and_then(|v| v.parse().ok()) {
+        tts.set_speed(speed)?;
+    }
+    Ok(tts)
+}
+
+// Read `{"mode name": "system prompt", ...}` from `path`
+fn load_prompt_modes(path: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read prompt modes from {}: {}", path, e))?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+// Async workers only route requests; inference, synthesis and decoding run on the blocking pool
+const DEFAULT_WORKER_THREADS: usize = 2;
+// Tokio's own default; each in-flight inference, synthesis or decode holds one thread
+const DEFAULT_BLOCKING_THREADS: usize = 512;
+
+// A positive thread count from `key`, or `default` when unset or invalid
+fn thread_count(key: &str, default: usize) -> usize {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&threads| threads > 0)
+        .unwrap_or(default)
+}
+
+fn main() -> anyhow::Result<()> {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(thread_count("AIRA_WORKER_THREADS", DEFAULT_WORKER_THREADS))
+        .max_blocking_threads(thread_count("AIRA_BLOCKING_THREADS", DEFAULT_BLOCKING_THREADS))
+        .enable_all()
+        .build()?
+        .block_on(serve())
+}
+
+async fn serve() -> anyhow::Result<()> {
     // Parse command line arguments
     let args: Vec<String> = env::args().collect();
     
@@ -76,6 +236,8 @@ async fn main() -> anyhow::Result<()> {
     let mut stt_path: Option<String> = None;
     let mut llm_path: Option<String> = None;
     let mut tts_path: Option<String> = None;
+    let mut enable_stt = true;
+    let mut enable_tts = true;
     
     let mut i = 1;
     while i < args.len() {
@@ -98,6 +260,8 @@ async fn main() -> anyhow::Result<()> {
                     tts_path = Some(args[i].clone());
                 }
             }
+            "--no-stt" => enable_stt = false,
+            "--no-tts" => enable_tts = false,
             _ => {
                 eprintln!("Unknown argument: {}", args[i]);
                 print_usage();
@@ -122,15 +286,35 @@ async fn main() -> anyhow::Result<()> {
     
     // Print which models are being loaded
     println!("📦 Model paths:");
-    println!("   STT: {}", stt_model_path.display());
+    if enable_stt {
+        println!("   STT: {}", stt_model_path.display());
+    } else {
+        println!("   STT: disabled");
+    }
     println!("   LLM: {}", llm_model_path.display());
-    println!("   TTS: {}", tts_model_path.display());
+    if enable_tts {
+        println!("   TTS: {}", tts_model_path.display());
+    } else {
+        println!("   TTS: disabled");
+    }
+    
+    // Download missing models when a URL is configured
+    if enable_stt {
+        download::ensure_model(&stt_model_path, "AIRA_STT_MODEL").await?;
+    }
+    download::ensure_model(&llm_model_path, "AIRA_LLM_MODEL").await?;
+    if enable_tts {
+        download::ensure_model(&tts_model_path, "AIRA_TTS_CONFIG").await?;
+        // Piper needs the .onnx voice next to its .onnx.json config
+        download::ensure_model(&tts_model_path.with_extension(""), "AIRA_TTS_MODEL").await?;
+    }
     
     // Check if models exist
-    if !stt_model_path.exists() {
+    if enable_stt && !stt_model_path.exists() {
         eprintln!("❌ Error: STT model not found at: {}", stt_model_path.display());
         eprintln!("   Download it from: https://huggingface.co/ggerganov/whisper.cpp");
         eprintln!("   Or set AIRA_STT_MODEL environment variable");
+        eprintln!("   Or set AIRA_STT_MODEL_URL to download it automatically");
         eprintln!("   Or use --stt-model <path> argument");
         return Err(anyhow::anyhow!("STT model not found"));
     }
@@ -139,42 +323,224 @@ async fn main() -> anyhow::Result<()> {
         eprintln!("❌ Error: LLM model not found at: {}", llm_model_path.display());
         eprintln!("   Download it from: https://huggingface.co/Qwen/Qwen2.5-3B-Instruct-GGUF");
         eprintln!("   Or set AIRA_LLM_MODEL environment variable");
+        eprintln!("   Or set AIRA_LLM_MODEL_URL to download it automatically");
         eprintln!("   Or use --llm-model <path> argument");
         return Err(anyhow::anyhow!("LLM model not found"));
     }
     
-    if !tts_model_path.exists() {
+    if enable_tts && !tts_model_path.exists() {
         eprintln!("❌ Error: TTS model not found at: {}", tts_model_path.display());
         eprintln!("   Download it from: https://huggingface.co/rhasspy/piper-voices");
         eprintln!("   Or set AIRA_TTS_MODEL environment variable");
+        eprintln!("   Or set AIRA_TTS_CONFIG_URL (and AIRA_TTS_MODEL_URL for the .onnx voice) to download it automatically");
         eprintln!("   Or use --tts-model <path> argument");
         return Err(anyhow::anyhow!("TTS model not found"));
     }
     
-    // Load models
-    println!("🎤 Loading STT model...");
-    let stt = SttEngine::load(stt_model_path.to_str().unwrap())?;
-    
     println!("🧠 Loading LLM model...");
+    let assistant_name = env::var("AIRA_ASSISTANT_NAME")
+        .unwrap_or_else(|_| DEFAULT_ASSISTANT_NAME.to_string());
     let system_prompt = env::var("AIRA_SYSTEM_PROMPT")
-        .unwrap_or_else(|_| "<|im_start|>system\nYou are Aira, a warm, empathetic AI assistant.<|im_end|>\n".to_string());
-    let llm = LlmEngine::load(llm_model_path.to_str().unwrap(), &system_prompt)?;
+        .map(|prompt| with_assistant_name(&prompt, &assistant_name))
+        .unwrap_or_else(|_| default_system_prompt(&assistant_name));
+    let mut threads = ThreadConfig::detect();
+    if let Some(n) = env::var("AIRA_LLM_THREADS").ok().and_then(|v| v.parse().ok()) {
+        threads.n_threads = n;
+    }
+    if let Some(n) = env::var("AIRA_LLM_BATCH_THREADS").ok().and_then(|v| v.parse().ok()) {
+        threads.n_threads_batch = n;
+    }
+    let mut gpu = GpuConfig::default();
+    if let Some(n) = env::var("AIRA_LLM_GPU_LAYERS").ok().and_then(|v| v.parse().ok()) {
+        gpu.n_gpu_layers = n;
+    }
+    if let Ok(value) = env::var("AIRA_LLM_CPU_FALLBACK") {
+        gpu.cpu_fallback = value == "1" || value.eq_ignore_ascii_case("true");
+    }
+    let mut llm = LlmEngine::load_with_options(llm_model_path.to_str().unwrap(), &system_prompt, threads, gpu)?;
+    api::config::configure_llm(&mut llm, |key| env::var(key).ok());
     
-    println!("🔊 Loading TTS model...");
-    let tts = TtsEngine::load(tts_model_path.to_str().unwrap())?;
+    if let Ok(path) = env::var("AIRA_PROMPT_MODES") {
+        for (name, prompt) in load_prompt_modes(&path)? {
+            llm.add_mode(&name, &with_assistant_name(&prompt, &assistant_name));
+        }
+        println!("🎭 Prompt modes: {}", llm.mode_names().join(", "));
+    }
     
-    let aira = Arc::new(Mutex::new(Aira::new(stt, llm, tts)));
+    let mut builder = Aira::builder(llm);
     
-    let app = Router::new()
-        .route("/health", get(api::health))
+    if enable_stt {
+        println!("🎤 Loading STT model...");
+        builder = builder.stt(load_stt(&stt_model_path)?);
+    }
+    
+    if enable_tts {
+        println!("🔊 Loading TTS model...");
+        builder = builder.tts(load_tts(&tts_model_path)?);
+    }
+    
+    let llm_model_display = llm_model_path.display().to_string();
+    let stt_model_display = stt_model_path.display().to_string();
+    let tts_model_display = tts_model_path.display().to_string();
+    
+    // Let POST /api/models/unload free memory; models come back on the next request
+    let llm_path = llm_model_path.clone();
+    builder = builder.loaders(ModelLoaders {
+        llm: Box::new(move || LlmEngine::load_backend(llm_path.to_str().unwrap(), threads, gpu)),
+        stt: enable_stt.then(|| Box::new(move || load_stt(&stt_model_path)) as Loader<SttEngine>),
+        tts: enable_tts.then(|| Box::new(move || load_tts(&tts_model_path)) as Loader<TtsEngine>),
+    });
+    
+    if let Ok(greeting) = env::var("AIRA_GREETING") {
+        builder = builder.greeting(with_assistant_name(&greeting, &assistant_name));
+    }
+    
+    if let Ok(path) = env::var("AIRA_EXCHANGE_LOG") {
+        builder = builder.observer(JsonLinesObserver::create(&path)?);
+        println!("📝 Logging exchanges to {}", path);
+    }
+    
+    let mut aira = builder.build();
+    if let Some(threshold) = env::var("AIRA_EMOTION_CHANGE_THRESHOLD").ok().and_then(|v| v.parse().ok()) {
+        aira.set_emotion_change_threshold(threshold);
+    }
+    if let Some(ttl) = env::var("AIRA_EMOTION_TTL_SECS").ok().and_then(|v| v.parse().ok()) {
+        aira.set_emotion_ttl(Some(ttl));
+    }
+    if let Some(max_turns) = env::var("AIRA_MAX_TURNS").ok().and_then(|v| v.parse().ok()) {
+        aira.set_max_turns(Some(max_turns));
+    }
+    if let Ok(value) = env::var("AIRA_SUMMARIZE_ON_RESET") {
+        aira.set_summarize_on_reset(value == "1" || value.eq_ignore_ascii_case("true"));
+    }
+    if let Ok(value) = env::var("AIRA_EMOTION_PRIORITY") {
+        match EmotionPriority::parse(&value) {
+            Ok(priority) => {
+                aira.set_emotion_priority(priority);
+                api::camera::set_emotion_priority(priority);
+            }
+            Err(e) => eprintln!("Ignoring AIRA_EMOTION_PRIORITY: {}", e),
+        }
+    }
+    if let Some(ms) = env::var("AIRA_INTER_SENTENCE_PAUSE_MS").ok().and_then(|v| v.parse().ok()) {
+        api::chat::set_inter_sentence_pause(Duration::from_millis(ms));
+    }
+    if let Some(concurrency) = env::var("AIRA_TTS_CONCURRENCY").ok().and_then(|v| v.parse().ok()) {
+        api::chat::set_tts_concurrency(concurrency);
+    }
+    if let Some(language) = env::var("AIRA_RESPONSE_LANGUAGE").ok().and_then(|v| Language::parse(&v)) {
+        println!("🌍 Replying in {}", language.name);
+        api::chat::set_response_language(language);
+    }
+    if let Ok(paths) = env::var("AIRA_TTS_VOICES")
+        && enable_tts
+    {
+        let voices = paths
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(|path| load_tts(Path::new(path)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        println!("🗣️ Loaded {} extra voices", voices.len());
+        api::tts::set_extra_voices(voices);
+    }
+    if let Ok(value) = env::var("AIRA_STT_AUDIO_FIELDS") {
+        let fields: Vec<String> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect();
+        if !fields.is_empty() {
+            api::stt::set_audio_fields(fields);
+        }
+    }
+    if let Ok(value) = env::var("AIRA_MAX_REPEATED_PUNCTUATION") {
+        if value.eq_ignore_ascii_case("off") {
+            api::chat::set_punctuation_run_limit(None);
+        } else if let Ok(limit) = value.parse() {
+            api::chat::set_punctuation_run_limit(Some(limit));
+        }
+    }
+    if let Ok(value) = env::var("AIRA_FAST_FIRST_SENTENCE") {
+        api::chat::set_fast_first_sentence(value == "1" || value.eq_ignore_ascii_case("true"));
+    }
+    if let Ok(text) = env::var("AIRA_THINKING_FILLER").map(|text| with_assistant_name(&text, &assistant_name))
+        && let Some(tts) = aira.get_tts()
+    {
+        api::chat::prepare_thinking_filler(&tts, &text)?;
+        println!("💭 Thinking filler: {:?}", text);
+    }
+    let aira = Arc::new(Mutex::new(aira));
+    if let Some(niceness) = env::var("AIRA_INFERENCE_NICENESS").ok().and_then(|v| v.parse().ok()) {
+        priority::set_inference_niceness(niceness);
+        println!("🐢 Inference threads run at niceness {}", niceness);
+    }
+    
+    // Record what was resolved for GET /api/config
+    let download_vars = ["AIRA_STT_MODEL_URL", "AIRA_LLM_MODEL_URL", "AIRA_TTS_MODEL_URL", "AIRA_TTS_CONFIG_URL"];
+    let downloads = download_vars
+        .into_iter()
+        .filter_map(|key| Some((key.to_string(), api::config::redact_url(&env::var(key).ok()?))))
+        .collect();
+    api::config::set_startup_config(api::config::StartupConfig {
+        llm_model: llm_model_display,
+        stt_model: enable_stt.then_some(stt_model_display),
+        tts_model: enable_tts.then_some(tts_model_display),
+        assistant_name,
+        downloads,
+        exchange_log: env::var("AIRA_EXCHANGE_LOG").ok(),
+        chat_concurrency: CHAT_CONCURRENCY,
+        worker_threads: thread_count("AIRA_WORKER_THREADS", DEFAULT_WORKER_THREADS),
+        blocking_threads: thread_count("AIRA_BLOCKING_THREADS", DEFAULT_BLOCKING_THREADS),
+        inference_niceness: priority::inference_niceness(),
+        fast_first_sentence: api::chat::fast_first_sentence(),
+        cors: "permissive".to_string(),
+    });
+    
+    // Conversation endpoints; behind the token only with AIRA_API_TOKEN_ALL
+    let usage = Router::new()
         .route("/chat", post(api::chat))
+        .route("/api/chat/regenerate", post(api::regenerate))
+        .route("/api/chat/continue", post(api::continue_reply))
+        .route("/api/chat/stop-audio", post(api::stop_audio))
+        .route("/api/session/greet", post(api::greet_session))
+        .route("/api/estimate", post(api::estimate))
         .route("/api/tts", post(api::tts))
+        .route("/api/tts/captioned", post(api::tts_captioned))
         .route("/api/stt/transcribe", post(api::transcribe_audio))
+        .route("/api/stt/transcribe/stream", post(api::transcribe_audio_stream))
         .route("/api/camera/features", post(api::process_camera_features))
         .route("/api/camera/status", get(api::get_camera_status))
         .route("/api/emotion/current", get(api::get_emotion_details))
+        .route("/api/alerts", get(api::get_alert));
+    
+    // Endpoints that change or reveal server state; behind the token whenever one is set
+    let admin = Router::new()
+        .route("/api/config", get(api::get_config))
+        .route("/api/debug/prompt", post(api::debug_prompt))
+        .route("/api/tts/speed", post(api::set_speed))
+        .route("/api/models/unload", post(api::unload_models))
+        .route("/api/models/reload", post(api::reload_models))
+        .route("/api/emotion/enabled", post(api::set_emotion_enabled))
+        .route("/api/emotion/alpha", get(api::get_emotion_alpha).put(api::set_emotion_alpha))
         .route("/api/test-stress", post(api::test_stress))
-        .route("/api/alerts", get(api::get_alert))
+        .route("/api/session/clear", post(api::clear_session))
+        .route("/api/session/summarize", post(api::summarize_session))
+        .route("/api/session/export", get(api::export_session))
+        .route("/api/mode", post(api::set_mode));
+    
+    let api_token: Option<Arc<str>> = env::var("AIRA_API_TOKEN").ok().filter(|t| !t.is_empty()).map(Arc::from);
+    let guard_all = env::var("AIRA_API_TOKEN_ALL").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+    if api_token.is_some() {
+        println!("🔒 Admin endpoints{} require the API token", if guard_all { " and chat" } else { "" });
+    }
+    let usage = if guard_all { auth::protect(usage, api_token.as_ref()) } else { usage };
+    
+    let app = Router::new()
+        .route("/health", get(api::health))
+        .merge(usage)
+        .merge(auth::protect(admin, api_token.as_ref()))
         .with_state((aira, &CHAT_SEMAPHORE))
         .layer(CorsLayer::permissive());
     
@@ -1,13 +1,96 @@
+use aira_brain::llm::{ChatMessage, ResponseLength};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
 pub struct ChatRequest {
     pub message: String,
+    // Wanted reply length; guessed from the message when omitted
+    #[serde(default)]
+    pub length: Option<ResponseLength>,
+    // Sampling temperature for this message only (0 = deterministic)
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    // Output gain for this reply's audio; defaults to AIRA_TTS_VOLUME
+    #[serde(default)]
+    pub volume: Option<f32>,
+    // Prior conversation, replacing the server-side history (stateless mode)
+    #[serde(default)]
+    pub messages: Option<Vec<ChatMessage>>,
+    // How much text each streamed event carries
+    #[serde(default)]
+    pub stream_granularity: StreamGranularity,
+    // Send each sentence's audio in parts as it is synthesized, instead of one audio_complete
+    #[serde(default)]
+    pub audio_streaming: bool,
+    // 1 (mono) or 2 (stereo) for this reply's audio; defaults to AIRA_TTS_CHANNELS
+    #[serde(default)]
+    pub channels: Option<u16>,
+    // Generate and speak a quick greedy first sentence; defaults to AIRA_FAST_FIRST_SENTENCE
+    #[serde(default)]
+    pub fast_start: Option<bool>,
+    // Language to reply in, e.g. "French" or "fr"; defaults to AIRA_RESPONSE_LANGUAGE, "" for none
+    #[serde(default)]
+    pub response_language: Option<String>,
+    // Stop the reply after about this many words, at a word boundary
+    #[serde(default)]
+    pub max_words: Option<usize>,
+}
+
+// Unit of text per streamed event; coarser units mean fewer, larger events
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamGranularity {
+    // Every model token as it arrives
+    #[default]
+    Token,
+    // Whole words, emitted at whitespace
+    Word,
+    // Whole sentences, emitted at sentence-ending punctuation
+    Sentence,
+}
+
+// Body for POST /api/estimate
+#[derive(Deserialize)]
+pub struct EstimateRequest {
+    pub message: String,
+    #[serde(default)]
+    pub length: Option<ResponseLength>,
+}
+
+// Body for POST /api/debug/prompt
+#[derive(Deserialize)]
+pub struct PromptDebugRequest {
+    pub message: String,
+    #[serde(default)]
+    pub length: Option<ResponseLength>,
+}
+
+// Body for POST /api/mode
+#[derive(Deserialize)]
+pub struct ModeRequest {
+    pub mode: String,
 }
 
 #[derive(Deserialize)]
 pub struct TtsRequest {
+    // Plain text, or SSML-lite with <break> and <prosody rate> tags
     pub text: String,
+    // Output rate in Hz; defaults to the voice's native rate
+    #[serde(default)]
+    pub sample_rate: Option<u32>,
+    // Output gain (1.0 = unchanged); defaults to AIRA_TTS_VOLUME
+    #[serde(default)]
+    pub volume: Option<f32>,
+    // 1 (mono) or 2 (stereo); defaults to AIRA_TTS_CHANNELS
+    #[serde(default)]
+    pub channels: Option<u16>,
+}
+
+// Body for POST /api/tts/speed
+#[derive(Deserialize)]
+pub struct SpeedRequest {
+    // Speaking speed multiplier (1.0 = the voice's own pace)
+    pub speed: f32,
 }
 
 // Camera features sent from frontend for emotion detection
@@ -22,4 +105,71 @@ pub struct CameraFeatures {
     pub head_yaw: f32,
 }
 
+// POST /api/camera/features body: one frame, or several buffered frames in order
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum CameraFeaturesInput {
+    Single(CameraFeatures),
+    Batch(Vec<CameraFeatures>),
+}
+
+impl CameraFeaturesInput {
+    pub fn into_frames(self) -> Vec<CameraFeatures> {
+        match self {
+            CameraFeaturesInput::Single(features) => vec![features],
+            CameraFeaturesInput::Batch(frames) => frames,
+        }
+    }
+}
+
+// Query options for POST /api/camera/features
+#[derive(Deserialize, Default)]
+pub struct CameraFeaturesQuery {
+    // Also return the smoothed state after every frame
+    #[serde(default)]
+    pub per_frame: bool,
+    // How to render each state's timestamp in the response
+    #[serde(default)]
+    pub timestamp: TimestampFormat,
+}
+
+// Wire format of reading timestamps: unix seconds (default), ISO 8601 UTC, or left out
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampFormat {
+    #[default]
+    Unix,
+    Iso,
+    Omit,
+}
+
+// Body for POST /api/emotion/enabled
+#[derive(Deserialize)]
+pub struct EmotionToggleRequest {
+    pub enabled: bool,
+}
+
+// Body for PUT /api/emotion/alpha
+#[derive(Deserialize)]
+pub struct EmotionAlphaRequest {
+    // Weight of each new frame in the smoothed state, in (0.0, 1.0]
+    pub alpha: f32,
+}
+
+// Query options for GET /api/emotion/current
+#[derive(Deserialize, Default)]
+pub struct EmotionDetailsQuery {
+    // Return the unsmoothed reading from the latest camera frame
+    #[serde(default)]
+    pub raw: bool,
+}
+
+// Query options for GET /health
+#[derive(Deserialize, Default)]
+pub struct HealthQuery {
+    // Run a tiny synthesis and transcription instead of only answering
+    #[serde(default)]
+    pub deep: bool,
+}
+
 // EmotionalContext is available through aira_brain when needed
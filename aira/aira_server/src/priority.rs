@@ -0,0 +1,121 @@
+use crate::states::run_blocking;
+use std::io;
+use std::sync::OnceLock;
+
+// Niceness for threads running LLM/STT inference; unset keeps normal priority
+static INFERENCE_NICENESS: OnceLock<i32> = OnceLock::new();
+
+// Only ever lowered: a negative niceness would take CPU from the audio threads it protects
+pub fn set_inference_niceness(niceness: i32) {
+    let _ = INFERENCE_NICENESS.set(niceness.clamp(0, 19));
+}
+
+pub fn inference_niceness() -> Option<i32> {
+    INFERENCE_NICENESS.get().copied()
+}
+
+// Run blocking LLM/STT inference at the configured priority
+// An unprivileged process can't raise a thread's priority back, so lowered work gets a
+// thread of its own that ends with it; at normal priority the blocking pool is used
+pub async fn run_inference<F, T>(work: F) -> anyhow::Result<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    run_at_niceness(inference_niceness(), work).await
+}
+
+async fn run_at_niceness<F, T>(niceness: Option<i32>, work: F) -> anyhow::Result<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    if niceness.is_none() {
+        return run_blocking(move || Ok(work())).await;
+    }
+
+    let (result_tx, result_rx) = tokio::sync::oneshot::channel();
+    std::thread::Builder::new()
+        .name("aira-inference".to_string())
+        .spawn(move || {
+            apply_niceness(niceness, set_thread_niceness);
+            let _ = result_tx.send(work());
+        })?;
+    result_rx
+        .await
+        .map_err(|_| anyhow::anyhow!("Inference thread panicked"))
+}
+
+// Hand the configured niceness to `set`, so the OS call can be swapped out in tests
+fn apply_niceness(niceness: Option<i32>, set: impl FnOnce(i32) -> io::Result<()>) {
+    let Some(niceness) = niceness else {
+        return;
+    };
+    if let Err(e) = set(niceness) {
+        eprintln!(
+            "⚠️  Could not set inference thread niceness to {}: {}",
+            niceness, e
+        );
+    }
+}
+
+// Linux schedules threads individually, so a thread id targets only the calling thread
+#[cfg(target_os = "linux")]
+fn set_thread_niceness(niceness: i32) -> io::Result<()> {
+    // SAFETY: gettid and setpriority have no memory-safety preconditions
+    let result =
+        unsafe { libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, niceness) };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_thread_niceness(_niceness: i32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "per-thread niceness is only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configured_niceness_reaches_the_os_call() {
+        let mut seen = Vec::new();
+        apply_niceness(Some(10), |niceness| {
+            seen.push(niceness);
+            Ok(())
+        });
+        // Normal priority leaves the thread alone
+        apply_niceness(None, |niceness| {
+            seen.push(niceness);
+            Ok(())
+        });
+        // A refused change is logged, not propagated
+        apply_niceness(Some(5), |niceness| {
+            seen.push(niceness);
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        });
+        assert_eq!(seen, vec![10, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_lowered_inference_never_reuses_a_thread() {
+        let thread = || std::thread::current().id();
+        let first = run_at_niceness(Some(0), thread).await.unwrap();
+        let second = run_at_niceness(Some(0), thread).await.unwrap();
+        // Each lowered task ends with its thread, so no pool thread inherits the niceness
+        assert_ne!(first, second);
+        assert_ne!(first, thread());
+
+        let name = run_at_niceness(Some(0), || std::thread::current().name().map(String::from))
+            .await
+            .unwrap();
+        assert_eq!(name.as_deref(), Some("aira-inference"));
+    }
+}
@@ -1,4 +1,115 @@
 use aira_brain::aira::Aira;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::timeout;
 
 pub type SharedAira = Arc<Mutex<Aira>>;
+
+// Lock a mutex, recovering the data if an earlier holder panicked
+// A poisoned lock would otherwise make every later request panic as well
+pub fn lock_or_recover<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        eprintln!("⚠️  Recovering from a poisoned lock left by a panicked request");
+        poisoned.into_inner()
+    })
+}
+
+// Reload Aira's models if they were unloaded; other requests wait on the lock meanwhile
+// Runs on the blocking pool, as loading a model can take several seconds
+pub async fn ensure_loaded(aira: &SharedAira) -> anyhow::Result<()> {
+    let aira = aira.clone();
+    run_blocking(move || lock_or_recover(&aira).reload()).await
+}
+
+// Run CPU-heavy or blocking work (inference, decoding, subprocesses) on the blocking pool
+// The server has only a couple of async workers; work run inline on one stalls every request
+pub async fn run_blocking<F, T>(work: F) -> anyhow::Result<T>
+where
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(work).await?
+}
+
+// Wait for the chat semaphore, which a chat holds until its reply has been spoken
+// `respond` turns the failure message into the caller's own kind of response
+pub async fn acquire_permit<E>(
+    semaphore: &'static Semaphore,
+    respond: impl FnOnce(&'static str) -> E,
+) -> Result<SemaphorePermit<'static>, E> {
+    match timeout(Duration::from_secs(5), semaphore.acquire()).await {
+        Ok(Ok(permit)) => Ok(permit),
+        Ok(Err(_)) => Err(respond("Server is shutting down")),
+        Err(_) => Err(respond("Server is busy, please try again")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, http::StatusCode, response::IntoResponse};
+
+    // As few async workers as possible, so inline blocking work would stall everything
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_runtime_stays_responsive_during_a_long_transcription() {
+        // A slow Whisper pass stand-in that holds its thread
+        let transcription = tokio::spawn(run_blocking(|| {
+            std::thread::sleep(Duration::from_millis(500));
+            Ok("hello".to_string())
+        }));
+        tokio::task::yield_now().await;
+
+        // Timers and other tasks keep running on the lone worker meanwhile
+        let ticks = tokio::spawn(async {
+            for _ in 0..5 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        tokio::time::timeout(Duration::from_millis(200), ticks)
+            .await
+            .expect("runtime was blocked")
+            .unwrap();
+        assert!(!transcription.is_finished());
+
+        assert_eq!(transcription.await.unwrap().unwrap(), "hello");
+    }
+
+    struct SilentBackend;
+
+    impl aira_brain::llm::CompletionBackend for SilentBackend {
+        fn complete(
+            &mut self,
+            _prompt: &str,
+            _params: &aira_brain::llm::SamplingParams,
+            _on_piece: &mut dyn FnMut(&str) -> bool,
+        ) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicked_holder_does_not_block_later_requests() {
+        static SEMAPHORE: Semaphore = Semaphore::const_new(1);
+        let llm = aira_brain::llm::LlmEngine::with_backend(Box::new(SilentBackend), "");
+        let aira: SharedAira = Arc::new(Mutex::new(Aira::builder(llm).build()));
+
+        // First "request" panics while holding Aira
+        let poisoner = aira.clone();
+        let result = tokio::spawn(async move {
+            let _guard = poisoner.lock().unwrap();
+            panic!("handler failed");
+        })
+        .await;
+        assert!(result.is_err());
+        assert!(aira.is_poisoned());
+
+        // Following requests still get Aira and succeed
+        for _ in 0..2 {
+            let response = crate::api::session::clear_session(State((aira.clone(), &SEMAPHORE)))
+                .await
+                .into_response();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+}
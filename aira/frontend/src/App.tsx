@@ -1,5 +1,5 @@
 import { useState, useEffect, useRef, useCallback, useMemo } from 'react';
-import { sendChatMessage, transcribeAudio, sendCameraFeatures, pollAlerts } from './api/chatAPI';
+import { sendChatMessage, transcribeAudio, sendCameraFeatures, pollAlerts, clearConversation } from './api/chatAPI';
 import { saveConversations, loadConversations, saveDarkMode, loadDarkMode } from './api/storageAPI';
 import type { Message, Conversation } from './types/chat';
 import type { CameraFeatures, EmotionalState } from './api/chatAPI';
@@ -14,6 +14,15 @@ import './App.css';
 
 type ViewState = 'landing' | 'chat';
 
+// Hint added to a new conversation's first message, keyed by the server's dominant emotion
+const MOOD_CONTEXT: Record<string, string> = {
+	fatigued: '[User seems fatigued - be concise and supportive] ',
+	stressed: '[User seems stressed - be calm and reassuring] ',
+	focused: '[User seems focused and engaged] ',
+	disengaged: '[User seems disengaged - try to re-engage them] ',
+	happy: '[User seems happy - match their positive energy] ',
+};
+
 function App() {
 	const [messages, setMessages] = useState<Message[]>([]);
 	const [inputMessage, setInputMessage] = useState<string>('');
@@ -93,6 +102,9 @@ function App() {
 	};
 
 	const handleNewConversation = useCallback(() => {
+		// Start the server-side history fresh as well
+		clearConversation().catch(err => console.error('Failed to clear conversation:', err));
+
 		// Generate mood summary before starting new conversation
 		if (moodHistoryRef.current.length > 5) {
 			const moodCounts: Record<string, number> = {};
@@ -519,20 +531,7 @@ function App() {
 
 				// Add mood context to first message if camera is enabled and we have emotion data
 				if (cameraEnabled && _emotionalState) {
-					const state = _emotionalState;
-					let moodContext = '';
-
-					if (state.fatigue > 0.7) {
-						moodContext = '[User seems fatigued - be concise and supportive] ';
-					} else if (state.stress > 0.6) {
-						moodContext = '[User seems stressed - be calm and reassuring] ';
-					} else if (state.engagement > 0.7) {
-						moodContext = '[User seems focused and engaged] ';
-					} else if (state.engagement < 0.3) {
-						moodContext = '[User seems disengaged - try to re-engage them] ';
-					} else if (state.positive_affect > 0.6) {
-						moodContext = '[User seems happy - match their positive energy] ';
-					}
+					const moodContext = MOOD_CONTEXT[_emotionalState.dominant_emotion ?? ''] ?? '';
 
 					if (moodContext) {
 						messageToSend = moodContext + messageToSend;
@@ -749,19 +748,8 @@ function App() {
 				const state = await sendCameraFeatures(features);
 				setEmotionalState(state);
 
-				// Determine dominant emotion from state
-				let dominantEmotion = 'neutral';
-				if (state.fatigue > 0.7) {
-					dominantEmotion = 'fatigued';
-				} else if (state.stress > 0.6) {
-					dominantEmotion = 'stressed';
-				} else if (state.positive_affect > 0.6) {
-					dominantEmotion = 'happy';
-				} else if (state.engagement > 0.7) {
-					dominantEmotion = 'focused';
-				} else if (state.engagement < 0.3) {
-					dominantEmotion = 'disengaged';
-				}
+				// The server picks the dominant emotion in its configured priority order
+				const dominantEmotion = state.dominant_emotion ?? 'neutral';
 				setEmotion(dominantEmotion);
 
 				// Track mood during conversation (every 10 seconds)
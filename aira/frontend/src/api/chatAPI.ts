@@ -1,50 +1,117 @@
 import { fetchEventSource, EventSourceMessage } from '@microsoft/fetch-event-source';
 import type {
-	ChatRequest, ChatCallbacks,
+	ChatRequest, ChatCallbacks, ChatDone,
 	EmotionResponse,
 	CameraFeatures,
 	EmotionalState,
+	TranscribeResult,
+	ErrorStage,
+	CaptionedSpeech,
 } from '../types/api';
 
 import type { CameraStatus } from '../types/camera';
 
 const API_BASE_URL = 'http://127.0.0.1:3000';
 
-export type { ChatRequest, ChatCallbacks, EmotionResponse, CameraFeatures, EmotionalState, CameraStatus };
+// Token for a server started with AIRA_API_TOKEN, set as VITE_AIRA_API_TOKEN at build time
+// Admin endpoints (clearing the conversation, mode, speech speed, models) need it, and with
+// AIRA_API_TOKEN_ALL every endpoint does, so it goes on every request
+const API_TOKEN: string | undefined = import.meta.env.VITE_AIRA_API_TOKEN || undefined;
+
+function authHeaders(headers: Record<string, string> = {}): Record<string, string> {
+	return API_TOKEN ? { ...headers, Authorization: `Bearer ${API_TOKEN}` } : headers;
+}
+
+export type { ChatRequest, ChatCallbacks, ChatDone, EmotionResponse, CameraFeatures, EmotionalState, CameraStatus, TranscribeResult };
 
 // Send a message to Aira and receive streaming response
 export async function sendChatMessage(
 	message: string,
 	callbacks: ChatCallbacks,
 	abortSignal?: AbortSignal
+): Promise<void> {
+	return streamChat('/chat', JSON.stringify({ message } satisfies ChatRequest), callbacks, abortSignal);
+}
+
+// Ask Aira for a fresh answer to the last message, replacing the previous reply
+export async function regenerateLastResponse(
+	callbacks: ChatCallbacks,
+	abortSignal?: AbortSignal
+): Promise<void> {
+	return streamChat('/api/chat/regenerate', undefined, callbacks, abortSignal);
+}
+
+// Stream more of the last reply, e.g. after onDone reports finish_reason 'length'
+// Tokens continue the same message, so append them to the reply already shown
+export async function continueLastResponse(
+	callbacks: ChatCallbacks,
+	abortSignal?: AbortSignal
+): Promise<void> {
+	return streamChat('/api/chat/continue', undefined, callbacks, abortSignal);
+}
+
+// POST to a streaming chat endpoint and dispatch its SSE events
+function streamChat(
+	path: string,
+	body: string | undefined,
+	callbacks: ChatCallbacks,
+	abortSignal?: AbortSignal
 ): Promise<void> {
 	return new Promise((resolve, reject) => {
-		fetchEventSource(`${API_BASE_URL}/chat`, {
+		fetchEventSource(`${API_BASE_URL}${path}`, {
 			method: 'POST',
-			headers: {
+			headers: authHeaders({
 				'Content-Type': 'application/json',
-			},
-			body: JSON.stringify({ message } satisfies ChatRequest),
+			}),
+			body,
 			signal: abortSignal,
 			onmessage(event: EventSourceMessage) {
+				const chunkId = event.id ? Number(event.id) : undefined;
 				switch (event.event) {
 					case 'tps':
 						callbacks.onTps(event.data);
 						break;
 					case 'audio_complete':
-						callbacks.onAudio(event.data);
+						callbacks.onAudio(event.data, chunkId);
+						break;
+					case 'audio_chunk': {
+						const chunk = JSON.parse(event.data) as { audio: string | null; end: boolean };
+						callbacks.onAudioChunk?.(chunk.audio, chunk.end, chunkId);
+						break;
+					}
+					case 'error': {
+						// Stage errors are JSON; busy/shutdown errors are plain text
+						try {
+							const error = JSON.parse(event.data) as { stage: ErrorStage; message: string };
+							callbacks.onError(error.message, error.stage);
+						} catch {
+							callbacks.onError(event.data);
+						}
+						break;
+					}
+					case 'warning':
+						callbacks.onWarning?.(event.data);
+						break;
+					case 'done':
+						callbacks.onDone?.(JSON.parse(event.data) as ChatDone);
 						break;
-					case 'error':
-						callbacks.onError(event.data);
+					case 'audio_stopped':
+						callbacks.onAudioStopped?.();
+						break;
+					case 'no_audio':
+						callbacks.onNoAudio?.();
+						break;
+					case 'filler':
+						callbacks.onFiller?.(event.data);
 						break;
 					case 'tts_error':
 					case 'audio_error':
 						console.error('Server error:', event.data);
 						break;
 					default:
-						// Regular token
+						// Regular token; keep-alive comments arrive here with no data and are skipped
 						if (event.data) {
-							callbacks.onToken(event.data);
+							callbacks.onToken(event.data, chunkId);
 						}
 						break;
 				}
@@ -69,6 +136,7 @@ export async function analyzeEmotion(audioBlob: Blob): Promise<EmotionResponse>
 
 	const response = await fetch(`${API_BASE_URL}/api/emotion`, {
 		method: 'POST',
+		headers: authHeaders(),
 		body: formData,
 	});
 
@@ -80,12 +148,12 @@ export async function analyzeEmotion(audioBlob: Blob): Promise<EmotionResponse>
 }
 
 // Send camera features to backend
-export async function sendCameraFeatures(features: CameraFeatures): Promise<EmotionalState> {
+export async function sendCameraFeatures(features: CameraFeatures | CameraFeatures[]): Promise<EmotionalState> {
 	const response = await fetch(`${API_BASE_URL}/api/camera/features`, {
 		method: 'POST',
-		headers: {
+		headers: authHeaders({
 			'Content-Type': 'application/json',
-		},
+		}),
 		body: JSON.stringify(features),
 	});
 
@@ -98,7 +166,7 @@ export async function sendCameraFeatures(features: CameraFeatures): Promise<Emot
 
 // Get camera status from backend
 export async function getCameraStatus(): Promise<CameraStatus> {
-	const response = await fetch(`${API_BASE_URL}/api/camera/status`);
+	const response = await fetch(`${API_BASE_URL}/api/camera/status`, { headers: authHeaders() });
 
 	if (!response.ok) {
 		throw new Error(`Failed to get camera status: ${response.statusText}`);
@@ -107,6 +175,193 @@ export async function getCameraStatus(): Promise<CameraStatus> {
 	return response.json();
 }
 
+// Turn emotion-adaptive replies on or off
+// Read or retune how strongly each camera frame moves the smoothed emotion (0-1]
+export async function getEmotionAlpha(): Promise<number> {
+	const response = await fetch(`${API_BASE_URL}/api/emotion/alpha`, { headers: authHeaders() });
+	if (!response.ok) {
+		throw new Error(`Failed to read emotion smoothing: ${response.statusText}`);
+	}
+	const data: { alpha: number } = await response.json();
+	return data.alpha;
+}
+
+export async function setEmotionAlpha(alpha: number): Promise<number> {
+	const response = await fetch(`${API_BASE_URL}/api/emotion/alpha`, {
+		method: 'PUT',
+		headers: authHeaders({
+			'Content-Type': 'application/json',
+		}),
+		body: JSON.stringify({ alpha }),
+	});
+
+	if (!response.ok) {
+		throw new Error(`Failed to update emotion smoothing: ${await response.text()}`);
+	}
+
+	const data: { alpha: number } = await response.json();
+	return data.alpha;
+}
+
+export async function setEmotionEnabled(enabled: boolean): Promise<boolean> {
+	const response = await fetch(`${API_BASE_URL}/api/emotion/enabled`, {
+		method: 'POST',
+		headers: authHeaders({
+			'Content-Type': 'application/json',
+		}),
+		body: JSON.stringify({ enabled }),
+	});
+
+	if (!response.ok) {
+		throw new Error(`Failed to update emotion setting: ${response.statusText}`);
+	}
+
+	const data: { enabled: boolean } = await response.json();
+	return data.enabled;
+}
+
+// Reset the server-side conversation history and emotional context
+export async function clearConversation(): Promise<void> {
+	const response = await fetch(`${API_BASE_URL}/api/session/clear`, {
+		method: 'POST',
+		headers: authHeaders(),
+	});
+
+	if (!response.ok) {
+		throw new Error(`Failed to clear conversation: ${response.statusText}`);
+	}
+}
+
+// Compress older turns into a summary to free up context
+export async function summarizeConversation(): Promise<{ summarized: boolean; history_length: number; history_tokens: number }> {
+	const response = await fetch(`${API_BASE_URL}/api/session/summarize`, {
+		method: 'POST',
+		headers: authHeaders(),
+	});
+
+	if (!response.ok) {
+		throw new Error(`Failed to summarize conversation: ${response.statusText}`);
+	}
+
+	return response.json();
+}
+
+// Switch Aira's system prompt mode without losing the conversation
+export async function setPromptMode(mode: string): Promise<{ mode: string; modes: string[] }> {
+	const response = await fetch(`${API_BASE_URL}/api/mode`, {
+		method: 'POST',
+		headers: authHeaders({
+			'Content-Type': 'application/json',
+		}),
+		body: JSON.stringify({ mode }),
+	});
+
+	if (!response.ok) {
+		throw new Error(`Failed to set mode: ${await response.text()}`);
+	}
+
+	return response.json();
+}
+
+// Stop speaking the current reply; its text keeps streaming
+export async function stopAudio(): Promise<boolean> {
+	const response = await fetch(`${API_BASE_URL}/api/chat/stop-audio`, {
+		method: 'POST',
+		headers: authHeaders(),
+	});
+
+	if (!response.ok) {
+		throw new Error(`Failed to stop audio: ${response.statusText}`);
+	}
+
+	const data: { stopped: boolean } = await response.json();
+	return data.stopped;
+}
+
+// Set how fast Aira speaks (0.5 - 2.0); returns the speed the server applied
+export async function setSpeechSpeed(speed: number): Promise<number> {
+	const response = await fetch(`${API_BASE_URL}/api/tts/speed`, {
+		method: 'POST',
+		headers: authHeaders({
+			'Content-Type': 'application/json',
+		}),
+		body: JSON.stringify({ speed }),
+	});
+
+	if (!response.ok) {
+		throw new Error(`Failed to set speech speed: ${await response.text()}`);
+	}
+
+	const data: { speed: number } = await response.json();
+	return data.speed;
+}
+
+// Speak plain text and get word timings with the audio, for live captions
+// Returns null for blank text, which has nothing to speak
+export async function synthesizeCaptioned(text: string): Promise<CaptionedSpeech | null> {
+	const response = await fetch(`${API_BASE_URL}/api/tts/captioned`, {
+		method: 'POST',
+		headers: authHeaders({
+			'Content-Type': 'application/json',
+		}),
+		body: JSON.stringify({ text }),
+	});
+
+	if (!response.ok) {
+		throw new Error(`Failed to synthesize captioned speech: ${await response.text()}`);
+	}
+	if (response.status === 204) {
+		return null;
+	}
+
+	return response.json();
+}
+
+// Aira's opening line for a new conversation; null when none is configured or it was already said
+export async function fetchGreeting(): Promise<string | null> {
+	const response = await fetch(`${API_BASE_URL}/api/session/greet`, {
+		method: 'POST',
+		headers: authHeaders(),
+	});
+
+	if (!response.ok) {
+		throw new Error(`Failed to fetch greeting: ${await response.text()}`);
+	}
+
+	const data: { greeting: string | null } = await response.json();
+	return data.greeting;
+}
+
+// Free the backend's model memory; the next request reloads them
+export async function unloadModels(): Promise<boolean> {
+	const response = await fetch(`${API_BASE_URL}/api/models/unload`, {
+		method: 'POST',
+		headers: authHeaders(),
+	});
+
+	if (!response.ok) {
+		throw new Error(`Failed to unload models: ${await response.text()}`);
+	}
+
+	const data: { loaded: boolean } = await response.json();
+	return data.loaded;
+}
+
+// Load the backend's models ahead of the next request
+export async function reloadModels(): Promise<boolean> {
+	const response = await fetch(`${API_BASE_URL}/api/models/reload`, {
+		method: 'POST',
+		headers: authHeaders(),
+	});
+
+	if (!response.ok) {
+		throw new Error(`Failed to reload models: ${await response.text()}`);
+	}
+
+	const data: { loaded: boolean } = await response.json();
+	return data.loaded;
+}
+
 // Check if backend is healthy
 export async function checkHealth(): Promise<boolean> {
 	try {
@@ -118,12 +373,13 @@ export async function checkHealth(): Promise<boolean> {
 }
 
 // Transcribe audio to text using STT
-export async function transcribeAudio(audioBlob: Blob): Promise<{ text: string; confidence: number }> {
+export async function transcribeAudio(audioBlob: Blob): Promise<TranscribeResult> {
 	const formData = new FormData();
 	formData.append('audio', audioBlob, 'recording.webm');
 
 	const response = await fetch(`${API_BASE_URL}/api/stt/transcribe`, {
 		method: 'POST',
+		headers: authHeaders(),
 		body: formData,
 	});
 
@@ -138,9 +394,9 @@ export async function transcribeAudio(audioBlob: Blob): Promise<{ text: string;
 export async function testStressAlert(durationSeconds: number = 60): Promise<{ message: string; alert_message: string; audio_base64: string | null }> {
 	const response = await fetch(`${API_BASE_URL}/api/test-stress`, {
 		method: 'POST',
-		headers: {
+		headers: authHeaders({
 			'Content-Type': 'application/json',
-		},
+		}),
 		body: JSON.stringify({ duration_seconds: durationSeconds }),
 	});
 
@@ -154,7 +410,7 @@ export async function testStressAlert(durationSeconds: number = 60): Promise<{ m
 // Poll for pending alerts from backend
 export async function pollAlerts(): Promise<{ has_alert: boolean; message: string | null; audio_base64: string | null }> {
 	try {
-		const response = await fetch(`${API_BASE_URL}/api/alerts`);
+		const response = await fetch(`${API_BASE_URL}/api/alerts`, { headers: authHeaders() });
 		if (!response.ok) {
 			console.error('pollAlerts: response not ok', response.status);
 			return { has_alert: false, message: null, audio_base64: null };
@@ -3,18 +3,105 @@ import type { CameraFeatures, EmotionalState } from './camera';
 
 export type { Message, CameraFeatures, EmotionalState };
 
+// A prior turn sent back to the server in stateless mode
+export interface ChatMessage {
+	role: 'user' | 'assistant';
+	content: string;
+}
+
 export interface ChatRequest {
 	message: string;
+	length?: 'short' | 'normal' | 'long';
+	temperature?: number;
+	// Output gain for the spoken reply (1.0 = unchanged)
+	volume?: number;
+	// Whole conversation so far, alternating user/assistant; replaces server-side history
+	messages?: ChatMessage[];
+	// Text per streamed event: every token (default), whole words or whole sentences
+	stream_granularity?: 'token' | 'word' | 'sentence';
+	// Receive each sentence's audio in parts as it is synthesized (onAudioChunk) instead of whole
+	audio_streaming?: boolean;
+	// 2 for stereo audio (the mono voice on both channels); defaults to the server's AIRA_TTS_CHANNELS
+	channels?: 1 | 2;
+	// Start with a quick greedy first sentence so speech begins sooner; defaults to AIRA_FAST_FIRST_SENTENCE
+	fast_start?: boolean;
+	// Language to reply in, e.g. "French" or "fr"; defaults to AIRA_RESPONSE_LANGUAGE, "" for none
+	response_language?: string;
+	// Stop the reply after this many words, at a word boundary
+	max_words?: number;
+}
+
+// A stretch of a transcribed clip that contained speech, in seconds
+export interface SpeechSegment {
+	start: number;
+	end: number;
+}
+
+export interface TranscribeResult {
+	text: string;
+	confidence: number;
+	segments: SpeechSegment[];
+}
+
+// A `partial` event of /api/stt/transcribe/stream; confidence is in [0, 1]
+export interface TranscribeSegment {
+	text: string;
+	start: number;
+	end: number;
+	confidence: number;
+}
+
+// When a word of captioned speech is spoken, in seconds from the start
+export interface WordTiming {
+	word: string;
+	start: number;
+	end: number;
+}
+
+// Response of /api/tts/captioned; audio is a base64 WAV
+export interface CaptionedSpeech {
+	audio: string;
+	sample_rate: number;
+	duration_secs: number;
+	words: WordTiming[];
+}
+
+// Pipeline stage a stream error came from
+export type ErrorStage = 'llm' | 'tts';
+
+// Why a reply ended: naturally, at the token cap, at the time limit, or cancelled
+export type FinishReason = 'stop' | 'length' | 'timeout' | 'cancelled';
+
+export interface ChatDone {
+	finish_reason: FinishReason;
+	tokens_per_second: number;
+	input_truncated: boolean;
+	// Replayed from the server's response cache instead of generated
+	cached: boolean;
 }
 
 export interface ChatCallbacks {
-	onToken: (token: string) => void;
+	// chunkId links caption tokens to the audio chunk that speaks them
+	onToken: (token: string, chunkId?: number) => void;
 	onTps: (tps: string) => void;
-	onAudio: (audioBase64: string) => void;
-	onError: (error: string) => void;
+	onAudio: (audioBase64: string, chunkId?: number) => void;
+	// `stage` says which part of the pipeline failed; absent for busy/shutdown errors
+	onError: (error: string, stage?: ErrorStage) => void;
+	onWarning?: (warning: string) => void;
+	// The server stopped speaking this reply after a stop-audio request
+	onAudioStopped?: () => void;
+	// The reply had nothing to speak, so no audio will follow
+	onNoAudio?: () => void;
+	// Part of a sentence's audio in streaming mode; `end` marks the sentence's last event, without audio
+	onAudioChunk?: (audioBase64: string | null, end: boolean, chunkId?: number) => void;
+	// Thinking filler to play until the first onAudio; fade it out when real audio arrives
+	onFiller?: (audioBase64: string) => void;
+	onDone?: (done: ChatDone) => void;
 	onComplete: () => void;
 }
 
 export interface EmotionResponse {
 	dominant_emotion: string;
+	// Replies are being steered by a fresh emotional context right now
+	injection_active: boolean;
 }
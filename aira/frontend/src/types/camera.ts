@@ -16,12 +16,18 @@ export interface EmotionalState {
 	engagement: number;
 	stress: number;
 	positive_affect: number;
-	timestamp: number;
+	// Unix seconds by default; ISO 8601 with ?timestamp=iso, absent with ?timestamp=omit
+	timestamp?: number | string;
+	face_present?: boolean;
+	// Picked by the server in its configured priority order, e.g. "fatigued" or "focused"
+	dominant_emotion?: string;
 }
 
 export interface CameraStatus {
 	enabled: boolean;
 	face_detected: boolean;
 	last_update?: number;
+	// Seconds since the last reading; stale readings report the camera as disabled
+	age_seconds?: number;
 }
 
@@ -0,0 +1,10 @@
+/// <reference types="vite/client" />
+
+interface ImportMetaEnv {
+	// Bearer token for a server started with AIRA_API_TOKEN; unset for an open server
+	readonly VITE_AIRA_API_TOKEN?: string;
+}
+
+interface ImportMeta {
+	readonly env: ImportMetaEnv;
+}
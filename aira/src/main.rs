@@ -4,14 +4,31 @@ use crossterm::{
     event::{self, Event, KeyCode, KeyEventKind},
     terminal,
 };
+use ringbuf::{
+    HeapRb,
+    traits::{Consumer, Producer, Split},
+};
 use rodio::{buffer::SamplesBuffer, OutputStream, Sink};
 use std::{
     io::{self, Write},
-    sync::{Arc, Mutex},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
     time::Duration,
 };
 
-use aira_brain::{aira::Aira, llm::LlmEngine, stt::SttEngine, tts::TtsEngine};
+use aira_brain::{
+    aira::Aira,
+    audio::{WHISPER_SAMPLE_RATE, apply_gain, downmix, i16_to_f32, resample, u16_to_f32},
+    config::{
+        DEFAULT_ASSISTANT_NAME, ExitCommandConfig, default_system_prompt, default_wake_phrase,
+        is_exit_command, normalize_words, with_assistant_name,
+    },
+    llm::{FinishReason, LlmEngine},
+    stt::SttEngine,
+    tts::{PIPER_SAMPLE_RATE, TtsEngine},
+};
 
 enum InputMode {
     Voice,
@@ -39,99 +56,362 @@ fn choose_mode() -> InputMode {
     }
 }
 
-fn stereo_to_mono(input: &[f32]) -> Vec<f32> {
-    input.chunks(2).map(|c| (c[0] + c[1]) * 0.5).collect()
-}
-
-fn downsample_to_16khz(input: &[f32], input_rate: u32) -> Vec<f32> {
-    let ratio = input_rate as f32 / 16_000.0;
-    let mut out = Vec::new();
-    let mut i = 0.0;
-    while (i as usize) < input.len() {
-        out.push(input[i as usize]);
-        i += ratio;
-    }
-    out
+// Convert mic audio at `input_rate` to Whisper's 16 kHz, up or down, with linear interpolation
+// Rates below 16 kHz (e.g. 8 kHz headsets) are upsampled; 16 kHz input passes through unchanged
+fn resample_to_16khz(input: &[f32], input_rate: u32) -> Vec<f32> {
+    resample(input.to_vec(), input_rate, WHISPER_SAMPLE_RATE)
 }
 
-fn process_audio(input: &[f32], sample_rate: u32) -> Vec<f32> {
-    let mono = if input.len() > 1 {
-        stereo_to_mono(input)
-    } else {
-        input.to_vec()
-    };
-    downsample_to_16khz(&mono, sample_rate)
+// Interleaved capture with the device's `channels` to 16 kHz mono, averaging every channel per frame
+fn process_audio(input: &[f32], sample_rate: u32, channels: u16) -> Vec<f32> {
+    resample_to_16khz(&downmix(input, channels), sample_rate)
 }
 
 fn wait_for_space() -> Result<()> {
     loop {
-        if event::poll(Duration::from_millis(10))? {
-            if let Event::Key(k) = event::read()? {
-                if k.code == KeyCode::Char(' ') && k.kind == KeyEventKind::Press {
-                    break;
-                }
-            }
+        if event::poll(Duration::from_millis(10))?
+            && let Event::Key(k) = event::read()?
+            && k.code == KeyCode::Char(' ')
+            && k.kind == KeyEventKind::Press
+        {
+            break;
         }
     }
     Ok(())
 }
 
-fn wait_for_any_key() -> Result<()> {
+// Wait for a key press, calling `on_tick` roughly every 10ms while waiting
+fn wait_for_any_key(mut on_tick: impl FnMut()) -> Result<()> {
     loop {
-        if event::poll(Duration::from_millis(10))? {
-            if let Event::Key(_) = event::read()? {
-                break;
-            }
+        on_tick();
+        if event::poll(Duration::from_millis(10))?
+            && let Event::Key(_) = event::read()?
+        {
+            break;
         }
     }
     Ok(())
 }
 
-fn record_microphone() -> Result<Vec<f32>> {
+// Default mic ring buffer length, override with AIRA_MIC_BUFFER_MS
+const DEFAULT_MIC_BUFFER_MS: usize = 2000;
+
+// Ring buffer capacity in samples for the configured buffer length
+fn mic_buffer_capacity(sample_rate: u32, channels: u16) -> usize {
+    let buffer_ms = std::env::var("AIRA_MIC_BUFFER_MS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|ms| *ms > 0)
+        .unwrap_or(DEFAULT_MIC_BUFFER_MS);
+
+    sample_rate as usize * channels as usize * buffer_ms / 1000
+}
+
+// Audio callback side: never blocks, counts samples that did not fit
+fn push_frame(producer: &mut impl Producer<Item = f32>, data: &[f32], dropped: &AtomicUsize) {
+    let written = producer.push_slice(data);
+    if written < data.len() {
+        dropped.fetch_add(data.len() - written, Ordering::Relaxed);
+    }
+}
+
+// Consumer side: move everything buffered so far into `out`
+fn drain_frames(consumer: &mut impl Consumer<Item = f32>, out: &mut Vec<f32>) {
+    out.extend(consumer.pop_iter());
+}
+
+// The device's default input config if it is f32, else an f32 config at the same rate and channels,
+// else the default as is (i16/u16, converted by `build_input_stream`)
+fn input_config(device: &cpal::Device) -> Result<cpal::SupportedStreamConfig> {
+    let default = device.default_input_config()?;
+    if default.sample_format() == cpal::SampleFormat::F32 {
+        return Ok(default);
+    }
+
+    let f32_config = device
+        .supported_input_configs()?
+        .filter(|range| {
+            range.sample_format() == cpal::SampleFormat::F32
+                && range.channels() == default.channels()
+        })
+        .find_map(|range| range.try_with_sample_rate(default.sample_rate()));
+    Ok(f32_config.unwrap_or(default))
+}
+
+// Open an input stream that hands `on_samples` f32 samples whatever the device's format
+fn build_input_stream(
+    device: &cpal::Device,
+    config: &cpal::SupportedStreamConfig,
+    mut on_samples: impl FnMut(&[f32]) + Send + 'static,
+) -> Result<cpal::Stream> {
+    let stream_config = config.config();
+    let on_error = |err| eprintln!("Mic error: {}", err);
+    // Reused across callbacks so converting never allocates on the audio thread once warmed up
+    let mut converted = Vec::new();
+
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => device.build_input_stream(
+            &stream_config,
+            move |data: &[f32], _| on_samples(data),
+            on_error,
+            None,
+        )?,
+        cpal::SampleFormat::I16 => device.build_input_stream(
+            &stream_config,
+            move |data: &[i16], _| {
+                converted.clear();
+                converted.extend(data.iter().map(|&s| i16_to_f32(s)));
+                on_samples(&converted);
+            },
+            on_error,
+            None,
+        )?,
+        cpal::SampleFormat::U16 => device.build_input_stream(
+            &stream_config,
+            move |data: &[u16], _| {
+                converted.clear();
+                converted.extend(data.iter().map(|&s| u16_to_f32(s)));
+                on_samples(&converted);
+            },
+            on_error,
+            None,
+        )?,
+        format => anyhow::bail!("Unsupported microphone sample format: {:?}", format),
+    };
+    Ok(stream)
+}
+
+// Seconds of audio each wake-word check transcribes
+const WAKE_WINDOW_SECS: usize = 2;
+
+// How often the rolling wake-word window is checked
+const WAKE_CHECK_INTERVAL: Duration = Duration::from_millis(1000);
+
+// Assistant name from AIRA_ASSISTANT_NAME (default: Aira)
+fn assistant_name() -> String {
+    std::env::var("AIRA_ASSISTANT_NAME").unwrap_or_else(|_| DEFAULT_ASSISTANT_NAME.to_string())
+}
+
+// Wake phrase from AIRA_WAKE_WORD; unset or empty keeps push-to-talk
+// "1" or "true" listens for "hey <assistant name>"
+fn wake_word() -> Option<String> {
+    let value = std::env::var("AIRA_WAKE_WORD").ok()?;
+    if value == "1" || value.eq_ignore_ascii_case("true") {
+        return Some(default_wake_phrase(&assistant_name()));
+    }
+    Some(value).filter(|phrase| !phrase.trim().is_empty())
+}
+
+// Exit phrases from AIRA_EXIT_PHRASES, comma-separated (default: "exit,quit")
+fn exit_commands() -> ExitCommandConfig {
+    let Ok(value) = std::env::var("AIRA_EXIT_PHRASES") else {
+        return ExitCommandConfig::default();
+    };
+    let phrases: Vec<String> = value
+        .split(',')
+        .map(str::trim)
+        .filter(|phrase| !phrase.is_empty())
+        .map(String::from)
+        .collect();
+    if phrases.is_empty() {
+        return ExitCommandConfig::default();
+    }
+    ExitCommandConfig { phrases }
+}
+
+// True if `transcript` contains every word of `phrase`, in order and adjacent
+fn matches_wake_word(transcript: &str, phrase: &str) -> bool {
+    let phrase = normalize_words(phrase);
+    if phrase.is_empty() {
+        return false;
+    }
+    normalize_words(transcript)
+        .windows(phrase.len())
+        .any(|window| window == phrase.as_slice())
+}
+
+// Listen hands-free until the wake phrase is heard
+// A short Whisper pass runs over the last couple of seconds of mic audio
+fn wait_for_wake_word(aira: &Aira, phrase: &str) -> Result<()> {
     let host = cpal::default_host();
     let device = host.default_input_device().context("No microphone found")?;
 
-    let config = device.default_input_config()?;
+    let config = input_config(&device)?;
     let sample_rate = config.sample_rate().0;
-    let config = config.config();
+    let channels = config.channels();
+    let window_len = sample_rate as usize * channels as usize * WAKE_WINDOW_SECS;
 
-    println!("\nPress SPACE to start recording...");
-    wait_for_space()?;
-    println!("Recording... (press any key to stop)");
+    let (mut producer, mut consumer) =
+        HeapRb::<f32>::new(mic_buffer_capacity(sample_rate, channels)).split();
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let dropped_clone = dropped.clone();
 
-    let buffer: Arc<Mutex<Vec<f32>>> = Arc::new(Mutex::new(Vec::new()));
-    let buffer_clone = buffer.clone();
+    let stream = build_input_stream(&device, &config, move |data| {
+        push_frame(&mut producer, data, &dropped_clone)
+    })?;
 
-    let stream = device.build_input_stream(
-        &config,
-        move |data: &[f32], _| {
-            buffer_clone.lock().unwrap().extend_from_slice(data);
-        },
-        |err| eprintln!("Mic error: {}", err),
-        None,
-    )?;
+    println!("\n👂 Listening for \"{}\"...", phrase);
+    stream.play()?;
+
+    let mut window = Vec::with_capacity(window_len);
+    loop {
+        std::thread::sleep(WAKE_CHECK_INTERVAL);
+        drain_frames(&mut consumer, &mut window);
+
+        // Keep only the most recent window
+        if window.len() > window_len {
+            window.drain(..window.len() - window_len);
+        }
+        if window.len() < window_len / 2 {
+            continue;
+        }
+
+        let heard = aira.transcribe(&process_audio(&window, sample_rate, channels))?;
+        if matches_wake_word(&heard, phrase) {
+            println!("Wake word heard");
+            return Ok(());
+        }
+    }
+}
+
+// Record until a key is pressed, first waiting for SPACE if `wait_for_start` is set
+fn record_microphone(wait_for_start: bool) -> Result<Vec<f32>> {
+    let host = cpal::default_host();
+    let device = host.default_input_device().context("No microphone found")?;
+
+    let config = input_config(&device)?;
+    let sample_rate = config.sample_rate().0;
+    let channels = config.channels();
 
+    // Lock-free ring buffer so the realtime callback never waits on the consumer
+    let (mut producer, mut consumer) =
+        HeapRb::<f32>::new(mic_buffer_capacity(sample_rate, channels)).split();
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let dropped_clone = dropped.clone();
+
+    if wait_for_start {
+        println!("\nPress SPACE to start recording...");
+        wait_for_space()?;
+    }
+    println!("Recording... (press any key to stop)");
+
+    let stream = build_input_stream(&device, &config, move |data| {
+        push_frame(&mut producer, data, &dropped_clone)
+    })?;
+
+    let mut raw = Vec::new();
     stream.play()?;
-    wait_for_any_key()?;
+    wait_for_any_key(|| drain_frames(&mut consumer, &mut raw))?;
     drop(stream);
+    drain_frames(&mut consumer, &mut raw);
 
     terminal::disable_raw_mode()?;
 
-    let raw = buffer.lock().unwrap().clone();
-    Ok(process_audio(&raw, sample_rate))
+    let dropped = dropped.load(Ordering::Relaxed);
+    if dropped > 0 {
+        eprintln!(
+            "⚠️  Dropped {} mic samples, consider raising AIRA_MIC_BUFFER_MS",
+            dropped
+        );
+    }
+
+    Ok(process_audio(&raw, sample_rate, channels))
 }
 
-fn play_audio(samples: Vec<f32>) -> Result<()> {
-    let (_stream, handle) = OutputStream::try_default()?;
-    let sink = Sink::try_new(&handle)?;
-    let buffer = SamplesBuffer::new(1, 22050, samples);
-    sink.append(buffer);
-    sink.sleep_until_end();
-    Ok(())
+// Playback gain from AIRA_TTS_VOLUME (default: 1.0)
+fn tts_volume() -> f32 {
+    std::env::var("AIRA_TTS_VOLUME")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1.0)
+}
+
+// Where a reply's playback is; keys move it along until the audio ends or is skipped
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PlaybackState {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+impl PlaybackState {
+    // SPACE toggles pause, any other key skips the rest of the reply
+    fn on_key(self, key: KeyCode) -> Self {
+        match (self, key) {
+            (Self::Stopped, _) => Self::Stopped,
+            (Self::Playing, KeyCode::Char(' ')) => Self::Paused,
+            (Self::Paused, KeyCode::Char(' ')) => Self::Playing,
+            _ => Self::Stopped,
+        }
+    }
+}
+
+// Output device and sink kept open across replies
+struct Player {
+    _stream: OutputStream,
+    sink: Sink,
+}
+
+impl Player {
+    fn new() -> Result<Self> {
+        let (stream, handle) = OutputStream::try_default()?;
+        let sink = Sink::try_new(&handle)?;
+        Ok(Self {
+            _stream: stream,
+            sink,
+        })
+    }
+
+    // Play `samples` until they end, with SPACE to pause/resume and any other key to skip
+    fn play(&self, mut samples: Vec<f32>, sample_rate: u32) -> Result<()> {
+        apply_gain(&mut samples, tts_volume());
+        self.sink
+            .append(SamplesBuffer::new(1, sample_rate, samples));
+        self.sink.play();
+
+        println!("(SPACE to pause/resume, any other key to skip)");
+        terminal::enable_raw_mode()?;
+        let result = self.wait_for_end();
+        terminal::disable_raw_mode()?;
+        result
+    }
+
+    fn wait_for_end(&self) -> Result<()> {
+        let mut state = PlaybackState::Playing;
+        while state != PlaybackState::Stopped && !self.sink.empty() {
+            if event::poll(Duration::from_millis(10))?
+                && let Event::Key(k) = event::read()?
+                && k.kind == KeyEventKind::Press
+            {
+                state = state.on_key(k.code);
+                match state {
+                    PlaybackState::Playing => self.sink.play(),
+                    PlaybackState::Paused => self.sink.pause(),
+                    PlaybackState::Stopped => self.sink.clear(),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Say the configured greeting before the user's first turn
+fn greet(aira: &mut Aira, name: &str, player: &Player) -> Result<()> {
+    let Some(greeting) = aira.greet() else {
+        return Ok(());
+    };
+    println!("{}: {}\n", name, greeting);
+    let speech = aira.speak(&greeting)?;
+    let sample_rate = aira.speech_sample_rate().unwrap_or(PIPER_SAMPLE_RATE);
+    player.play(speech, sample_rate)
 }
 
 fn text_loop(mut aira: Aira) -> Result<()> {
-    println!("💬 Text mode. Type 'exit' to quit.\n");
+    println!("💬 Text mode. Type 'exit' to quit, '/speed 1.2' to change speaking speed.\n");
+    let name = assistant_name();
+    let player = Player::new()?;
+    let exit = exit_commands();
+    greet(&mut aira, &name, &player)?;
 
     loop {
         print!("You: ");
@@ -145,11 +425,22 @@ fn text_loop(mut aira: Aira) -> Result<()> {
             continue;
         }
 
-        if text.to_lowercase().contains("exit") || text.to_lowercase().contains("quit") {
+        if is_exit_command(text, &exit) {
             println!("Goodbye 👋");
             return Ok(());
         }
 
+        if let Some(arg) = text.strip_prefix("/speed") {
+            match arg.trim().parse() {
+                Ok(speed) => match aira.set_speech_speed(speed) {
+                    Ok(applied) => println!("🔊 Speaking speed set to {:.2}x", applied),
+                    Err(e) => println!("Could not change speed: {}", e),
+                },
+                Err(_) => println!("Usage: /speed <0.5 - 2.0>"),
+            }
+            continue;
+        }
+
         let mut full_reply_text = String::new();
         let mut print_callback = |token: &str| {
             print!("{}", token);
@@ -157,22 +448,41 @@ fn text_loop(mut aira: Aira) -> Result<()> {
             std::io::stdout().flush().context("Failed to flush stdout")
         };
 
-        println!("Aira: ");
-        let _tps = aira.think(text, &mut print_callback)?;
+        println!("{}: ", name);
+        let metrics = aira.think(text, &mut print_callback)?;
         println!(); // Add newline after streaming
+        if metrics.input_truncated {
+            println!("(Your message was too long and was truncated)");
+        }
+        if metrics.finish_reason == FinishReason::Length {
+            println!("(Reply was cut off at the length limit)");
+        }
 
         // Speaking the full reply
         let speech = aira.speak(&full_reply_text)?;
-        play_audio(speech)?;
+        let sample_rate = aira.speech_sample_rate().unwrap_or(PIPER_SAMPLE_RATE);
+        player.play(speech, sample_rate)?;
     }
 }
 
-fn voice_loop(mut aira: aira_brain::aira::Aira) -> Result<()> {
-    println!("🎤 Voice mode. Press SPACE to talk.\n");
+fn voice_loop(mut aira: Aira) -> Result<()> {
+    let name = assistant_name();
+    let player = Player::new()?;
+    let wake_phrase = wake_word();
+    let exit = exit_commands();
+    match &wake_phrase {
+        Some(phrase) => println!("🎤 Voice mode. Say \"{}\" to talk.\n", phrase),
+        None => println!("🎤 Voice mode. Press SPACE to talk.\n"),
+    }
+    greet(&mut aira, &name, &player)?;
 
     loop {
+        if let Some(phrase) = &wake_phrase {
+            wait_for_wake_word(&aira, phrase)?;
+        }
+
         terminal::enable_raw_mode()?;
-        let audio = record_microphone()?;
+        let audio = record_microphone(wake_phrase.is_none())?;
 
         println!("Transcribing...");
         let text = aira.transcribe(&audio)?;
@@ -184,6 +494,11 @@ fn voice_loop(mut aira: aira_brain::aira::Aira) -> Result<()> {
 
         println!("You: {}", text);
 
+        if is_exit_command(&text, &exit) {
+            println!("Goodbye 👋");
+            return Ok(());
+        }
+
         let mut full_reply_text = String::new();
         let mut print_callback = |token: &str| {
             print!("{}", token);
@@ -191,29 +506,77 @@ fn voice_loop(mut aira: aira_brain::aira::Aira) -> Result<()> {
             std::io::stdout().flush().context("Failed to flush stdout")
         };
 
-        println!("Aira: ");
-        let _tps = aira.think(&text, &mut print_callback)?;
+        println!("{}: ", name);
+        let metrics = aira.think(&text, &mut print_callback)?;
         println!(); // Add newline after streaming
+        if metrics.input_truncated {
+            println!("(Your message was too long and was truncated)");
+        }
+        if metrics.finish_reason == FinishReason::Length {
+            println!("(Reply was cut off at the length limit)");
+        }
 
         // Speaking the full reply
         let speech = aira.speak(&full_reply_text)?;
-        play_audio(speech)?;
+        let sample_rate = aira.speech_sample_rate().unwrap_or(PIPER_SAMPLE_RATE);
+        player.play(speech, sample_rate)?;
     }
 }
 
+// Callback that writes each token and flushes right away, so piped output streams
+fn flushing_writer<W: Write>(writer: &mut W) -> impl FnMut(&str) -> Result<()> + '_ {
+    move |token: &str| {
+        writer.write_all(token.as_bytes())?;
+        writer.flush().context("Failed to flush output")
+    }
+}
+
+// Answer a single prompt on stdout and exit (`--prompt <text>`)
+// Status messages go to stderr so stdout carries only the reply
+fn batch_prompt(prompt: &str) -> Result<()> {
+    eprintln!("Loading {}...", assistant_name());
+
+    let llm = LlmEngine::load(
+        "/home/ninegak/Project_Aira/aira/models/llama-3.2-3b-instruct-q4_k_m.gguf",
+        &default_system_prompt(&assistant_name()),
+    )?;
+    let mut aira = Aira::builder(llm).build();
+
+    let mut stdout = io::stdout().lock();
+    aira.think(prompt, flushing_writer(&mut stdout))?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
 fn main() -> Result<()> {
-    println!("Loading Aira...");
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--prompt") {
+        let prompt = args.get(i + 1).context("--prompt needs a message")?;
+        return batch_prompt(prompt);
+    }
+
+    println!("Loading {}...", assistant_name());
 
     let stt = SttEngine::load("/home/ninegak/Project_Aira/aira/models/ggml-small.en-q5_1.bin")?;
     let llm = LlmEngine::load(
         "/home/ninegak/Project_Aira/aira/models/llama-3.2-3b-instruct-q4_k_m.gguf",
-        "<|im_start|>system\nYou are Aira, a warm, empathetic AI assistant.<|im_end|>\n",
+        &default_system_prompt(&assistant_name()),
     )?;
-    let tts = TtsEngine::load(
+    let mut tts = TtsEngine::load(
         "/home/ninegak/Project_Aira/aira/tts_models/en_US-hfc_female-medium.onnx.json",
     )?;
+    if let Some(speed) = std::env::var("AIRA_TTS_SPEED")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        tts.set_speed(speed)?;
+    }
 
-    let aira = Aira::new(stt, llm, tts);
+    let mut builder = Aira::builder(llm).stt(stt).tts(tts);
+    if let Ok(greeting) = std::env::var("AIRA_GREETING") {
+        builder = builder.greeting(with_assistant_name(&greeting, &assistant_name()));
+    }
+    let aira = builder.build();
 
     match choose_mode() {
         InputMode::Voice => voice_loop(aira)?,
@@ -222,3 +585,150 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_keeps_every_sample() {
+        let (mut producer, mut consumer) = HeapRb::<f32>::new(4096).split();
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let dropped_clone = dropped.clone();
+
+        // Simulated audio thread delivering 256-sample frames
+        let frames = 400;
+        let audio_thread = std::thread::spawn(move || {
+            for frame in 0..frames {
+                let data: Vec<f32> = (0..256).map(|i| (frame * 256 + i) as f32).collect();
+                push_frame(&mut producer, &data, &dropped_clone);
+                std::thread::sleep(Duration::from_micros(100));
+            }
+        });
+
+        let mut received = Vec::new();
+        while !audio_thread.is_finished() {
+            drain_frames(&mut consumer, &mut received);
+        }
+        audio_thread.join().unwrap();
+        drain_frames(&mut consumer, &mut received);
+
+        assert_eq!(dropped.load(Ordering::Relaxed), 0);
+        assert_eq!(received.len(), frames * 256);
+        assert!(received.iter().enumerate().all(|(i, s)| *s == i as f32));
+    }
+
+    #[test]
+    fn test_full_ring_buffer_counts_dropped_samples() {
+        let (mut producer, _consumer) = HeapRb::<f32>::new(300).split();
+        let dropped = AtomicUsize::new(0);
+
+        push_frame(&mut producer, &[0.0; 256], &dropped);
+        push_frame(&mut producer, &[0.0; 256], &dropped);
+
+        assert_eq!(dropped.load(Ordering::Relaxed), 212);
+    }
+
+    // Writer that records what had been written at every flush
+    struct FlushRecorder {
+        written: Vec<u8>,
+        flushes: Vec<String>,
+    }
+
+    impl Write for FlushRecorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes
+                .push(String::from_utf8_lossy(&self.written).into_owned());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_flushing_writer_emits_tokens_incrementally() {
+        let mut recorder = FlushRecorder {
+            written: Vec::new(),
+            flushes: Vec::new(),
+        };
+
+        {
+            let mut callback = flushing_writer(&mut recorder);
+            for token in ["Hel", "lo", " world"] {
+                callback(token).unwrap();
+            }
+        }
+
+        assert_eq!(recorder.flushes, ["Hel", "Hello", "Hello world"]);
+    }
+
+    #[test]
+    fn test_playback_pauses_resumes_and_skips() {
+        let state = PlaybackState::Playing;
+        let state = state.on_key(KeyCode::Char(' '));
+        assert_eq!(state, PlaybackState::Paused);
+        let state = state.on_key(KeyCode::Char(' '));
+        assert_eq!(state, PlaybackState::Playing);
+        let state = state.on_key(KeyCode::Enter);
+        assert_eq!(state, PlaybackState::Stopped);
+
+        // Skipping works while paused too, and a stopped reply stays stopped
+        assert_eq!(
+            PlaybackState::Paused.on_key(KeyCode::Char('q')),
+            PlaybackState::Stopped
+        );
+        assert_eq!(
+            PlaybackState::Stopped.on_key(KeyCode::Char(' ')),
+            PlaybackState::Stopped
+        );
+    }
+
+    #[test]
+    fn test_capture_is_downmixed_by_its_channel_count() {
+        let mono = [0.1, 0.2, 0.3];
+        assert_eq!(process_audio(&mono, 16_000, 1), mono);
+
+        let stereo = [0.2, 0.4, -0.5, 0.5];
+        assert_eq!(process_audio(&stereo, 16_000, 2), vec![0.3, 0.0]);
+
+        // A 4-channel frame averages all four, not just the first pair
+        let quad = [0.4, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0];
+        assert_eq!(process_audio(&quad, 16_000, 4), vec![0.1, 1.0]);
+    }
+
+    #[test]
+    fn test_8khz_input_is_upsampled_with_interpolation() {
+        let input = [0.0, 0.5, 1.0, 0.5];
+        let out = resample_to_16khz(&input, 8_000);
+        assert_eq!(out.len(), 8);
+        // New samples fall between their neighbours instead of repeating them
+        assert_eq!(&out[..6], &[0.0, 0.25, 0.5, 0.75, 1.0, 0.75]);
+    }
+
+    #[test]
+    fn test_48khz_input_is_downsampled() {
+        let input: Vec<f32> = (0..48_000).map(|i| i as f32 / 48_000.0).collect();
+        let out = resample_to_16khz(&input, 48_000);
+        assert_eq!(out.len(), 16_000);
+        assert!((out[100] - input[300]).abs() < 1e-6);
+        assert_eq!(resample_to_16khz(&input[..16], 16_000), &input[..16]);
+    }
+
+    #[test]
+    fn test_wake_word_matches_transcribed_phrase() {
+        assert!(matches_wake_word("Hey, Aira!", "hey aira"));
+        assert!(matches_wake_word("okay hey aira, what time", "Hey Aira"));
+        assert!(!matches_wake_word("Hey there, Aira", "hey aira"));
+        assert!(!matches_wake_word("They Airasia", "hey aira"));
+        assert!(!matches_wake_word("[BLANK_AUDIO]", "hey aira"));
+        assert!(!matches_wake_word("hey aira", ""));
+    }
+
+    #[test]
+    fn test_mic_buffer_capacity_scales_with_format() {
+        assert_eq!(mic_buffer_capacity(48_000, 2), 48_000 * 2 * 2);
+    }
+}